@@ -0,0 +1,605 @@
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value as Json};
+use ssa_parser::{
+    diagnostic::{Diagnostic, DiagnosticCode, Severity},
+    events::{tag::TagKind, Event, EventFormat, EventType},
+    file::{File, ReadOptions, WriteOptions},
+    lint::LintConfig,
+    parser::Parser as _,
+    resample::AspectRatio,
+    styles::StyleFormat,
+    timestamp::Timestamp,
+    value::Value,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Exit code of `lint` and `fmt --check` when they find problems.
+const EXIT_PROBLEMS: u8 = 1;
+/// Exit code for files that cannot be read, parsed or written.
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "ssa",
+    version,
+    about = "Inspect and edit ssa and ass subtitles"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Summarise the script info, styles and events
+    Info {
+        /// Subtitle file, `-` for stdin
+        input: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Move every event by an offset such as `1.5s`, `-250ms` or `-0:00:01.00`
+    Shift {
+        input: PathBuf,
+        #[arg(allow_hyphen_values = true)]
+        offset: String,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rescale styles, positions and drawings to another resolution such as `1920x1080`
+    Resample {
+        input: PathBuf,
+        resolution: String,
+        /// How to fit the script when the aspect ratio changes: stretch, letterbox or pillarbox
+        #[arg(long, default_value = "stretch")]
+        aspect: AspectRatio,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Convert between ass, srt, vtt and json, formats are taken from the file extensions
+    Convert {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format, overrides the extension of the output file
+        #[arg(long, value_enum)]
+        to: Option<Format>,
+    },
+    /// Report problems found while parsing and by the lint rules, exits with 1 if there are
+    /// errors
+    Lint {
+        input: PathBuf,
+        /// Rule configuration, JSON for `.json` files and TOML otherwise
+        #[arg(long)]
+        config: Option<PathBuf>,
+        #[arg(long)]
+        json: bool,
+        /// Exit with 1 on warnings too
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+    /// List the fonts used by styles and `\fn` tags and the embedded fonts
+    Fonts {
+        input: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the embedded fonts and graphics to a directory
+    Extract {
+        input: PathBuf,
+        dir: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Append the styles, events and attachments of the other files to the first one
+    Merge {
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Rewrite a script in canonical form
+    Fmt {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only check whether the file is formatted, exits with 1 if it is not
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+enum Format {
+    Ass,
+    Srt,
+    Vtt,
+    /// The serde schema of the library
+    Json,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ass" | "ssa" => Some(Format::Ass),
+            "srt" => Some(Format::Srt),
+            "vtt" => Some(Format::Vtt),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            eprintln!("ssa: {}", error);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(command: Command) -> Result<u8> {
+    match command {
+        Command::Info { input, json } => {
+            let file = read_file(&input)?;
+            let info = info(&file);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                print_info(&info);
+            }
+        }
+        Command::Shift {
+            input,
+            offset,
+            output,
+        } => {
+            let mut file = read_file(&input)?;
+            file.events.shift(parse_offset(&offset)?);
+            write_file(&file, &input, output.as_deref(), None, true)?;
+        }
+        Command::Resample {
+            input,
+            resolution,
+            aspect,
+            output,
+        } => {
+            let (width, height) = parse_resolution(&resolution)?;
+            let mut file = read_file(&input)?;
+            file.resample(width, height, aspect)?;
+            write_file(&file, &input, output.as_deref(), None, true)?;
+        }
+        Command::Convert { input, output, to } => {
+            let file = read_file(&input)?;
+            write_file(&file, &input, output.as_deref(), to, false)?;
+        }
+        Command::Lint {
+            input,
+            config,
+            json,
+            deny_warnings,
+        } => return lint(&input, config.as_deref(), json, deny_warnings),
+        Command::Fonts { input, json } => {
+            let file = read_file(&input)?;
+            let used = used_fonts(&file);
+            let embedded = file
+                .fonts
+                .iter()
+                .map(|font| json!({ "name": font.name, "size": font.data.len() }))
+                .collect::<Vec<_>>();
+            if json {
+                let fonts = json!({ "used": used, "embedded": embedded });
+                println!("{}", serde_json::to_string_pretty(&fonts)?);
+            } else {
+                println!("used:");
+                for font in &used {
+                    println!("  {}", font);
+                }
+                println!("embedded:");
+                for font in file.fonts.iter() {
+                    println!("  {} ({} bytes)", font.name, font.data.len());
+                }
+            }
+        }
+        Command::Extract { input, dir, json } => {
+            let file = read_file(&input)?;
+            let mut paths = file.fonts.extract_to(&dir)?;
+            paths.extend(file.graphics.extract_to(&dir)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&paths)?);
+            } else {
+                for path in paths {
+                    println!("{}", path.display());
+                }
+            }
+        }
+        Command::Merge { inputs, output } => {
+            let mut file = read_file(&inputs[0])?;
+            for input in &inputs[1..] {
+                merge(&mut file, read_file(input)?)?;
+            }
+            write_file(&file, &inputs[0], output.as_deref(), None, true)?;
+        }
+        Command::Fmt {
+            input,
+            output,
+            check,
+        } => {
+            let bytes = read_input(&input)?;
+            let file = File::from_bytes(&bytes)?;
+            let formatted = file.to_bytes_with(&WriteOptions::default())?;
+            if check {
+                if formatted != bytes {
+                    eprintln!("{} is not formatted", input.display());
+                    return Ok(EXIT_PROBLEMS);
+                }
+            } else {
+                write_output(output.as_deref(), &formatted)?;
+            }
+        }
+    }
+    Ok(0)
+}
+
+fn read_input(input: &Path) -> Result<Vec<u8>> {
+    if input == Path::new("-") {
+        let mut bytes = vec![];
+        std::io::stdin().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        std::fs::read(input).map_err(|error| format!("{}: {}", input.display(), error).into())
+    }
+}
+
+fn write_output(output: Option<&Path>, bytes: &[u8]) -> Result<()> {
+    match output {
+        Some(path) if path != Path::new("-") => std::fs::write(path, bytes)?,
+        _ => std::io::stdout().write_all(bytes)?,
+    }
+    Ok(())
+}
+
+fn read_file(input: &Path) -> Result<File> {
+    let bytes = read_input(input)?;
+    let file = match Format::from_path(input).unwrap_or(Format::Ass) {
+        Format::Ass => File::from_bytes(&bytes)?,
+        Format::Json => serde_json::from_slice(&bytes)?,
+        format => {
+            let (text, _, _) = ssa_parser::encoding::detect(&bytes).decode(&bytes);
+            match format {
+                Format::Srt => File::from_srt(text)?,
+                _ => File::from_webvtt(text)?,
+            }
+        }
+    };
+    Ok(file)
+}
+
+/// Writes in the format asked for, else the one of the output or input file. Scripts are
+/// written losslessly when `lossless` is set, so that edits keep the rest of the file as is.
+fn write_file(
+    file: &File,
+    input: &Path,
+    output: Option<&Path>,
+    format: Option<Format>,
+    lossless: bool,
+) -> Result<()> {
+    let format = format
+        .or_else(|| output.and_then(Format::from_path))
+        .or_else(|| Format::from_path(input))
+        .unwrap_or(Format::Ass);
+    let bytes = match format {
+        Format::Ass => {
+            let options = WriteOptions {
+                lossless,
+                ..Default::default()
+            };
+            file.to_bytes_with(&options)?
+        }
+        Format::Srt => file.to_srt().into_bytes(),
+        Format::Vtt => file.to_webvtt().into_bytes(),
+        Format::Json => {
+            let mut json = serde_json::to_vec_pretty(file)?;
+            json.push(b'\n');
+            json
+        }
+    };
+    write_output(output, &bytes)
+}
+
+/// Parses `1.5s`, `-250ms`, `2` (seconds) or a timestamp such as `-0:00:01.50`.
+fn parse_offset(src: &str) -> Result<Timestamp> {
+    let src = src.trim();
+    let (negative, offset) = match src.strip_prefix('-') {
+        Some(offset) => (true, offset),
+        None => (false, src.strip_prefix('+').unwrap_or(src)),
+    };
+    let invalid = || format!("invalid offset {}", src);
+    let offset = if offset.contains(':') {
+        if offset.contains('.') {
+            Timestamp::parse(offset)?
+        } else {
+            Timestamp::parse(&format!("{}.00", offset))?
+        }
+    } else if let Some(millis) = offset.strip_suffix("ms") {
+        Timestamp::from_millis(millis.trim().parse().map_err(|_| invalid())?)
+    } else {
+        let secs = offset.strip_suffix('s').unwrap_or(offset);
+        Timestamp::from_secs_f64(secs.trim().parse().map_err(|_| invalid())?)
+    };
+    Ok(if negative { -offset } else { offset })
+}
+
+/// Parses a resolution such as `1920x1080`.
+fn parse_resolution(src: &str) -> Result<(i64, i64)> {
+    let (width, height) = src
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("invalid resolution {}", src))?;
+    Ok((width.trim().parse()?, height.trim().parse()?))
+}
+
+fn lint(input: &Path, config: Option<&Path>, json: bool, deny_warnings: bool) -> Result<u8> {
+    let config = match config {
+        Some(path) => read_lint_config(path)?,
+        None => LintConfig::default(),
+    };
+    let (file, mut diagnostics) = match Format::from_path(input).unwrap_or(Format::Ass) {
+        Format::Ass => File::from_bytes_lenient(&read_input(input)?, &ReadOptions::default())?,
+        _ => (read_file(input)?, vec![]),
+    };
+    // reported by the configurable duplicate-style rule instead
+    diagnostics.retain(|diagnostic| diagnostic.code != DiagnosticCode::DuplicateStyle);
+    let lints = file.lint(&config);
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.severity)
+            .chain(lints.iter().map(|lint| lint.severity))
+            .filter(|s| *s == severity)
+            .count()
+    };
+    let (errors, warnings) = (count(Severity::Error), count(Severity::Warning));
+    if json {
+        let report = json!({
+            "file": input.display().to_string(),
+            "errors": errors,
+            "warnings": warnings,
+            "diagnostics": diagnostics.iter().map(diagnostic_json).collect::<Vec<_>>(),
+            "lints": lints,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for diagnostic in &diagnostics {
+            println!("{}:{}", input.display(), diagnostic);
+        }
+        for lint in &lints {
+            println!("{}:{}", input.display(), lint);
+        }
+    }
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        Ok(EXIT_PROBLEMS)
+    } else {
+        Ok(0)
+    }
+}
+
+/// Reads a lint configuration, JSON for `.json` files and TOML otherwise.
+fn read_lint_config(path: &Path) -> Result<LintConfig> {
+    let src = std::fs::read_to_string(path)?;
+    if Format::from_path(path) == Some(Format::Json) {
+        Ok(serde_json::from_str(&src)?)
+    } else {
+        Ok(toml::from_str(&src)?)
+    }
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> Json {
+    json!({
+        "severity": diagnostic.severity.to_string(),
+        "code": diagnostic.code.to_string(),
+        "line": diagnostic.line,
+        "column": diagnostic.column,
+        "span": [diagnostic.span.start, diagnostic.span.end],
+        "message": diagnostic.message,
+    })
+}
+
+fn time(event: &Event, format: EventFormat) -> Timestamp {
+    event
+        .get(format)
+        .and_then(Value::as_timestamp)
+        .unwrap_or_default()
+}
+
+fn info(file: &File) -> Json {
+    let script = &file.script;
+    let count = |event_type| {
+        file.events
+            .iter()
+            .filter(|event| event.event_type() == event_type)
+            .count()
+    };
+    let end = file
+        .events
+        .iter()
+        .map(|event| time(event, EventFormat::End))
+        .max()
+        .unwrap_or_default();
+    json!({
+        "title": script.get_title(),
+        "script_type": script.get_script_type().map(|t| t.to_string()),
+        "play_res_x": script.get_play_res_x(),
+        "play_res_y": script.get_play_res_y(),
+        "styles": file.styles.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        "events": {
+            "total": file.events.len(),
+            "dialogue": count(EventType::Dialogue),
+            "comment": count(EventType::Comment),
+        },
+        "duration": end.to_string(),
+        "duration_ms": end.millis(),
+        "fonts": file.fonts.iter().map(|font| &font.name).collect::<Vec<_>>(),
+        "graphics": file.graphics.iter().map(|graphic| &graphic.name).collect::<Vec<_>>(),
+    })
+}
+
+fn print_info(info: &Json) {
+    let text = |key: &str| match &info[key] {
+        Json::Null => "-".to_string(),
+        Json::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    let list = |key: &str| match &info[key] {
+        Json::Array(items) => items
+            .iter()
+            .filter_map(Json::as_str)
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::new(),
+    };
+    println!("Title:       {}", text("title"));
+    println!("Script type: {}", text("script_type"));
+    println!("Resolution:  {}x{}", text("play_res_x"), text("play_res_y"));
+    println!("Styles:      {}", list("styles"));
+    println!(
+        "Events:      {} ({} dialogue, {} comment)",
+        info["events"]["total"], info["events"]["dialogue"], info["events"]["comment"]
+    );
+    println!("Duration:    {}", text("duration"));
+    println!("Fonts:       {}", list("fonts"));
+    println!("Graphics:    {}", list("graphics"));
+}
+
+/// Font names used by styles and `\fn` tags, sorted and without duplicates.
+fn used_fonts(file: &File) -> BTreeSet<String> {
+    let mut fonts = BTreeSet::new();
+    for (_, style) in file.styles.iter() {
+        if let Some(font) = style.get(StyleFormat::Fontname).and_then(Value::as_str) {
+            fonts.insert(font.trim_start_matches('@').to_string());
+        }
+    }
+    for event in file.events.iter() {
+        let Some(text) = event.get(EventFormat::Text).and_then(Value::as_text) else {
+            continue;
+        };
+        for tag in text.tags() {
+            if let TagKind::FontName(Some(font)) = tag.kind() {
+                fonts.insert(font.trim_start_matches('@').to_string());
+            }
+        }
+    }
+    fonts
+}
+
+/// Appends the events of `other` and the styles and attachments whose names are not yet used.
+fn merge(file: &mut File, other: File) -> Result<()> {
+    for (name, style) in other.styles.iter() {
+        if file.styles.get(name).is_none() {
+            let style = style.resolve().to_style(&file.styles);
+            file.styles.add(style)?;
+        }
+    }
+    for event in other.events.iter() {
+        let mut merged = Event::new(event.event_type(), &file.events);
+        for format in file.events.order().clone() {
+            if let Some(value) = event.get(format) {
+                merged.set(format, value.clone());
+            }
+        }
+        file.events.push(merged);
+    }
+    for font in other.fonts.fonts {
+        if file.fonts.find(&font.name).is_none() {
+            file.fonts.push(font);
+        }
+    }
+    for graphic in other.graphics.graphics {
+        if file.graphics.find(&graphic.name).is_none() {
+            file.graphics.push(graphic);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssa_parser::lint::Rule;
+
+    #[test]
+    fn test_parse_offset() -> Result<()> {
+        assert_eq!(parse_offset("1.5s")?, Timestamp::from_centis(150));
+        assert_eq!(parse_offset("-250ms")?, Timestamp::from_centis(-25));
+        assert_eq!(parse_offset("+2")?, Timestamp::from_centis(200));
+        assert_eq!(parse_offset("-0:01:00")?, Timestamp::from_centis(-6000));
+        assert!(parse_offset("soon").is_err());
+        assert_eq!(parse_resolution("1920x1080")?, (1920, 1080));
+        assert!(parse_resolution("1920").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_config() -> Result<()> {
+        let config: LintConfig = toml::from_str(
+            "max-cps = 17\n\n[rules.unused-style]\nenabled = false\n\n\
+             [rules.line-length]\nseverity = \"error\"\nignore-styles = [\"Signs\", \"OP\"]\n",
+        )?;
+        assert_eq!(config.max_cps, 17.0);
+        assert!(!config.rule(Rule::UnusedStyle).enabled);
+        let line_length = config.rule(Rule::LineLength);
+        assert_eq!(line_length.severity, Some(Severity::Error));
+        assert_eq!(line_length.ignore_styles, ["Signs", "OP"]);
+        assert!(toml::from_str::<LintConfig>("max-cps = \"fast\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_srt() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("ssa_cli_lint_{}.srt", std::process::id()));
+        std::fs::write(&path, "1\n00:00:01,000 --> 00:00:03,000\nHello\n")?;
+        let clean = lint(&path, None, false, true);
+        std::fs::write(&path, "1\n00:00:03,000 --> 00:00:01,000\nHello\n")?;
+        let end_before_start = lint(&path, None, false, false);
+        std::fs::remove_file(&path)?;
+        assert_eq!(clean?, 0);
+        assert_eq!(end_before_start?, EXIT_PROBLEMS);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let header =
+            "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\n";
+        let mut signs = File::from_str(format!(
+            "{}Style: Sign,Arial,40\n\n[Events]\nFormat: Layer, Start, End, Style, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Sign,{{\\fnComic Sans}}Sign\n",
+            header
+        ))?;
+        let dialogue = File::from_str(format!(
+            "{}Style: Sign,Verdana,10\nStyle: Main,Verdana,50\n\n[Events]\nFormat: Start, End, Style, Text\nDialogue: 0:00:03.00,0:00:04.00,Main,Hello\n",
+            header
+        ))?;
+        merge(&mut signs, dialogue)?;
+        assert_eq!(signs.styles.iter().count(), 2);
+        assert_eq!(signs.events.len(), 2);
+        assert_eq!(
+            signs.events[1].get(EventFormat::Layer),
+            None,
+            "fields missing in the merged file stay unset"
+        );
+        assert_eq!(time(&signs.events[1], EventFormat::End).centis(), 400);
+        let fonts = used_fonts(&signs).into_iter().collect::<Vec<_>>();
+        assert_eq!(fonts, ["Arial", "Comic Sans", "Verdana"]);
+        assert_eq!(info(&signs)["duration"], "0:00:04.00");
+        Ok(())
+    }
+}
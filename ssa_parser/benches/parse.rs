@@ -0,0 +1,76 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use ssa_parser::{borrowed::FileRef, file::File};
+
+/// A script with as many events as a long series has lines.
+fn corpus(events: usize) -> String {
+    let mut src = String::from(
+        "[Script Info]\nTitle: Bench\nScriptType: v4.00+\nPlayResX: 1920\nPlayResY: 1080\n\n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+         BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+         BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+    );
+    for style in ["Default", "Italics", "Sign", "Song"] {
+        src.push_str(&format!(
+            "Style: {},Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,\
+             0,0,1,3,1,2,40,40,40,1\n",
+            style
+        ));
+    }
+    src.push_str(
+        "\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, \
+         Effect, Text\n",
+    );
+    for i in 0..events {
+        let start = i * 150;
+        let end = start + 120;
+        src.push_str(&format!(
+            "Dialogue: 0,{}:{:02}:{:02}.{:02},{}:{:02}:{:02}.{:02},Default,,0,0,0,,\
+             {{\\pos(960,1000)\\fad(100,100)}}Line number {} with {{\\i1}}some{{\\i0}} text\n",
+            start / 360_000,
+            start / 6000 % 60,
+            start / 100 % 60,
+            start % 100,
+            end / 360_000,
+            end / 6000 % 60,
+            end / 100 % 60,
+            end % 100,
+            i
+        ));
+    }
+    src
+}
+
+fn parse(c: &mut Criterion) {
+    let src = corpus(20_000);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("File::from_str", |b| {
+        b.iter(|| File::from_str(black_box(&src)).unwrap())
+    });
+    group.bench_function("FileRef::parse", |b| {
+        b.iter(|| FileRef::parse(black_box(&src)).unwrap())
+    });
+    group.bench_function("FileRef::parse timings", |b| {
+        b.iter(|| {
+            let file = FileRef::parse(black_box(&src)).unwrap();
+            let mut duration = 0;
+            for event in &file.events {
+                duration += (event.end().unwrap() - event.start().unwrap()).centis();
+            }
+            duration
+        })
+    });
+    group.bench_function("FileRef::into_owned", |b| {
+        b.iter(|| {
+            FileRef::parse(black_box(&src))
+                .unwrap()
+                .into_owned()
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
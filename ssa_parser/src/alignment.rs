@@ -0,0 +1,162 @@
+use crate::version::Version;
+
+/// Where a line is anchored, numbered like the numpad as used by `\an` and V4+ styles.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum Alignment {
+    BottomLeft = 1,
+    #[default]
+    BottomCenter = 2,
+    BottomRight = 3,
+    MiddleLeft = 4,
+    MiddleCenter = 5,
+    MiddleRight = 6,
+    TopLeft = 7,
+    TopCenter = 8,
+    TopRight = 9,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum HorizontalAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum VerticalAlign {
+    #[default]
+    Bottom,
+    Middle,
+    Top,
+}
+
+impl Alignment {
+    pub fn new(horizontal: HorizontalAlign, vertical: VerticalAlign) -> Self {
+        use HorizontalAlign::*;
+        use VerticalAlign::*;
+        match (vertical, horizontal) {
+            (Bottom, Left) => Alignment::BottomLeft,
+            (Bottom, Center) => Alignment::BottomCenter,
+            (Bottom, Right) => Alignment::BottomRight,
+            (Middle, Left) => Alignment::MiddleLeft,
+            (Middle, Center) => Alignment::MiddleCenter,
+            (Middle, Right) => Alignment::MiddleRight,
+            (Top, Left) => Alignment::TopLeft,
+            (Top, Center) => Alignment::TopCenter,
+            (Top, Right) => Alignment::TopRight,
+        }
+    }
+
+    pub fn horizontal(&self) -> HorizontalAlign {
+        match (self.numpad() - 1) % 3 {
+            0 => HorizontalAlign::Left,
+            1 => HorizontalAlign::Center,
+            _ => HorizontalAlign::Right,
+        }
+    }
+
+    pub fn vertical(&self) -> VerticalAlign {
+        match (self.numpad() - 1) / 3 {
+            0 => VerticalAlign::Bottom,
+            1 => VerticalAlign::Middle,
+            _ => VerticalAlign::Top,
+        }
+    }
+
+    pub fn from_numpad(numpad: i64) -> Option<Self> {
+        let alignment = match numpad {
+            1 => Alignment::BottomLeft,
+            2 => Alignment::BottomCenter,
+            3 => Alignment::BottomRight,
+            4 => Alignment::MiddleLeft,
+            5 => Alignment::MiddleCenter,
+            6 => Alignment::MiddleRight,
+            7 => Alignment::TopLeft,
+            8 => Alignment::TopCenter,
+            9 => Alignment::TopRight,
+            _ => return None,
+        };
+        Some(alignment)
+    }
+
+    pub fn numpad(&self) -> i64 {
+        *self as i64
+    }
+
+    /// From the SSA v4 numbering of `\a` and V4 styles: 1-3 at the bottom, plus 4 at the top and
+    /// plus 8 in the middle.
+    pub fn from_legacy(legacy: i64) -> Option<Self> {
+        match legacy {
+            1..=3 => Self::from_numpad(legacy),
+            5..=7 => Self::from_numpad(legacy + 2),
+            9..=11 => Self::from_numpad(legacy - 5),
+            _ => None,
+        }
+    }
+
+    pub fn legacy(&self) -> i64 {
+        let row = match self.vertical() {
+            VerticalAlign::Bottom => 0,
+            VerticalAlign::Top => 4,
+            VerticalAlign::Middle => 8,
+        };
+        self.horizontal() as i64 + 1 + row
+    }
+
+    /// From the numbering styles of `version` use, legacy for V4 and numpad for V4+.
+    pub fn from_version(value: i64, version: Version) -> Option<Self> {
+        match version {
+            Version::V4 => Self::from_legacy(value),
+            Version::V4Plus => Self::from_numpad(value),
+        }
+    }
+
+    pub fn to_version(&self, version: Version) -> i64 {
+        match version {
+            Version::V4 => self.legacy(),
+            Version::V4Plus => self.numpad(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numpad() {
+        for numpad in 1..=9 {
+            assert_eq!(Alignment::from_numpad(numpad).unwrap().numpad(), numpad);
+        }
+        assert_eq!(Alignment::from_numpad(0), None);
+        assert_eq!(Alignment::from_numpad(10), None);
+        for legacy in [1, 2, 3, 5, 6, 7, 9, 10, 11] {
+            assert_eq!(Alignment::from_legacy(legacy).unwrap().legacy(), legacy);
+        }
+        assert_eq!(Alignment::from_legacy(6), Some(Alignment::TopCenter));
+        assert_eq!(Alignment::from_legacy(9), Some(Alignment::MiddleLeft));
+        assert_eq!(Alignment::from_legacy(4), None);
+    }
+
+    #[test]
+    fn test_components() {
+        let alignment = Alignment::new(HorizontalAlign::Right, VerticalAlign::Middle);
+        assert_eq!(alignment, Alignment::MiddleRight);
+        assert_eq!(alignment.to_version(Version::V4Plus), 6);
+        assert_eq!(alignment.to_version(Version::V4), 11);
+        for numpad in 1..=9 {
+            let alignment = Alignment::from_numpad(numpad).unwrap();
+            assert_eq!(
+                Alignment::new(alignment.horizontal(), alignment.vertical()),
+                alignment
+            );
+        }
+        assert_eq!(Alignment::TopLeft.vertical(), VerticalAlign::Top);
+        assert_eq!(Alignment::TopLeft.horizontal(), HorizontalAlign::Left);
+        assert_eq!(
+            Alignment::from_version(7, Version::V4),
+            Some(Alignment::TopRight)
+        );
+    }
+}
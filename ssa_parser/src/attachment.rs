@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+/// Length of the encoded lines written to `[Fonts]` and `[Graphics]` sections.
+pub const LINE_LENGTH: usize = 80;
+
+/// A file embedded in the `[Fonts]` or `[Graphics]` section.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Attachment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    pub fn new(name: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            name: name.into(),
+            data: data.into(),
+        }
+    }
+
+    /// Reads a file to embed, named after the file name of `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_name()
+            .ok_or_else(|| Error::parse_error::<Attachment>(format!("{:?} is no file", path)))?;
+        Ok(Self::new(name.to_string_lossy(), std::fs::read(path)?))
+    }
+
+    /// Writes the data to `dir`. Only the file name part of [`Attachment::name`] is used, so
+    /// that a script cannot write outside of `dir`.
+    pub fn write_to_dir(&self, dir: impl AsRef<Path>) -> crate::Result<PathBuf> {
+        let name = Path::new(&self.name).file_name().ok_or_else(|| {
+            Error::parse_error::<Attachment>(format!("invalid name {}", self.name))
+        })?;
+        let path = dir.as_ref().join(name);
+        std::fs::write(&path, &self.data)?;
+        Ok(path)
+    }
+
+    /// The encoded data split into lines of [`LINE_LENGTH`] characters.
+    pub fn encoded_lines(&self) -> Vec<String> {
+        let encoded = uuencode(&self.data);
+        encoded
+            .as_bytes()
+            .chunks(LINE_LENGTH)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect()
+    }
+}
+
+pub(crate) fn write_all(attachments: &[Attachment], dir: &Path) -> crate::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    attachments
+        .iter()
+        .map(|attachment| attachment.write_to_dir(dir))
+        .collect()
+}
+
+/// Encodes with the UUEncode variant of SSA: every 6 bits become one character by adding 33,
+/// without length prefixes. A trailing group of one or two bytes becomes two or three
+/// characters.
+pub fn uuencode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let src = [
+            group[0],
+            group.get(1).copied().unwrap_or(0),
+            group.get(2).copied().unwrap_or(0),
+        ];
+        let dst = [
+            src[0] >> 2,
+            (src[0] & 0x3) << 4 | src[1] >> 4,
+            (src[1] & 0xF) << 2 | src[2] >> 6,
+            src[2] & 0x3F,
+        ];
+        for c in &dst[..group.len() + 1] {
+            encoded.push((c + 33) as char);
+        }
+    }
+    encoded
+}
+
+/// Decodes [`uuencode`] output, line breaks and other whitespace are skipped.
+pub fn uudecode(src: &str) -> crate::Result<Vec<u8>> {
+    let mut values = Vec::with_capacity(src.len());
+    for c in src.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '!'..='`' => values.push(c as u8 - 33),
+            _ => {
+                return Err(Error::parse_error::<Attachment>(format!(
+                    "invalid character {:?} in encoded data",
+                    c
+                )))
+            }
+        }
+    }
+    if values.len() % 4 == 1 {
+        return Err(Error::parse_error::<Attachment>(
+            "encoded data ends with an incomplete group",
+        ));
+    }
+    let mut data = Vec::with_capacity(values.len() / 4 * 3 + 2);
+    for group in values.chunks(4) {
+        let src = [
+            group[0],
+            group[1],
+            group.get(2).copied().unwrap_or(0),
+            group.get(3).copied().unwrap_or(0),
+        ];
+        let dst = [
+            src[0] << 2 | src[1] >> 4,
+            (src[1] & 0xF) << 4 | src[2] >> 2,
+            (src[2] & 0x3) << 6 | src[3],
+        ];
+        data.extend_from_slice(&dst[..group.len() - 1]);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuencode() -> crate::Result<()> {
+        assert_eq!(uuencode(b"Man"), "47&O");
+        assert_eq!(uuencode(b"Ma"), "47%");
+        assert_eq!(uuencode(b"M"), "41");
+        assert_eq!(uudecode("47&O\n47%")?, b"ManMa");
+        let data = (0..=255).collect::<Vec<u8>>();
+        let attachment = Attachment::new("bytes.bin", data.clone());
+        let lines = attachment.encoded_lines();
+        assert_eq!(lines[0].len(), LINE_LENGTH);
+        assert_eq!(lines.last().unwrap().len(), 342 % LINE_LENGTH);
+        assert_eq!(uudecode(&lines.join("\n"))?, data);
+        assert!(uudecode("47&O4").is_err());
+        assert!(uudecode("47a").is_err());
+        Ok(())
+    }
+}
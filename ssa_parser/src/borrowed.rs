@@ -0,0 +1,436 @@
+//! Parsing that borrows from the source instead of allocating every value.
+//!
+//! [`FileRef::parse`] only splits the source into sections, lines and fields. Values are
+//! parsed on access, or all at once by [`FileRef::into_owned`], so reading a few fields of
+//! many scripts is much cheaper than building a [`File`] for each of them.
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::{
+    attachment::{uudecode, Attachment},
+    error::Error,
+    events::{Event, EventFormat, EventType, Events},
+    file::File,
+    layout::{split_lines, LineLayout, RawText, Section, SectionKind},
+    parser::{Parser, SsaParser},
+    script_info::{Key, ScriptType},
+    styles::{Style, StyleFormat, V4Styles},
+    timestamp::Timestamp,
+    value::Value,
+    version::Version,
+};
+
+/// A script whose fields point into the source it was parsed from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FileRef<'a> {
+    pub version: Version,
+    /// `[Script Info]` properties in source order, comments have the key `;`.
+    pub script: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    pub style_order: Vec<StyleFormat>,
+    pub styles: Vec<StyleRef<'a>>,
+    pub event_order: Vec<EventFormat>,
+    pub events: Vec<EventRef<'a>>,
+    pub fonts: Vec<AttachmentRef<'a>>,
+    pub graphics: Vec<AttachmentRef<'a>>,
+    /// Sections this parser does not know, as their header and lines.
+    unknown: Vec<(&'a str, Vec<&'a str>)>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StyleRef<'a> {
+    line: usize,
+    fields: Vec<(StyleFormat, Cow<'a, str>)>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventRef<'a> {
+    pub event_type: EventType,
+    line: usize,
+    fields: Vec<(EventFormat, Cow<'a, str>)>,
+}
+
+/// An embedded file whose data is still encoded.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AttachmentRef<'a> {
+    pub name: Cow<'a, str>,
+    line: usize,
+    encoded: Vec<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Context {
+    None,
+    ScriptInfo,
+    Styles,
+    Events,
+    Fonts,
+    Graphics,
+    Unknown,
+}
+
+impl<'a> FileRef<'a> {
+    /// Splits `src` without parsing any value. Only the section layout, the `Format:` lines and
+    /// the event types are checked here, everything else is checked when it is accessed.
+    pub fn parse(src: &'a str) -> crate::Result<Self> {
+        let src = src.strip_prefix('\u{FEFF}').unwrap_or(src);
+        let mut file = Self {
+            version: Version::V4Plus,
+            script: vec![],
+            style_order: V4Styles::default().order().to_vec(),
+            styles: vec![],
+            event_order: Events::default().order().clone(),
+            events: vec![],
+            fonts: vec![],
+            graphics: vec![],
+            unknown: vec![],
+        };
+        let mut context = Context::None;
+        let mut lines = split_lines(src).map(|(line, _)| line).zip(1..);
+        while let Some((src, number)) = lines.next() {
+            let line = src.trim();
+            if line.starts_with('[') && line.ends_with(']') {
+                let section = line[1..line.len() - 1].trim().to_lowercase();
+                let mut format = || {
+                    lines.next().ok_or_else(|| {
+                        let error = Error::ParseError {
+                            ty: "header",
+                            msg: format!("missing {} format", section),
+                        };
+                        error.at_line(number)
+                    })
+                };
+                context = match section.as_str() {
+                    "script info" => Context::ScriptInfo,
+                    "v4 styles" | "v4+ styles" => {
+                        let (format, number) = format()?;
+                        file.style_order = File::parse_style_header(format)
+                            .and_then(V4Styles::new)
+                            .map_err(|error| error.at_line(number))?
+                            .order()
+                            .to_vec();
+                        if section == "v4 styles" {
+                            file.version = Version::V4;
+                        }
+                        Context::Styles
+                    }
+                    "events" => {
+                        let (format, number) = format()?;
+                        file.event_order = File::parse_event_header(format)
+                            .map_err(|error| error.at_line(number))?;
+                        Context::Events
+                    }
+                    "fonts" => Context::Fonts,
+                    "graphics" => Context::Graphics,
+                    _ => {
+                        file.unknown.push((src, vec![]));
+                        Context::Unknown
+                    }
+                };
+                continue;
+            }
+            match context {
+                Context::ScriptInfo => file.push_property(line),
+                Context::Styles => {
+                    if let Some(body) = line.strip_prefix("Style:") {
+                        let fields = split_fields(body, file.style_order.len(), false);
+                        file.styles.push(StyleRef {
+                            line: number,
+                            fields: file.style_order.iter().copied().zip(fields).collect(),
+                        });
+                    }
+                }
+                Context::Events => {
+                    let Some(pos) = line.find(':').filter(|_| !line.starts_with(';')) else {
+                        continue;
+                    };
+                    let key = &line[..pos];
+                    let event_type = EventType::from_str(key).map_err(|_| {
+                        let msg = format!("invalid event type {}", key);
+                        Error::parse_error::<EventType>(msg).at_line(number)
+                    })?;
+                    let fields = split_fields(&line[pos + 1..], file.event_order.len(), true);
+                    file.events.push(EventRef {
+                        event_type,
+                        line: number,
+                        fields: file.event_order.iter().copied().zip(fields).collect(),
+                    });
+                }
+                Context::Fonts => push_attachment(&mut file.fonts, "fontname:", line, number),
+                Context::Graphics => push_attachment(&mut file.graphics, "filename:", line, number),
+                Context::Unknown => {
+                    if let Some((_, lines)) = file.unknown.last_mut() {
+                        lines.push(src);
+                    }
+                }
+                Context::None => {}
+            }
+        }
+        Ok(file)
+    }
+
+    fn push_property(&mut self, line: &'a str) {
+        if let Some(comment) = line.strip_prefix(';').or_else(|| line.strip_prefix("!:")) {
+            self.script
+                .push((Key::Comment.as_ref().into(), comment.trim().into()));
+        } else if let Some((key, value)) = line.split_once(':') {
+            self.script.push((key.into(), value.trim().into()));
+        }
+    }
+
+    /// The value of the script info property `key`.
+    pub fn property(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.script
+            .iter()
+            .find(|(k, _)| k == key.as_ref())
+            .map(|(_, value)| value.as_ref())
+    }
+
+    pub fn style(&self, name: &str) -> Option<&StyleRef<'a>> {
+        self.styles.iter().find(|style| style.name() == Some(name))
+    }
+
+    /// Parses every value into a [`File`]. The result has no source layout, writing it
+    /// losslessly gives the canonical output.
+    pub fn into_owned(self) -> crate::Result<File> {
+        let mut parser = SsaParser::default();
+        for (key, value) in &self.script {
+            match Key::from_str(key) {
+                Ok(Key::Comment) => parser.script_info.add_comment(value.as_ref()),
+                Ok(key) => parser.set_property(key, value)?,
+                Err(_) => parser
+                    .script_info
+                    .add_property(key.as_ref(), Value::Str(value.to_string())),
+            }
+        }
+        if parser.script_info.get_script_type().is_none() {
+            parser.script_info.set_script_type(match self.version {
+                Version::V4 => ScriptType::V4,
+                Version::V4Plus => ScriptType::V4Plus,
+            });
+        }
+        let mut styles = V4Styles::new(self.style_order)?;
+        for style in self.styles {
+            styles.add(style.into_owned(&styles)?)?;
+        }
+        let mut events = Events::new(self.event_order);
+        for event in self.events {
+            let event = event.into_owned(&events)?;
+            events.push(event);
+        }
+        let mut file = File {
+            version: self.version,
+            script: parser.script_info,
+            styles,
+            events,
+            ..Default::default()
+        };
+        for font in &self.fonts {
+            file.fonts.push(font.decode()?);
+        }
+        for graphic in &self.graphics {
+            file.graphics.push(graphic.decode()?);
+        }
+        for (header, lines) in self.unknown {
+            let mut section = Section::new(SectionKind::Unknown, Some(RawText::new(header, "\n")));
+            for line in lines {
+                section
+                    .lines
+                    .push(LineLayout::Raw(RawText::new(line, "\n")));
+            }
+            file.layout.sections.push(section);
+        }
+        Ok(file)
+    }
+}
+
+impl<'a> StyleRef<'a> {
+    pub fn name(&self) -> Option<&str> {
+        self.get(StyleFormat::Name)
+    }
+
+    /// The unparsed field, `None` when the line is too short to have it.
+    pub fn get(&self, format: StyleFormat) -> Option<&str> {
+        get(&self.fields, format)
+    }
+
+    pub fn set(&mut self, format: StyleFormat, value: impl Into<Cow<'a, str>>) {
+        set(&mut self.fields, format, value.into())
+    }
+
+    pub fn into_owned(self, styles: &V4Styles) -> crate::Result<Style> {
+        let mut style = Style::new(styles);
+        for (format, value) in &self.fields {
+            let value = format
+                .parse_value(value)
+                .map_err(|error| error.at_line(self.line))?;
+            style.set(*format, value);
+        }
+        Ok(style)
+    }
+}
+
+impl<'a> EventRef<'a> {
+    /// The unparsed field, `None` when the line is too short to have it.
+    pub fn get(&self, format: EventFormat) -> Option<&str> {
+        get(&self.fields, format)
+    }
+
+    pub fn set(&mut self, format: EventFormat, value: impl Into<Cow<'a, str>>) {
+        set(&mut self.fields, format, value.into())
+    }
+
+    pub fn start(&self) -> crate::Result<Timestamp> {
+        self.timestamp(EventFormat::Start)
+    }
+
+    pub fn end(&self) -> crate::Result<Timestamp> {
+        self.timestamp(EventFormat::End)
+    }
+
+    pub fn style(&self) -> Option<&str> {
+        self.get(EventFormat::Style)
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.get(EventFormat::Text)
+    }
+
+    fn timestamp(&self, format: EventFormat) -> crate::Result<Timestamp> {
+        match self.get(format) {
+            Some(src) => Timestamp::parse(src).map_err(|error| error.at_line(self.line)),
+            None => Ok(Timestamp::default()),
+        }
+    }
+
+    pub fn into_owned(self, events: &Events) -> crate::Result<Event> {
+        let mut event = Event::new(self.event_type, events);
+        for (format, value) in &self.fields {
+            let value = format
+                .parse_value(value)
+                .map_err(|error| error.at_line(self.line))?;
+            event.set(*format, value);
+        }
+        Ok(event)
+    }
+}
+
+impl AttachmentRef<'_> {
+    pub fn decode(&self) -> crate::Result<Attachment> {
+        let data = uudecode(&self.encoded.concat()).map_err(|error| error.at_line(self.line))?;
+        Ok(Attachment::new(self.name.as_ref(), data))
+    }
+}
+
+fn push_attachment<'a>(
+    attachments: &mut Vec<AttachmentRef<'a>>,
+    prefix: &str,
+    line: &'a str,
+    number: usize,
+) {
+    match line.strip_prefix(prefix) {
+        Some(name) => attachments.push(AttachmentRef {
+            name: name.trim().into(),
+            line: number,
+            encoded: vec![],
+        }),
+        None => {
+            if let Some(attachment) = attachments.last_mut() {
+                attachment.encoded.push(line);
+            }
+        }
+    }
+}
+
+/// Splits the part of a style or event line after its key like the owned parser does.
+fn split_fields(body: &str, n: usize, keep_tail: bool) -> impl Iterator<Item = Cow<'_, str>> {
+    let body = body.trim();
+    let fields = if keep_tail {
+        body.splitn(n, ',').collect::<Vec<_>>()
+    } else {
+        body.split(',').collect()
+    };
+    fields.into_iter().map(Cow::Borrowed)
+}
+
+fn get<'s, F: PartialEq>(fields: &'s [(F, Cow<'_, str>)], format: F) -> Option<&'s str> {
+    fields
+        .iter()
+        .find(|(f, _)| f == &format)
+        .map(|(_, value)| value.as_ref())
+}
+
+fn set<'a, F: PartialEq>(fields: &mut Vec<(F, Cow<'a, str>)>, format: F, value: Cow<'a, str>) {
+    match fields.iter_mut().find(|(f, _)| f == &format) {
+        Some((_, v)) => *v = value,
+        None => fields.push((format, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::text::Text;
+
+    const SOURCE: &str = "[Script Info]
+; made by hand
+Title: Borrowed
+ScriptType: v4.00+
+PlayResX: 1920
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour
+Style: Default,Arial,60,&H00FFFFFF
+Style: Sign,Verdana,40,&H000000FF
+
+[Events]
+Format: Layer, Start, End, Style, Text
+Dialogue: 0,0:00:01.00,0:00:02.50,Default,{\\b1}one, two
+Comment: 0,0:00:03.00,0:00:04.00,Sign,three
+
+[Fonts]
+fontname: a_0.ttf
+47&O
+
+[Aegisub Project Garbage]
+Active Line: 1
+";
+
+    #[test]
+    fn test_borrowed_parse() -> crate::Result<()> {
+        let mut file = FileRef::parse(SOURCE)?;
+        assert_eq!(file.property("Title"), Some("Borrowed"));
+        assert_eq!(
+            file.style("Sign")
+                .and_then(|s| s.get(StyleFormat::Fontname)),
+            Some("Verdana")
+        );
+        let event = &file.events[0];
+        assert_eq!(event.start()?, Timestamp::from_centis(100));
+        assert_eq!(event.end()?, Timestamp::from_centis(250));
+        assert_eq!(event.text(), Some("{\\b1}one, two"));
+        assert!(matches!(file.events[1].fields[4].1, Cow::Borrowed(_)));
+        assert_eq!(file.into_owned()?, File::from_str(SOURCE)?);
+
+        file = FileRef::parse(SOURCE)?;
+        file.events[1].set(EventFormat::Text, format!("{}!", "four"));
+        let owned = file.into_owned()?;
+        assert_eq!(owned.to_string()?, {
+            let mut expected = File::from_str(SOURCE)?;
+            expected.events[1].set(EventFormat::Text, Text::parse("four!")?);
+            expected.to_string()?
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_borrowed_errors() -> crate::Result<()> {
+        let file = FileRef::parse("[Events]\nFormat: Start\nDialogue: x\n")?;
+        assert_eq!(file.events[0].start().unwrap_err().line(), Some(3));
+        assert_eq!(file.into_owned().unwrap_err().line(), Some(3));
+        let error = FileRef::parse("[Events]\nFormat: Start\nSpeech: 1\n").unwrap_err();
+        assert_eq!(error.line(), Some(3));
+        assert_eq!(FileRef::parse("[Events]").unwrap_err().line(), Some(1));
+        Ok(())
+    }
+}
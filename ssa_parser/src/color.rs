@@ -0,0 +1,418 @@
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+use crate::{error::Error, parser::Parser};
+
+/// How a colour was written, so that it is serialised back the same way.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum ColorFormat {
+    /// `&HAABBGGRR`, as found in V4+ styles.
+    #[default]
+    Style,
+    /// `&HBBGGRR&`, as found in override tags. The alpha is only written when not opaque.
+    Tag,
+    /// The decimal value of `AABBGGRR`, as written by old V4 tools.
+    Decimal,
+}
+
+/// A colour as used by styles and colour tags. `a` is the ASS alpha, which counts transparency:
+/// `0` is opaque and `255` invisible. Comparisons ignore the [`ColorFormat`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+    pub format: ColorFormat,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            r,
+            g,
+            b,
+            a,
+            format: ColorFormat::Style,
+        }
+    }
+
+    /// Creates a colour from conventional RGBA where an alpha of `255` is opaque.
+    pub const fn from_rgba(r: u8, g: u8, b: u8, alpha: u8) -> Self {
+        Self::new(r, g, b, 255 - alpha)
+    }
+
+    /// The colour as conventional RGBA where an alpha of `255` is opaque.
+    pub fn rgba(&self) -> (u8, u8, u8, u8) {
+        (self.r, self.g, self.b, self.opacity())
+    }
+
+    pub fn opacity(&self) -> u8 {
+        255 - self.a
+    }
+
+    pub fn with_format(mut self, format: ColorFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The colour packed as `0xAABBGGRR`, the layout used by the decimal form of SSA.
+    pub fn to_abgr(&self) -> u32 {
+        u32::from_be_bytes([self.a, self.b, self.g, self.r])
+    }
+
+    pub fn from_abgr(abgr: u32) -> Self {
+        let [a, b, g, r] = abgr.to_be_bytes();
+        Self::new(r, g, b, a)
+    }
+
+    /// `#RRGGBB`, or `#RRGGBBAA` with conventional alpha when the colour is not opaque.
+    pub fn to_hex(&self) -> String {
+        match self.opacity() {
+            255 => format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b),
+            alpha => format!("#{:02X}{:02X}{:02X}{:02X}", self.r, self.g, self.b, alpha),
+        }
+    }
+
+    /// Parses `#RGB`, `#RRGGBB` or `#RRGGBBAA`.
+    pub fn from_hex(src: &str) -> crate::Result<Self> {
+        let invalid = || Error::parse_error::<Color>(format!("invalid hex color {}", src));
+        let hex = src.trim().strip_prefix('#').ok_or_else(invalid)?;
+        if !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        match hex.len() {
+            3 => {
+                let digit = |i: usize| {
+                    u8::from_str_radix(&hex[i..=i], 16)
+                        .map(|d| d * 17)
+                        .map_err(|_| invalid())
+                };
+                Ok(Self::from_rgba(digit(0)?, digit(1)?, digit(2)?, 255))
+            }
+            6 => Ok(Self::from_rgba(byte(0)?, byte(2)?, byte(4)?, 255)),
+            8 => Ok(Self::from_rgba(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// A CSS colour, `#RRGGBB` when opaque and `rgba()` otherwise.
+    pub fn to_css(&self) -> String {
+        match self.opacity() {
+            255 => self.to_hex(),
+            alpha => {
+                let alpha = format!("{:.3}", alpha as f64 / 255.0);
+                let alpha = alpha.trim_end_matches('0').trim_end_matches('.');
+                format!("rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha)
+            }
+        }
+    }
+
+    /// Parses a CSS colour given as hex, `rgb()` or `rgba()`.
+    pub fn from_css(src: &str) -> crate::Result<Self> {
+        let src = src.trim();
+        if src.starts_with('#') {
+            return Self::from_hex(src);
+        }
+        let invalid = || Error::parse_error::<Color>(format!("invalid css color {}", src));
+        let args = src
+            .strip_prefix("rgba(")
+            .or_else(|| src.strip_prefix("rgb("))
+            .and_then(|args| args.strip_suffix(')'))
+            .ok_or_else(invalid)?;
+        let args: Vec<_> = args
+            .split([',', '/', ' '])
+            .filter(|a| !a.is_empty())
+            .collect();
+        let channel = |arg: &str| -> crate::Result<u8> {
+            match arg.strip_suffix('%') {
+                Some(percent) => percent
+                    .parse::<f64>()
+                    .map(|p| (p.clamp(0.0, 100.0) * 2.55).round() as u8)
+                    .map_err(|_| invalid()),
+                None => arg
+                    .parse::<f64>()
+                    .map(|c| c.clamp(0.0, 255.0).round() as u8)
+                    .map_err(|_| invalid()),
+            }
+        };
+        let alpha = match args.get(3) {
+            Some(alpha) => match alpha.strip_suffix('%') {
+                Some(_) => channel(alpha)?,
+                None => alpha
+                    .parse::<f64>()
+                    .map(|a| (a.clamp(0.0, 1.0) * 255.0).round() as u8)
+                    .map_err(|_| invalid())?,
+            },
+            None => 255,
+        };
+        match args[..] {
+            [r, g, b] | [r, g, b, _] => Ok(Self::from_rgba(
+                channel(r)?,
+                channel(g)?,
+                channel(b)?,
+                alpha,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl PartialEq for Color {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_abgr() == other.to_abgr()
+    }
+}
+
+impl Eq for Color {}
+
+impl Hash for Color {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_abgr().hash(state);
+    }
+}
+
+impl Parser for Color {
+    /// Accepts `&HAABBGGRR`, the tag form `&HBBGGRR&` and the decimal form found in SSA files.
+    /// Hex values may have fewer digits, as renderers pad them with zeros.
+    fn parse(src: &str) -> crate::Result<Self> {
+        let invalid = || Error::parse_error::<Color>(format!("invalid color {}", src));
+        let trimmed = src.trim();
+        let hex = trimmed.strip_prefix('&').unwrap_or(trimmed);
+        let Some(hex) = hex.strip_prefix(['H', 'h']) else {
+            let decimal = trimmed.parse::<i64>().map_err(|_| invalid())?;
+            return Ok(Color::from_abgr(decimal as u32).with_format(ColorFormat::Decimal));
+        };
+        let (hex, format) = match hex.strip_suffix('&') {
+            Some(hex) => (hex, ColorFormat::Tag),
+            None => (hex, ColorFormat::Style),
+        };
+        if hex.is_empty() || hex.len() > 8 {
+            return Err(invalid());
+        }
+        let abgr = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        Ok(Color::from_abgr(abgr).with_format(format))
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.format {
+            ColorFormat::Style => write!(f, "&H{:08X}", self.to_abgr()),
+            ColorFormat::Tag if self.a == 0 => {
+                write!(f, "&H{:02X}{:02X}{:02X}&", self.b, self.g, self.r)
+            }
+            ColorFormat::Tag => write!(f, "&H{:08X}&", self.to_abgr()),
+            ColorFormat::Decimal => write!(f, "{}", self.to_abgr()),
+        }
+    }
+}
+
+/// The `YCbCr Matrix` of a script: the matrix renderers use to turn the RGB colours of the script
+/// into the YCbCr of the video. `TV` matrices use limited range, `PC` matrices full range.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum YCbCrMatrix {
+    /// Colours are used as they are.
+    None,
+    Tv601,
+    Pc601,
+    Tv709,
+    Pc709,
+    TvFcc,
+    PcFcc,
+    Tv240M,
+    Pc240M,
+}
+
+impl YCbCrMatrix {
+    /// The luma coefficients `Kr` and `Kb` and whether the range is full.
+    fn coefficients(&self) -> Option<(f64, f64, bool)> {
+        match self {
+            YCbCrMatrix::None => None,
+            YCbCrMatrix::Tv601 => Some((0.299, 0.114, false)),
+            YCbCrMatrix::Pc601 => Some((0.299, 0.114, true)),
+            YCbCrMatrix::Tv709 => Some((0.2126, 0.0722, false)),
+            YCbCrMatrix::Pc709 => Some((0.2126, 0.0722, true)),
+            YCbCrMatrix::TvFcc => Some((0.3, 0.11, false)),
+            YCbCrMatrix::PcFcc => Some((0.3, 0.11, true)),
+            YCbCrMatrix::Tv240M => Some((0.212, 0.087, false)),
+            YCbCrMatrix::Pc240M => Some((0.212, 0.087, true)),
+        }
+    }
+
+    /// Converts a colour meant for this matrix into one that ends up as the same YCbCr value
+    /// under `to`, so that it looks the same on screen. Colours are kept when either matrix is
+    /// [`YCbCrMatrix::None`].
+    pub fn convert(&self, color: Color, to: YCbCrMatrix) -> Color {
+        let (Some(from), Some(to)) = (self.coefficients(), to.coefficients()) else {
+            return color;
+        };
+        let [y, cb, cr] = to_ycbcr([color.r, color.g, color.b], from);
+        let [r, g, b] = to_rgb([y, cb, cr], to);
+        Color { r, g, b, ..color }
+    }
+}
+
+/// RGB to 8 bit YCbCr, unrounded.
+fn to_ycbcr(rgb: [u8; 3], (kr, kb, full): (f64, f64, bool)) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+    let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+    let pb = (b - y) / (2.0 * (1.0 - kb));
+    let pr = (r - y) / (2.0 * (1.0 - kr));
+    if full {
+        [y * 255.0, 128.0 + pb * 255.0, 128.0 + pr * 255.0]
+    } else {
+        [16.0 + y * 219.0, 128.0 + pb * 224.0, 128.0 + pr * 224.0]
+    }
+}
+
+fn to_rgb([y, cb, cr]: [f64; 3], (kr, kb, full): (f64, f64, bool)) -> [u8; 3] {
+    let (y, pb, pr) = if full {
+        (y / 255.0, (cb - 128.0) / 255.0, (cr - 128.0) / 255.0)
+    } else {
+        (
+            (y - 16.0) / 219.0,
+            (cb - 128.0) / 224.0,
+            (cr - 128.0) / 224.0,
+        )
+    };
+    let r = y + 2.0 * (1.0 - kr) * pr;
+    let b = y + 2.0 * (1.0 - kb) * pb;
+    let g = (y - kr * r - kb * b) / (1.0 - kr - kb);
+    [r, g, b].map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+impl Display for YCbCrMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            YCbCrMatrix::None => "None",
+            YCbCrMatrix::Tv601 => "TV.601",
+            YCbCrMatrix::Pc601 => "PC.601",
+            YCbCrMatrix::Tv709 => "TV.709",
+            YCbCrMatrix::Pc709 => "PC.709",
+            YCbCrMatrix::TvFcc => "TV.FCC",
+            YCbCrMatrix::PcFcc => "PC.FCC",
+            YCbCrMatrix::Tv240M => "TV.240M",
+            YCbCrMatrix::Pc240M => "PC.240M",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Parser for YCbCrMatrix {
+    fn parse(s: &str) -> crate::Result<Self> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "NONE" => Ok(YCbCrMatrix::None),
+            "TV.601" => Ok(YCbCrMatrix::Tv601),
+            "PC.601" => Ok(YCbCrMatrix::Pc601),
+            "TV.709" => Ok(YCbCrMatrix::Tv709),
+            "PC.709" => Ok(YCbCrMatrix::Pc709),
+            "TV.FCC" => Ok(YCbCrMatrix::TvFcc),
+            "PC.FCC" => Ok(YCbCrMatrix::PcFcc),
+            "TV.240M" => Ok(YCbCrMatrix::Tv240M),
+            "PC.240M" => Ok(YCbCrMatrix::Pc240M),
+            _ => Err(Error::parse_error::<YCbCrMatrix>(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() -> crate::Result<()> {
+        assert_eq!(Color::parse("&H00FFFFFF")?, Color::new(255, 255, 255, 0));
+        assert_eq!(Color::parse("&H0000FF&")?, Color::new(255, 0, 0, 0));
+        assert_eq!(
+            Color::parse("&H80112233")?,
+            Color::new(0x33, 0x22, 0x11, 0x80)
+        );
+        assert_eq!(Color::parse("16777215")?, Color::new(255, 255, 255, 0));
+        assert_eq!(Color::parse("&HFF&")?, Color::new(255, 0, 0, 0));
+        assert!(Color::parse("&Hxyz").is_err());
+        assert!(Color::parse("white").is_err());
+        assert!(Color::parse("&H123456789").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_format() -> crate::Result<()> {
+        for src in [
+            "&H80112233",
+            "&H00FFFFFF",
+            "&H0000FF&",
+            "&H80FFFFFF&",
+            "255",
+        ] {
+            assert_eq!(Color::parse(src)?.to_string(), src);
+        }
+        let mut color = Color::parse("&H0000FF&")?;
+        color.g = 0x80;
+        assert_eq!(color.to_string(), "&H0080FF&");
+        assert_eq!(Color::new(1, 2, 3, 0).to_string(), "&H00030201");
+        Ok(())
+    }
+
+    #[test]
+    fn test_alpha() {
+        let color = Color::from_rgba(255, 128, 0, 255);
+        assert_eq!(color.a, 0);
+        assert_eq!(color.rgba(), (255, 128, 0, 255));
+        let color = Color::new(0, 0, 0, 0xFF);
+        assert_eq!(color.opacity(), 0);
+    }
+
+    #[test]
+    fn test_hex_and_css() -> crate::Result<()> {
+        let color = Color::parse("&H000080FF")?;
+        assert_eq!(color.to_hex(), "#FF8000");
+        assert_eq!(color.to_css(), "#FF8000");
+        assert_eq!(Color::from_hex("#FF8000")?, color);
+        assert_eq!(
+            Color::from_hex("#f80")?,
+            Color::from_rgba(0xFF, 0x88, 0, 255)
+        );
+        let translucent = Color::parse("&H800080FF")?;
+        assert_eq!(translucent.to_hex(), "#FF80007F");
+        assert_eq!(translucent.to_css(), "rgba(255, 128, 0, 0.498)");
+        assert_eq!(Color::from_hex("#FF80007F")?, translucent);
+        assert_eq!(Color::from_css("rgba(255, 128, 0, 0.498)")?, translucent);
+        assert_eq!(Color::from_css("rgb(255 128 0)")?, color);
+        assert!(Color::from_hex("#FF80").is_err());
+        assert!(Color::from_css("red").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ycbcr_matrix() -> crate::Result<()> {
+        for matrix in [
+            "None", "TV.601", "PC.601", "TV.709", "PC.709", "TV.FCC", "PC.240M",
+        ] {
+            assert_eq!(YCbCrMatrix::parse(matrix)?.to_string(), matrix);
+        }
+        assert_eq!(YCbCrMatrix::parse("tv.709")?, YCbCrMatrix::Tv709);
+        assert!(YCbCrMatrix::parse("TV.2020").is_err());
+
+        let color = Color::parse("&H80FF8000&")?;
+        let converted = YCbCrMatrix::Tv601.convert(color, YCbCrMatrix::Tv709);
+        assert_eq!(converted.to_string(), "&H80FF7B00&");
+        let skin = Color::new(200, 120, 60, 0);
+        let converted = YCbCrMatrix::Tv601.convert(skin, YCbCrMatrix::Tv709);
+        assert_eq!(converted.rgba(), (208, 124, 56, 255));
+        assert_eq!(
+            YCbCrMatrix::Tv709.convert(converted, YCbCrMatrix::Tv601),
+            skin
+        );
+        // grays keep their value between matrices of the same range
+        let gray = Color::new(100, 100, 100, 0);
+        assert_eq!(YCbCrMatrix::Tv601.convert(gray, YCbCrMatrix::Tv709), gray);
+        assert_eq!(
+            YCbCrMatrix::Tv601.convert(gray, YCbCrMatrix::Pc601),
+            Color::new(102, 102, 102, 0)
+        );
+        assert_eq!(YCbCrMatrix::None.convert(color, YCbCrMatrix::Tv709), color);
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Conversion between SSA v4 and ASS v4+ scripts, see [`File::convert_to`].
+//!
+//! The outline colour of V4+ styles is the tertiary colour of V4 ones, and style alignments
+//! and alignment tags are renumbered between legacy `\a` and numpad `\an` values. Fields that
+//! only one of the versions has are dropped or filled with their default. Downgrading reports
+//! every field dropped with a value other than its default as a [`Loss`], as well as colour
+//! alpha, which V4 only has as the `AlphaLevel` of a style, and override tags other than the
+//! `\b`, `\i`, `\fn`, `\fs`, `\fe`, `\c`, `\a`, `\k` and `\r` of SSA v4. Such tags are kept
+//! in the text.
+
+use std::fmt::Display;
+
+use crate::{
+    alignment::Alignment,
+    events::{tag::TagKind, EventFormat, Events},
+    file::File,
+    script_info::ScriptType,
+    styles::{StyleFormat, V4Styles},
+    value::Value,
+    version::Version,
+};
+
+const V4_STYLE_ORDER: [StyleFormat; 18] = [
+    StyleFormat::Name,
+    StyleFormat::Fontname,
+    StyleFormat::Fontsize,
+    StyleFormat::PrimaryColour,
+    StyleFormat::SecondaryColour,
+    StyleFormat::TertiaryColour,
+    StyleFormat::BackColour,
+    StyleFormat::Bold,
+    StyleFormat::Italic,
+    StyleFormat::BorderStyle,
+    StyleFormat::Outline,
+    StyleFormat::Shadow,
+    StyleFormat::Alignment,
+    StyleFormat::MarginL,
+    StyleFormat::MarginR,
+    StyleFormat::MarginV,
+    StyleFormat::AlphaLevel,
+    StyleFormat::Encoding,
+];
+
+const V4_EVENT_ORDER: [EventFormat; 10] = [
+    EventFormat::Marked,
+    EventFormat::Start,
+    EventFormat::End,
+    EventFormat::Style,
+    EventFormat::Name,
+    EventFormat::MarginL,
+    EventFormat::MarginR,
+    EventFormat::MarginV,
+    EventFormat::Effect,
+    EventFormat::Text,
+];
+
+const COLOR_FIELDS: [StyleFormat; 4] = [
+    StyleFormat::PrimaryColour,
+    StyleFormat::SecondaryColour,
+    StyleFormat::OutlineColour,
+    StyleFormat::BackColour,
+];
+
+/// Style fields without counterpart in V4.
+const V4_PLUS_STYLE_FIELDS: [StyleFormat; 6] = [
+    StyleFormat::Underline,
+    StyleFormat::StrikeOut,
+    StyleFormat::ScaleX,
+    StyleFormat::ScaleY,
+    StyleFormat::Spacing,
+    StyleFormat::Angle,
+];
+
+/// Something a V4 script cannot express.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Loss {
+    /// Position of the event in [`Events`], `None` for styles.
+    pub event: Option<usize>,
+    /// The style of the event or the style losing a field.
+    pub style: String,
+    pub message: String,
+}
+
+impl Display for Loss {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.event {
+            Some(position) => write!(f, "event {}", position)?,
+            None => write!(f, "style {}", self.style)?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub(crate) fn convert_to(file: &mut File, version: Version) -> crate::Result<Vec<Loss>> {
+    if file.version == version {
+        return Ok(vec![]);
+    }
+    let mut losses = vec![];
+    convert_styles(&mut file.styles, version, &mut losses)?;
+    convert_events(&mut file.events, version, &mut losses);
+    file.script.set_script_type(match version {
+        Version::V4 => ScriptType::V4,
+        Version::V4Plus => ScriptType::V4Plus,
+    });
+    file.version = version;
+    Ok(losses)
+}
+
+fn convert_styles(
+    styles: &mut V4Styles,
+    version: Version,
+    losses: &mut Vec<Loss>,
+) -> crate::Result<()> {
+    let (order, from, to) = match version {
+        Version::V4 => (
+            V4_STYLE_ORDER.to_vec(),
+            StyleFormat::OutlineColour,
+            StyleFormat::TertiaryColour,
+        ),
+        Version::V4Plus => (
+            V4Styles::default().order().to_vec(),
+            StyleFormat::TertiaryColour,
+            StyleFormat::OutlineColour,
+        ),
+    };
+    let mut colors = vec![];
+    for (name, style) in styles.iter() {
+        colors.push(style.get(from).cloned());
+        if version != Version::V4 {
+            continue;
+        }
+        for format in V4_PLUS_STYLE_FIELDS {
+            match style.get(format) {
+                Some(value) if *value != format.default_value() => losses.push(Loss {
+                    event: None,
+                    style: name.to_string(),
+                    message: format!("{} {} is dropped", format, value),
+                }),
+                _ => {}
+            }
+        }
+        for format in COLOR_FIELDS {
+            match style.get(format).and_then(Value::as_color) {
+                Some(color) if color.a != 0 => losses.push(Loss {
+                    event: None,
+                    style: name.to_string(),
+                    message: format!("{} alpha {} is dropped", format, color.a),
+                }),
+                _ => {}
+            }
+        }
+    }
+    styles.set_order(order)?;
+    for ((_, style), color) in styles.iter_mut().zip(colors) {
+        if let Some(color) = color {
+            style.set(to, color);
+        }
+        if let Some(alignment) = style
+            .get_mut(StyleFormat::Alignment)
+            .and_then(Value::as_int_mut)
+        {
+            *alignment = renumber(*alignment, version);
+        }
+    }
+    Ok(())
+}
+
+fn convert_events(events: &mut Events, version: Version, losses: &mut Vec<Loss>) {
+    for (position, event) in events.iter_mut().enumerate() {
+        let style = event
+            .get(EventFormat::Style)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut loss = |message| {
+            losses.push(Loss {
+                event: Some(position),
+                style: style.clone(),
+                message,
+            })
+        };
+        let layer = event.get(EventFormat::Layer).and_then(Value::as_int);
+        match layer {
+            Some(layer) if layer != 0 && version == Version::V4 => {
+                loss(format!("layer {} is dropped", layer))
+            }
+            _ => {}
+        }
+        let Some(text) = event
+            .get_mut(EventFormat::Text)
+            .and_then(Value::as_text_mut)
+        else {
+            continue;
+        };
+        if version == Version::V4 {
+            for tag in text.tags().filter(|tag| !in_v4(tag.kind())) {
+                loss(format!("tag {} is not supported", tag));
+            }
+        }
+        for tag in text.tags_mut() {
+            let kind = match (version, tag.kind()) {
+                (Version::V4, TagKind::Alignment(value)) => {
+                    TagKind::LegacyAlignment(value.map(|value| renumber(value, version)))
+                }
+                (Version::V4Plus, TagKind::LegacyAlignment(value)) => {
+                    TagKind::Alignment(value.map(|value| renumber(value, version)))
+                }
+                _ => continue,
+            };
+            *tag.kind_mut() = kind;
+        }
+    }
+    let order = match version {
+        Version::V4 => V4_EVENT_ORDER.to_vec(),
+        Version::V4Plus => Events::default().order().clone(),
+    };
+    events.set_order(order);
+}
+
+fn in_v4(kind: &TagKind) -> bool {
+    match kind {
+        TagKind::Color { color, .. } => color.is_none_or(|color| color.a == 0),
+        TagKind::Bold(_)
+        | TagKind::Italic(_)
+        | TagKind::FontName(_)
+        | TagKind::FontSize(_)
+        | TagKind::FontEncoding(_)
+        | TagKind::Alignment(_)
+        | TagKind::LegacyAlignment(_)
+        | TagKind::Karaoke { .. }
+        | TagKind::Reset(_)
+        | TagKind::Unknown(_) => true,
+        _ => false,
+    }
+}
+
+/// Renumbers an alignment for `version`, leaving values that are invalid as they are.
+fn renumber(value: i64, version: Version) -> i64 {
+    let source = match version {
+        Version::V4 => Version::V4Plus,
+        Version::V4Plus => Version::V4,
+    };
+    Alignment::from_version(value, source).map_or(value, |alignment| alignment.to_version(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{events::EventFormat, file::WriteOptions, version::Version};
+
+    #[test]
+    fn test_convert_to() -> crate::Result<()> {
+        let src = "[Script Info]
+ScriptType: v4.00+
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00112233,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1
+Style: Sign,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,1,0,120,100,0,0,1,2,2,8,10,10,10,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\an5}Hello
+Dialogue: 1,0:00:01.00,0:00:02.00,Sign,,0,0,0,,{\\an7\\b1}Sign
+";
+        let mut file = crate::file::File::from_str(src)?;
+        let losses = file.convert_to(Version::V4)?;
+        assert_eq!(
+            losses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "style Sign: Underline 1 is dropped",
+                "style Sign: ScaleX 120 is dropped",
+                "event 1: layer 1 is dropped",
+            ]
+        );
+        let v4 = "[Script Info]
+ScriptType: v4.00
+
+[V4 Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, TertiaryColour, BackColour, Bold, Italic, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, AlphaLevel, Encoding
+Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00112233,&H00000000,0,0,1,2,2,2,10,10,10,10,1
+Style: Sign,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,1,2,2,6,10,10,10,10,1
+
+[Events]
+Format: Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: Marked=0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\a10}Hello
+Dialogue: Marked=0,0:00:01.00,0:00:02.00,Sign,,0,0,0,,{\\a5\\b1}Sign
+";
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, v4);
+
+        let losses = file.convert_to(Version::V4Plus)?;
+        assert!(losses.is_empty());
+        let v4_plus = file.to_string_with(&WriteOptions::lossless())?;
+        assert!(v4_plus.contains("[V4+ Styles]"));
+        assert!(v4_plus.contains(
+            "Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00112233,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1"
+        ));
+        assert!(v4_plus.contains("Dialogue: 0,0:00:01.00,0:00:02.00,Sign,,0,0,0,,{\\an7\\b1}Sign"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_to_reports_tags_and_alpha() -> crate::Result<()> {
+        let src = "[Script Info]
+ScriptType: v4.00+
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour
+Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H80000000
+
+[Events]
+Format: Layer, Start, End, Style, Text
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,{\\b1\\blur2\\c&HFF&\\1c&H800000FF&}a{\\frx10\\t(\\fscx50)}b
+Dialogue: 0,0:00:02.00,0:00:03.00,Default,{\\clip(0,0,10,10)\\p1}m 0 0 l 1 1
+";
+        let mut file = crate::file::File::from_str(src)?;
+        let losses = file.convert_to(Version::V4)?;
+        assert_eq!(
+            losses.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "style Default: BackColour alpha 128 is dropped",
+                "event 0: tag \\blur2 is not supported",
+                "event 0: tag \\1c&H800000FF& is not supported",
+                "event 0: tag \\frx10 is not supported",
+                "event 0: tag \\t(\\fscx50) is not supported",
+                "event 1: tag \\clip(0,0,10,10) is not supported",
+                "event 1: tag \\p1 is not supported",
+            ]
+        );
+        let text = file.events[1].get(EventFormat::Text).unwrap();
+        assert_eq!(text.to_string(), "{\\clip(0,0,10,10)\\p1}m 0 0 l 1 1");
+        Ok(())
+    }
+}
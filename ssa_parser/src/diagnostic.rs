@@ -0,0 +1,61 @@
+use std::fmt::Display;
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display, strum::EnumString, strum::VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum DiagnosticCode {
+    /// A script info property whose value cannot be parsed.
+    InvalidProperty,
+    /// A styles or events section without `Format:` line.
+    MissingFormat,
+    /// A `Format:` line naming unknown fields.
+    InvalidFormat,
+    /// A `Style:` line with a malformed field.
+    InvalidStyle,
+    /// An event line with a malformed field.
+    InvalidEvent,
+    /// A line in the events section that is not a known event type.
+    UnknownEventType,
+    /// A style or event line with fewer fields than its `Format:` line.
+    MissingFields,
+    /// A style whose name is already used by an earlier style.
+    DuplicateStyle,
+    /// A `[Fonts]` or `[Graphics]` attachment whose data cannot be decoded.
+    InvalidAttachment,
+}
+
+/// A problem found while parsing, located by its 1-based line and column as well as the byte
+/// range in the decoded source.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub span: Range<usize>,
+    pub code: DiagnosticCode,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.line, self.column, self.severity, self.code, self.message
+        )
+    }
+}
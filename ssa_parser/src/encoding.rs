@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::error::Error;
+
+/// Detects the encoding of `bytes` from its byte order mark, falling back to UTF-8 when the
+/// bytes are valid UTF-8 and to statistical sniffing otherwise.
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Decodes `bytes`, returning the text without byte order mark and whether one was present.
+pub(crate) fn decode<'a>(
+    bytes: &'a [u8],
+    encoding: &'static Encoding,
+) -> crate::Result<(Cow<'a, str>, bool)> {
+    let bom =
+        matches!(Encoding::for_bom(bytes), Some((bom_encoding, _)) if bom_encoding == encoding);
+    let (text, had_errors) = encoding.decode_with_bom_removal(bytes);
+    if had_errors {
+        return Err(decode_error(encoding));
+    }
+    Ok((text, bom))
+}
+
+/// Encodes `text` into `encoding`. UTF-16 is written directly as `encoding_rs` only decodes it,
+/// and a leading byte order mark is dropped for encodings that cannot represent it.
+pub(crate) fn encode(text: &str, encoding: &'static Encoding) -> crate::Result<Vec<u8>> {
+    if encoding == UTF_16LE {
+        return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == UTF_16BE {
+        return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+    if encoding == UTF_8 {
+        return Ok(text.as_bytes().to_vec());
+    }
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let (bytes, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        return Err(Error::UnmappableCharacter {
+            encoding: encoding.name(),
+        });
+    }
+    Ok(bytes.into_owned())
+}
+
+/// Encodes the beginning of a file. UTF-16 always gets a byte order mark, without one it would
+/// be read back as UTF-8 or sniffed wrongly.
+pub(crate) fn encode_start(text: &str, encoding: &'static Encoding) -> crate::Result<Vec<u8>> {
+    if (encoding == UTF_16LE || encoding == UTF_16BE) && !text.starts_with('\u{FEFF}') {
+        return encode(&format!("\u{FEFF}{}", text), encoding);
+    }
+    encode(text, encoding)
+}
+
+/// Encodes text written with [`std::fmt::Write`] into an [`std::io::Write`] in chunks, so that
+/// output never has to be built as one string. The first error is kept for [`Self::finish`].
+pub(crate) struct EncodeWriter<W> {
+    out: W,
+    encoding: &'static Encoding,
+    buf: String,
+    started: bool,
+    error: Option<Error>,
+}
+
+impl<W: std::io::Write> EncodeWriter<W> {
+    const CHUNK: usize = 8 * 1024;
+
+    pub(crate) fn new(out: W, encoding: &'static Encoding) -> Self {
+        Self {
+            out,
+            encoding,
+            buf: String::with_capacity(Self::CHUNK),
+            started: false,
+            error: None,
+        }
+    }
+
+    fn flush_buf(&mut self) -> crate::Result<()> {
+        let bytes = if self.started {
+            encode(&self.buf, self.encoding)?
+        } else {
+            self.started = true;
+            encode_start(&self.buf, self.encoding)?
+        };
+        self.buf.clear();
+        self.out.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Writes what is left and returns the output, or the error that stopped the writing.
+    pub(crate) fn finish(mut self, result: std::fmt::Result) -> crate::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        result?;
+        self.flush_buf()?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: std::io::Write> std::fmt::Write for EncodeWriter<W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.buf.push_str(s);
+        if self.buf.len() >= Self::CHUNK {
+            if let Err(error) = self.flush_buf() {
+                self.error = Some(error);
+                return Err(std::fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn decode_error(encoding: &'static Encoding) -> Error {
+    if encoding == UTF_8 {
+        Error::InvalidUTF8Encoding
+    } else {
+        Error::InvalidEncoding {
+            encoding: encoding.name(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{BIG5, GBK, SHIFT_JIS};
+
+    #[test]
+    fn test_detect_bom() {
+        assert_eq!(detect(b"\xEF\xBB\xBF[Script Info]"), UTF_8);
+        assert_eq!(detect(b"\xFF\xFE[\x00S\x00"), UTF_16LE);
+        assert_eq!(detect(b"\xFE\xFF\x00[\x00S"), UTF_16BE);
+        assert_eq!(detect(b"[Script Info]"), UTF_8);
+    }
+
+    #[test]
+    fn test_sniff_legacy_encodings() {
+        let text = "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,你好，世界！今天天气很好，我们一起去公园散步吧。";
+        let (gbk, _, _) = GBK.encode(text);
+        assert_eq!(detect(&gbk), GBK);
+        let text = "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,這是一個繁體中文的字幕檔案，我們今天要去看電影。";
+        let (big5, _, _) = BIG5.encode(text);
+        assert_eq!(detect(&big5), BIG5);
+        let text = "Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,こんにちは、世界。今日はとても良い天気ですね。";
+        let (sjis, _, _) = SHIFT_JIS.encode(text);
+        assert_eq!(detect(&sjis), SHIFT_JIS);
+    }
+
+    #[test]
+    fn test_encode_decode() -> crate::Result<()> {
+        let text = "\u{FEFF}字幕";
+        let bytes = encode(text, UTF_16LE)?;
+        assert_eq!(&bytes[..2], b"\xFF\xFE");
+        let (decoded, bom) = decode(&bytes, UTF_16LE)?;
+        assert_eq!(decoded, "字幕");
+        assert!(bom);
+        let bytes = encode(text, GBK)?;
+        let (decoded, bom) = decode(&bytes, GBK)?;
+        assert_eq!(decoded, "字幕");
+        assert!(!bom);
+        assert!(encode("한국어", SHIFT_JIS).is_err());
+        assert_eq!(encode_start("a", UTF_16BE)?, b"\xFE\xFF\0a");
+        assert_eq!(encode_start(text, UTF_16LE)?, encode(text, UTF_16LE)?);
+        assert_eq!(encode_start("a", UTF_8)?, b"a");
+        Ok(())
+    }
+}
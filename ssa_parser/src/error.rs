@@ -24,6 +24,12 @@ pub enum Error {
     InvalidType { expected: String },
     #[error("invalid utf-8 encoding")]
     InvalidUTF8Encoding,
+    #[error("invalid {encoding} encoding")]
+    InvalidEncoding { encoding: &'static str },
+    #[error("text cannot be represented in {encoding}")]
+    UnmappableCharacter { encoding: &'static str },
+    #[error("line {line}: {source}")]
+    Line { line: usize, source: Box<Error> },
     #[error("io error")]
     IoError {
         #[from]
@@ -33,7 +39,7 @@ pub enum Error {
     FmtError {
         #[from]
         source: std::fmt::Error,
-    }
+    },
 }
 
 impl Error {
@@ -62,6 +68,25 @@ impl Error {
         Error::UnknownSSAVersion(version.into())
     }
 
+    /// Annotates the error with the 1-based line it was found on.
+    pub fn at_line(self, line: usize) -> Self {
+        match self {
+            Error::Line { .. } => self,
+            error => Error::Line {
+                line,
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// The line the error was found on, if known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Error::Line { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
     pub fn invalid_type(expected: impl Into<String>) -> Self {
         Error::InvalidType {
             expected: expected.into(),
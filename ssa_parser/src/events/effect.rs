@@ -153,8 +153,8 @@ impl Display for Effect {
 impl Parser for Effect {
     fn parse(src: &str) -> crate::Result<Self> {
         let parts: Vec<&str> = src.split(';').collect();
-        let effect = parts.get(0).map(|s| s.to_ascii_lowercase());
-        match effect.as_ref().map(|s| s.as_str()) {
+        let effect = parts.first().map(|s| s.to_ascii_lowercase());
+        match effect.as_deref() {
             Some("karaoke") => Ok(Effect::Karaoke),
             Some("scroll up") => Effect::parse_scroll_effect(&parts, "Scroll up"),
             Some("scroll down") => Effect::parse_scroll_effect(&parts, "Scroll down"),
@@ -0,0 +1,324 @@
+use std::ops::Range;
+
+use crate::{
+    events::{Event, EventFormat, EventType, Events},
+    timestamp::Timestamp,
+    value::Value,
+};
+
+/// An interval index over the `[Start, End)` times of events, answering which events are
+/// visible at a time or intersect a range in logarithmic time plus the number of results.
+///
+/// Results are positions in the [`Events`] the index was built from. The index follows edits
+/// of that list through [`TimeIndex::update`], [`TimeIndex::push`] and
+/// [`TimeIndex::swap_remove`], other reorderings need a new index.
+#[derive(Debug, Clone, Default)]
+pub struct TimeIndex {
+    /// Only events of this type are indexed, all of them when `None`.
+    filter: Option<EventType>,
+    /// The interval of every event position, `None` for events filtered out.
+    intervals: Vec<Option<Range<Timestamp>>>,
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    seed: u64,
+}
+
+/// A treap node keyed by start and position, knowing the latest end below it.
+#[derive(Debug, Clone)]
+struct Node {
+    start: Timestamp,
+    end: Timestamp,
+    position: usize,
+    priority: u64,
+    max_end: Timestamp,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl TimeIndex {
+    pub fn new(events: &Events) -> Self {
+        Self::build(events, None)
+    }
+
+    /// Indexes only `Dialogue` events, leaving out comments and the other types.
+    pub fn dialogue(events: &Events) -> Self {
+        Self::build(events, Some(EventType::Dialogue))
+    }
+
+    fn build(events: &Events, filter: Option<EventType>) -> Self {
+        let mut index = Self {
+            filter,
+            ..Default::default()
+        };
+        for event in events.iter() {
+            index.push(event);
+        }
+        index
+    }
+
+    /// The number of indexed events.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Positions of the events visible at `time`, i.e. starting at or before it and ending
+    /// after it, in ascending order.
+    pub fn at(&self, time: Timestamp) -> Vec<usize> {
+        self.overlapping(time..time + Timestamp::from_centis(1))
+    }
+
+    /// Positions of the events intersecting `range`, in ascending order. Events ending at
+    /// `range.start` or starting at `range.end` do not intersect it.
+    pub fn overlapping(&self, range: Range<Timestamp>) -> Vec<usize> {
+        let mut positions = vec![];
+        self.collect(self.root, &range, &mut positions);
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Indexes the event just appended to the events.
+    pub fn push(&mut self, event: &Event) {
+        self.intervals.push(None);
+        self.update(self.intervals.len() - 1, event);
+    }
+
+    /// Indexes `event` again after the event at `position` was edited.
+    pub fn update(&mut self, position: usize, event: &Event) {
+        self.unlink(position);
+        let interval = self.interval(event);
+        if let Some(range) = &interval {
+            self.link(range.clone(), position);
+        }
+        self.intervals[position] = interval;
+    }
+
+    /// Follows [`Vec::swap_remove`] of the events: the event at `position` is dropped and the
+    /// last one takes its place.
+    pub fn swap_remove(&mut self, position: usize) {
+        self.unlink(position);
+        let last = self.intervals.len() - 1;
+        if position != last {
+            let moved = self.intervals[last].clone();
+            self.unlink(last);
+            if let Some(range) = &moved {
+                self.link(range.clone(), position);
+            }
+            self.intervals[position] = moved;
+        }
+        self.intervals.pop();
+    }
+
+    fn interval(&self, event: &Event) -> Option<Range<Timestamp>> {
+        if self
+            .filter
+            .is_some_and(|filter| filter != event.event_type())
+        {
+            return None;
+        }
+        let time = |format| {
+            event
+                .get(format)
+                .and_then(Value::as_timestamp)
+                .unwrap_or_default()
+        };
+        Some(time(EventFormat::Start)..time(EventFormat::End))
+    }
+
+    fn collect(&self, node: Option<usize>, range: &Range<Timestamp>, out: &mut Vec<usize>) {
+        let Some(node) = node.map(|id| &self.nodes[id]) else {
+            return;
+        };
+        if node.max_end <= range.start {
+            return;
+        }
+        self.collect(node.left, range, out);
+        if node.start < range.end {
+            if node.end > range.start && node.start < node.end {
+                out.push(node.position);
+            }
+            self.collect(node.right, range, out);
+        }
+    }
+
+    fn link(&mut self, range: Range<Timestamp>, position: usize) {
+        // splitmix64, so that the tree stays balanced whatever order events come in
+        self.seed = self.seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut priority = self.seed;
+        priority = (priority ^ (priority >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        priority = (priority ^ (priority >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        let node = Node {
+            start: range.start,
+            end: range.end,
+            position,
+            priority: priority ^ (priority >> 31),
+            max_end: range.end,
+            left: None,
+            right: None,
+        };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        let (left, right) = self.split(self.root, (range.start, position));
+        let left = self.merge(left, Some(id));
+        self.root = self.merge(left, right);
+    }
+
+    fn unlink(&mut self, position: usize) {
+        let Some(range) = self.intervals.get(position).cloned().flatten() else {
+            return;
+        };
+        let (left, rest) = self.split(self.root, (range.start, position));
+        let (node, right) = self.split(rest, (range.start, position + 1));
+        self.free.extend(node);
+        self.root = self.merge(left, right);
+    }
+
+    /// Splits into the nodes with keys before `key` and the others.
+    fn split(
+        &mut self,
+        node: Option<usize>,
+        key: (Timestamp, usize),
+    ) -> (Option<usize>, Option<usize>) {
+        let Some(id) = node else {
+            return (None, None);
+        };
+        if (self.nodes[id].start, self.nodes[id].position) < key {
+            let (left, right) = self.split(self.nodes[id].right, key);
+            self.nodes[id].right = left;
+            self.refresh(id);
+            (Some(id), right)
+        } else {
+            let (left, right) = self.split(self.nodes[id].left, key);
+            self.nodes[id].left = right;
+            self.refresh(id);
+            (left, Some(id))
+        }
+    }
+
+    fn merge(&mut self, left: Option<usize>, right: Option<usize>) -> Option<usize> {
+        match (left, right) {
+            (None, node) | (node, None) => node,
+            (Some(l), Some(r)) => {
+                if self.nodes[l].priority > self.nodes[r].priority {
+                    self.nodes[l].right = self.merge(self.nodes[l].right, right);
+                    self.refresh(l);
+                    left
+                } else {
+                    self.nodes[r].left = self.merge(left, self.nodes[r].left);
+                    self.refresh(r);
+                    right
+                }
+            }
+        }
+    }
+
+    fn refresh(&mut self, id: usize) {
+        let node = &self.nodes[id];
+        let max_end = [node.left, node.right]
+            .into_iter()
+            .flatten()
+            .map(|child| self.nodes[child].max_end)
+            .fold(node.end, Timestamp::max);
+        self.nodes[id].max_end = max_end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(events: &Events, event_type: EventType, start: i64, end: i64) -> Event {
+        let mut event = Event::new(event_type, events);
+        event.set(EventFormat::Start, Timestamp::from_centis(start));
+        event.set(EventFormat::End, Timestamp::from_centis(end));
+        event
+    }
+
+    fn scan(events: &Events, range: Range<i64>, filter: Option<EventType>) -> Vec<usize> {
+        let time = |event: &Event, format| event.get(format).unwrap().as_timestamp().unwrap();
+        events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| filter.is_none_or(|filter| filter == event.event_type()))
+            .filter(|(_, event)| {
+                let (start, end) = (
+                    time(event, EventFormat::Start),
+                    time(event, EventFormat::End),
+                );
+                start < end
+                    && start < Timestamp::from_centis(range.end)
+                    && end > Timestamp::from_centis(range.start)
+            })
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    #[test]
+    fn test_time_index() {
+        let mut events = Events::default();
+        let mut seed = 7u64;
+        let mut next = |n: i64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as i64 % n
+        };
+        for i in 0..500 {
+            let start = next(10_000);
+            let event_type = if i % 5 == 0 {
+                EventType::Comment
+            } else {
+                EventType::Dialogue
+            };
+            let e = event(&events, event_type, start, start + next(800));
+            events.push(e);
+        }
+        let mut index = TimeIndex::new(&events);
+        let dialogue = TimeIndex::dialogue(&events);
+        assert_eq!(index.len(), 500);
+        assert_eq!(dialogue.len(), 400);
+        for t in (0..11_000).step_by(37) {
+            let time = Timestamp::from_centis(t);
+            assert_eq!(index.at(time), scan(&events, t..t + 1, None));
+            let range = Timestamp::from_centis(t)..Timestamp::from_centis(t + 250);
+            assert_eq!(
+                dialogue.overlapping(range),
+                scan(&events, t..t + 250, Some(EventType::Dialogue))
+            );
+        }
+
+        for position in (0..500).step_by(3) {
+            let start = next(10_000);
+            events[position] = event(&events, EventType::Dialogue, start, start + next(300));
+            index.update(position, &events[position]);
+        }
+        for position in [499, 0, 250, 17] {
+            events.swap_remove(position);
+            index.swap_remove(position);
+        }
+        let e = event(&events, EventType::Dialogue, 5000, 5001);
+        events.push(e);
+        index.push(events.last().unwrap());
+        for t in (0..11_000).step_by(41) {
+            assert_eq!(
+                index.at(Timestamp::from_centis(t)),
+                scan(&events, t..t + 1, None)
+            );
+        }
+        let last = events.len() - 1;
+        assert!(index.at(Timestamp::from_centis(5000)).contains(&last));
+        assert!(!index.at(Timestamp::from_centis(5001)).contains(&last));
+    }
+}
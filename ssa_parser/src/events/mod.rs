@@ -1,14 +1,22 @@
 use effect::Effect;
 use itertools::Itertools;
+use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
-use std::{fmt::Display, time::Duration};
 use text::Text;
 
 use crate::error::Error;
+use crate::frames::{Boundary, FrameTimes};
+use crate::keyframes::Keyframes;
+use crate::layout::RawLine;
 use crate::parser::{parse_i64, Parser};
+use crate::resync::Resync;
+use crate::timestamp::Timestamp;
+use crate::timing::{KeyframeSnap, PostProcess, TimingChange};
 use crate::value::Value;
 
 pub mod effect;
+pub mod index;
+pub mod tag;
 pub mod text;
 
 #[derive(
@@ -34,7 +42,7 @@ impl EventFormat {
         match self {
             EventFormat::Layer => 0.into(),
             EventFormat::Marked => "Marked=0".to_owned().into(),
-            EventFormat::Start | EventFormat::End => Duration::default().into(),
+            EventFormat::Start | EventFormat::End => Timestamp::default().into(),
             EventFormat::Style | EventFormat::Name => "".to_owned().into(),
             EventFormat::MarginL | EventFormat::MarginR | EventFormat::MarginV => 10.into(),
             EventFormat::Effect => Effect::None.into(),
@@ -51,7 +59,7 @@ impl EventFormat {
                     msg: format!("Invalid Marked value: {}", src),
                 })?;
                 let key = &src[..pos];
-                if key.to_ascii_lowercase() != "marked" {
+                if !key.eq_ignore_ascii_case("marked") {
                     return Err(Error::ParseError {
                         ty: "Marked",
                         msg: format!("Invalid Marked key: {}", key),
@@ -60,7 +68,7 @@ impl EventFormat {
                 parse_i64(&src[pos + 1..])?
             }
             EventFormat::Start | EventFormat::End => {
-                let value = Duration::parse(src)?;
+                let value = Timestamp::parse(src)?;
                 value.into()
             }
             EventFormat::Style | EventFormat::Name => src.to_owned().into(),
@@ -89,19 +97,32 @@ pub enum EventType {
     Command,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Event {
     event_type: EventType,
     values: Vec<(EventFormat, Option<Value>)>,
+    pub(crate) raw: Option<Box<RawLine<EventFormat>>>,
 }
 
 impl Event {
     pub fn new(event_type: EventType, events: &Events) -> Self {
         let mut values = vec![];
         for format in events.order() {
-            values.push((format.clone(), None));
+            values.push((*format, None));
+        }
+        Self {
+            event_type,
+            values,
+            raw: None,
         }
-        Self { event_type, values }
+    }
+
+    pub fn event_type(&self) -> EventType {
+        self.event_type
+    }
+
+    pub fn set_event_type(&mut self, event_type: EventType) {
+        self.event_type = event_type;
     }
 
     pub fn set(&mut self, format: EventFormat, value: impl Into<Value>) {
@@ -113,6 +134,13 @@ impl Event {
         }
     }
 
+    /// The fields in format order, `None` for fields that are not set.
+    pub fn iter(&self) -> impl Iterator<Item = (EventFormat, Option<&Value>)> {
+        self.values
+            .iter()
+            .map(|(format, value)| (*format, value.as_ref()))
+    }
+
     pub fn get(&self, format: EventFormat) -> Option<&Value> {
         for (f, v) in self.values.iter() {
             if f == &format {
@@ -141,6 +169,32 @@ impl Event {
     }
 }
 
+impl Event {
+    pub(crate) fn write_lossless<W: std::fmt::Write>(&self, out: &mut W) -> std::fmt::Result {
+        match &self.raw {
+            Some(raw) => raw.write(
+                out,
+                &self.event_type.to_string(),
+                &self.values,
+                |format, src| format.parse_value(src).ok(),
+                |format, value| match value {
+                    Some(value) => value.to_string(),
+                    None => format.default_value().to_string(),
+                },
+            ),
+            None => write!(out, "{}", self),
+        }
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.event_type == other.event_type && self.values == other.values
+    }
+}
+
+impl Eq for Event {}
+
 impl Display for Event {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut values = Vec::with_capacity(self.values.len());
@@ -176,6 +230,80 @@ impl Events {
     pub fn order(&self) -> &Vec<EventFormat> {
         &self.order
     }
+
+    /// Rearranges every event to `order`, dropping the fields it leaves out and filling the ones
+    /// it adds with their default.
+    pub fn set_order(&mut self, order: Vec<EventFormat>) {
+        for event in self.events.iter_mut() {
+            event.values = order
+                .iter()
+                .map(|format| {
+                    let value = event.get(*format).cloned();
+                    (
+                        *format,
+                        Some(value.unwrap_or_else(|| format.default_value())),
+                    )
+                })
+                .collect();
+        }
+        self.order = order;
+    }
+
+    /// Moves the start and end of every event by `offset`.
+    pub fn shift(&mut self, offset: Timestamp) {
+        self.map_times(|_, time| time + offset);
+    }
+
+    /// Moves every start and end to the frame boundary Aegisub would time it to, so that each
+    /// event starts and ends between the same frames as before.
+    pub fn snap_to_frames(&mut self, frames: &FrameTimes) {
+        self.map_times(|format, time| {
+            let boundary = match format {
+                EventFormat::Start => Boundary::Start,
+                _ => Boundary::End,
+            };
+            frames.time_at(frames.frame_at(time, boundary), boundary)
+        });
+    }
+
+    /// Retimes the events for a video with other frame times, e.g. from 25 fps to 23.976 fps,
+    /// keeping every start and end on the same frame.
+    pub fn convert_framerate(&mut self, from: &FrameTimes, to: &FrameTimes) {
+        self.map_times(|_, time| from.convert(time, to));
+    }
+
+    /// Retimes the events through a piecewise-linear map, see [`crate::resync::Resync`]. The
+    /// times of `\move`, `\fad`, `\fade`, `\t` and karaoke tags are mapped along.
+    pub fn resync(&mut self, resync: &Resync) {
+        crate::resync::resync(self, resync);
+    }
+
+    /// Runs the timing post-processor, see [`crate::timing::PostProcess`]. Returns every change
+    /// made.
+    pub fn post_process(&mut self, options: &PostProcess) -> Vec<TimingChange> {
+        crate::timing::post_process(self, options)
+    }
+
+    /// Snaps starts and ends to nearby keyframes, see [`crate::timing::KeyframeSnap`]. Returns
+    /// every change made.
+    pub fn snap_to_keyframes(
+        &mut self,
+        keyframes: &Keyframes,
+        frames: &FrameTimes,
+        options: &KeyframeSnap,
+    ) -> Vec<TimingChange> {
+        crate::timing::snap_to_keyframes(self, keyframes, frames, options)
+    }
+
+    fn map_times(&mut self, mut map: impl FnMut(EventFormat, Timestamp) -> Timestamp) {
+        for event in self.events.iter_mut() {
+            for format in [EventFormat::Start, EventFormat::End] {
+                if let Some(time) = event.get_mut(format).and_then(Value::as_timestamp_mut) {
+                    *time = map(format, *time);
+                }
+            }
+        }
+    }
 }
 
 impl Display for Events {
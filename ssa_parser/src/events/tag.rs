@@ -0,0 +1,866 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    alignment::Alignment,
+    color::{Color, ColorFormat},
+    error::Error,
+    parser::Parser,
+    version::Version,
+};
+
+/// Tag names ordered so that longer names are tried before their prefixes
+/// (`\fscx` before `\fsc` before `\fs`).
+const TAG_NAMES: [&str; 53] = [
+    "xbord", "ybord", "xshad", "yshad", "alpha", "iclip", "blur", "bord", "shad", "clip", "move",
+    "fade", "fscx", "fscy", "fsp", "fsc", "frx", "fry", "frz", "fax", "fay", "pos", "org", "pbo",
+    "fad", "be", "fn", "fs", "fe", "fr", "an", "kf", "ko", "1c", "2c", "3c", "4c", "1a", "2a",
+    "3a", "4a", "b", "i", "u", "s", "c", "a", "k", "K", "q", "r", "p", "t",
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum KaraokeKind {
+    /// `\k`
+    Instant,
+    /// `\K`
+    Sweep,
+    /// `\kf`
+    Fill,
+    /// `\ko`
+    Outline,
+}
+
+impl KaraokeKind {
+    fn name(&self) -> &'static str {
+        match self {
+            KaraokeKind::Instant => "k",
+            KaraokeKind::Sweep => "K",
+            KaraokeKind::Fill => "kf",
+            KaraokeKind::Outline => "ko",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipShape {
+    Rect { x1: f64, y1: f64, x2: f64, y2: f64 },
+    Vector { scale: Option<i64>, drawing: String },
+}
+
+impl Display for ClipShape {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClipShape::Rect { x1, y1, x2, y2 } => write!(f, "{x1},{y1},{x2},{y2}"),
+            ClipShape::Vector {
+                scale: Some(scale),
+                drawing,
+            } => write!(f, "{scale},{drawing}"),
+            ClipShape::Vector {
+                scale: None,
+                drawing,
+            } => write!(f, "{drawing}"),
+        }
+    }
+}
+
+/// The typed meaning of an override tag. `None` arguments mean the tag was written without a
+/// value, which resets the property to the line's style.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagKind {
+    /// `\b`, either `0`/`1` or a font weight such as `700`
+    Bold(Option<i64>),
+    /// `\i`
+    Italic(Option<bool>),
+    /// `\u`
+    Underline(Option<bool>),
+    /// `\s`
+    StrikeOut(Option<bool>),
+    /// `\bord`
+    Border(Option<f64>),
+    /// `\xbord`
+    BorderX(Option<f64>),
+    /// `\ybord`
+    BorderY(Option<f64>),
+    /// `\shad`
+    Shadow(Option<f64>),
+    /// `\xshad`
+    ShadowX(Option<f64>),
+    /// `\yshad`
+    ShadowY(Option<f64>),
+    /// `\be`
+    BlurEdges(Option<f64>),
+    /// `\blur`
+    Blur(Option<f64>),
+    /// `\fn`
+    FontName(Option<String>),
+    /// `\fs`
+    FontSize(Option<f64>),
+    /// `\fsc`, resets both scales
+    FontScale(Option<f64>),
+    /// `\fscx`
+    FontScaleX(Option<f64>),
+    /// `\fscy`
+    FontScaleY(Option<f64>),
+    /// `\fsp`
+    FontSpacing(Option<f64>),
+    /// `\fr`
+    FontRotation(Option<f64>),
+    /// `\frx`
+    FontRotationX(Option<f64>),
+    /// `\fry`
+    FontRotationY(Option<f64>),
+    /// `\frz`
+    FontRotationZ(Option<f64>),
+    /// `\fax`
+    FontShearX(Option<f64>),
+    /// `\fay`
+    FontShearY(Option<f64>),
+    /// `\fe`
+    FontEncoding(Option<i64>),
+    /// `\c` (index 1) and `\1c` to `\4c`, the colour is kept as written, e.g. `&HFFFFFF&`
+    Color { index: u8, color: Option<Color> },
+    /// `\alpha` (no index) and `\1a` to `\4a`
+    Alpha {
+        index: Option<u8>,
+        alpha: Option<u8>,
+    },
+    /// `\an`, numpad alignment
+    Alignment(Option<i64>),
+    /// `\a`, legacy SSA alignment
+    LegacyAlignment(Option<i64>),
+    /// `\k`, `\K`, `\kf` and `\ko`, duration in centiseconds
+    Karaoke {
+        kind: KaraokeKind,
+        duration: Option<i64>,
+    },
+    /// `\q`
+    WrapStyle(Option<i64>),
+    /// `\r`, optionally naming the style to reset to
+    Reset(Option<String>),
+    /// `\pos(x,y)`
+    Position { x: f64, y: f64 },
+    /// `\move(x1,y1,x2,y2[,t1,t2])`
+    Move {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+        times: Option<(i64, i64)>,
+    },
+    /// `\org(x,y)`
+    Origin { x: f64, y: f64 },
+    /// `\fad(in,out)`
+    Fade { fade_in: i64, fade_out: i64 },
+    /// `\fade(a1,a2,a3,t1,t2,t3,t4)`
+    FadeComplex { alphas: [i64; 3], times: [i64; 4] },
+    /// `\t([t1,t2,][accel,]tags)`
+    Transform {
+        times: Option<(i64, i64)>,
+        accel: Option<f64>,
+        tags: Vec<Tag>,
+    },
+    /// `\clip(...)` and `\iclip(...)`
+    Clip { inverse: bool, shape: ClipShape },
+    /// `\p`
+    Drawing(i64),
+    /// `\pbo`
+    DrawingBaselineOffset(f64),
+    /// Anything else, stored without the leading backslash
+    Unknown(String),
+}
+
+impl Eq for TagKind {}
+
+impl TagKind {
+    /// `\an` for V4+ and `\a` for V4.
+    pub fn from_alignment(alignment: Alignment, version: Version) -> Self {
+        let value = Some(alignment.to_version(version));
+        match version {
+            Version::V4 => TagKind::LegacyAlignment(value),
+            Version::V4Plus => TagKind::Alignment(value),
+        }
+    }
+
+    /// The alignment set by an `\an` or `\a` tag, `None` for other tags, resets and values out
+    /// of range.
+    pub fn alignment(&self) -> Option<Alignment> {
+        match self {
+            TagKind::Alignment(Some(numpad)) => Alignment::from_numpad(*numpad),
+            TagKind::LegacyAlignment(Some(legacy)) => Alignment::from_legacy(*legacy),
+            _ => None,
+        }
+    }
+
+    fn parse(src: &str) -> Self {
+        Self::parse_known(src).unwrap_or_else(|| TagKind::Unknown(src.to_string()))
+    }
+
+    fn parse_known(src: &str) -> Option<Self> {
+        let src = src.trim_start();
+        let name = TAG_NAMES.iter().find(|name| src.starts_with(**name))?;
+        let arg = &src[name.len()..];
+        let kind = match *name {
+            "b" => TagKind::Bold(opt(arg, parse_int)?),
+            "i" => TagKind::Italic(opt(arg, parse_bool)?),
+            "u" => TagKind::Underline(opt(arg, parse_bool)?),
+            "s" => TagKind::StrikeOut(opt(arg, parse_bool)?),
+            "bord" => TagKind::Border(opt(arg, parse_float)?),
+            "xbord" => TagKind::BorderX(opt(arg, parse_float)?),
+            "ybord" => TagKind::BorderY(opt(arg, parse_float)?),
+            "shad" => TagKind::Shadow(opt(arg, parse_float)?),
+            "xshad" => TagKind::ShadowX(opt(arg, parse_float)?),
+            "yshad" => TagKind::ShadowY(opt(arg, parse_float)?),
+            "be" => TagKind::BlurEdges(opt(arg, parse_float)?),
+            "blur" => TagKind::Blur(opt(arg, parse_float)?),
+            "fn" => TagKind::FontName(opt(arg, |s| Some(s.to_string()))?),
+            "fs" => TagKind::FontSize(opt(arg, parse_float)?),
+            "fsc" => TagKind::FontScale(opt(arg, parse_float)?),
+            "fscx" => TagKind::FontScaleX(opt(arg, parse_float)?),
+            "fscy" => TagKind::FontScaleY(opt(arg, parse_float)?),
+            "fsp" => TagKind::FontSpacing(opt(arg, parse_float)?),
+            "fr" => TagKind::FontRotation(opt(arg, parse_float)?),
+            "frx" => TagKind::FontRotationX(opt(arg, parse_float)?),
+            "fry" => TagKind::FontRotationY(opt(arg, parse_float)?),
+            "frz" => TagKind::FontRotationZ(opt(arg, parse_float)?),
+            "fax" => TagKind::FontShearX(opt(arg, parse_float)?),
+            "fay" => TagKind::FontShearY(opt(arg, parse_float)?),
+            "fe" => TagKind::FontEncoding(opt(arg, parse_int)?),
+            "c" | "1c" | "2c" | "3c" | "4c" => TagKind::Color {
+                index: index_of(name),
+                color: opt(arg, parse_color)?,
+            },
+            "alpha" => TagKind::Alpha {
+                index: None,
+                alpha: opt(arg, parse_alpha)?,
+            },
+            "1a" | "2a" | "3a" | "4a" => TagKind::Alpha {
+                index: Some(index_of(name)),
+                alpha: opt(arg, parse_alpha)?,
+            },
+            "an" => TagKind::Alignment(opt(arg, parse_int)?),
+            "a" => TagKind::LegacyAlignment(opt(arg, parse_int)?),
+            "k" | "K" | "kf" | "ko" => TagKind::Karaoke {
+                kind: match *name {
+                    "k" => KaraokeKind::Instant,
+                    "K" => KaraokeKind::Sweep,
+                    "kf" => KaraokeKind::Fill,
+                    _ => KaraokeKind::Outline,
+                },
+                duration: opt(arg, parse_int)?,
+            },
+            "q" => TagKind::WrapStyle(opt(arg, parse_int)?),
+            "r" => TagKind::Reset(opt(arg, |s| Some(s.to_string()))?),
+            "p" => TagKind::Drawing(parse_int(arg.trim())?),
+            "pbo" => TagKind::DrawingBaselineOffset(parse_float(arg.trim())?),
+            "pos" => {
+                let [x, y] = floats::<2>(&args(arg)?)?;
+                TagKind::Position { x, y }
+            }
+            "org" => {
+                let [x, y] = floats::<2>(&args(arg)?)?;
+                TagKind::Origin { x, y }
+            }
+            "move" => {
+                let args = args(arg)?;
+                match args.len() {
+                    4 => {
+                        let [x1, y1, x2, y2] = floats::<4>(&args)?;
+                        TagKind::Move {
+                            x1,
+                            y1,
+                            x2,
+                            y2,
+                            times: None,
+                        }
+                    }
+                    6 => {
+                        let [x1, y1, x2, y2] = floats::<4>(&args[..4])?;
+                        let [t1, t2] = ints::<2>(&args[4..])?;
+                        TagKind::Move {
+                            x1,
+                            y1,
+                            x2,
+                            y2,
+                            times: Some((t1, t2)),
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            "fad" => {
+                let [fade_in, fade_out] = ints::<2>(&args(arg)?)?;
+                TagKind::Fade { fade_in, fade_out }
+            }
+            "fade" => {
+                let args = args(arg)?;
+                let alphas = ints::<3>(args.get(..3)?)?;
+                let times = ints::<4>(args.get(3..)?)?;
+                TagKind::FadeComplex { alphas, times }
+            }
+            "clip" | "iclip" => TagKind::Clip {
+                inverse: *name == "iclip",
+                shape: parse_clip(&args(arg)?)?,
+            },
+            "t" => parse_transform(arg)?,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+impl Display for TagKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn w<T: Display>(f: &mut Formatter<'_>, name: &str, value: &Option<T>) -> std::fmt::Result {
+            match value {
+                Some(value) => write!(f, "\\{name}{value}"),
+                None => write!(f, "\\{name}"),
+            }
+        }
+        let flag = |b: &Option<bool>| b.map(i64::from);
+        match self {
+            TagKind::Bold(v) => w(f, "b", v),
+            TagKind::Italic(v) => w(f, "i", &flag(v)),
+            TagKind::Underline(v) => w(f, "u", &flag(v)),
+            TagKind::StrikeOut(v) => w(f, "s", &flag(v)),
+            TagKind::Border(v) => w(f, "bord", v),
+            TagKind::BorderX(v) => w(f, "xbord", v),
+            TagKind::BorderY(v) => w(f, "ybord", v),
+            TagKind::Shadow(v) => w(f, "shad", v),
+            TagKind::ShadowX(v) => w(f, "xshad", v),
+            TagKind::ShadowY(v) => w(f, "yshad", v),
+            TagKind::BlurEdges(v) => w(f, "be", v),
+            TagKind::Blur(v) => w(f, "blur", v),
+            TagKind::FontName(v) => w(f, "fn", v),
+            TagKind::FontSize(v) => w(f, "fs", v),
+            TagKind::FontScale(v) => w(f, "fsc", v),
+            TagKind::FontScaleX(v) => w(f, "fscx", v),
+            TagKind::FontScaleY(v) => w(f, "fscy", v),
+            TagKind::FontSpacing(v) => w(f, "fsp", v),
+            TagKind::FontRotation(v) => w(f, "fr", v),
+            TagKind::FontRotationX(v) => w(f, "frx", v),
+            TagKind::FontRotationY(v) => w(f, "fry", v),
+            TagKind::FontRotationZ(v) => w(f, "frz", v),
+            TagKind::FontShearX(v) => w(f, "fax", v),
+            TagKind::FontShearY(v) => w(f, "fay", v),
+            TagKind::FontEncoding(v) => w(f, "fe", v),
+            TagKind::Color { index, color } => {
+                let name = match index {
+                    1 => "c".to_string(),
+                    index => format!("{index}c"),
+                };
+                w(f, &name, &color.map(|c| c.with_format(ColorFormat::Tag)))
+            }
+            TagKind::Alpha { index, alpha } => {
+                let name = match index {
+                    Some(index) => format!("{index}a"),
+                    None => "alpha".to_string(),
+                };
+                w(f, &name, &alpha.map(|a| format!("&H{a:02X}&")))
+            }
+            TagKind::Alignment(v) => w(f, "an", v),
+            TagKind::LegacyAlignment(v) => w(f, "a", v),
+            TagKind::Karaoke { kind, duration } => w(f, kind.name(), duration),
+            TagKind::WrapStyle(v) => w(f, "q", v),
+            TagKind::Reset(v) => w(f, "r", v),
+            TagKind::Position { x, y } => write!(f, "\\pos({x},{y})"),
+            TagKind::Move {
+                x1,
+                y1,
+                x2,
+                y2,
+                times,
+            } => {
+                write!(f, "\\move({x1},{y1},{x2},{y2}")?;
+                if let Some((t1, t2)) = times {
+                    write!(f, ",{t1},{t2}")?;
+                }
+                write!(f, ")")
+            }
+            TagKind::Origin { x, y } => write!(f, "\\org({x},{y})"),
+            TagKind::Fade { fade_in, fade_out } => write!(f, "\\fad({fade_in},{fade_out})"),
+            TagKind::FadeComplex {
+                alphas: [a1, a2, a3],
+                times: [t1, t2, t3, t4],
+            } => write!(f, "\\fade({a1},{a2},{a3},{t1},{t2},{t3},{t4})"),
+            TagKind::Transform { times, accel, tags } => {
+                write!(f, "\\t(")?;
+                if let Some((t1, t2)) = times {
+                    write!(f, "{t1},{t2},")?;
+                }
+                if let Some(accel) = accel {
+                    write!(f, "{accel},")?;
+                }
+                for tag in tags {
+                    write!(f, "{tag}")?;
+                }
+                write!(f, ")")
+            }
+            TagKind::Clip { inverse, shape } => {
+                let name = if *inverse { "iclip" } else { "clip" };
+                write!(f, "\\{name}({shape})")
+            }
+            TagKind::Drawing(v) => write!(f, "\\p{v}"),
+            TagKind::DrawingBaselineOffset(v) => write!(f, "\\pbo{v}"),
+            TagKind::Unknown(s) => write!(f, "\\{s}"),
+        }
+    }
+}
+
+/// A single override tag. Parsed tags remember how they were written so that untouched tags
+/// serialise exactly as they appeared in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    kind: TagKind,
+    raw: Option<String>,
+}
+
+impl Tag {
+    pub fn new(kind: TagKind) -> Self {
+        Self { kind, raw: None }
+    }
+
+    pub fn kind(&self) -> &TagKind {
+        &self.kind
+    }
+
+    /// Mutable access to the tag, the tag is re-rendered from its typed value afterwards.
+    pub fn kind_mut(&mut self) -> &mut TagKind {
+        self.raw = None;
+        &mut self.kind
+    }
+
+    pub fn into_kind(self) -> TagKind {
+        self.kind
+    }
+
+    /// The tag as it was written in the source, including the leading backslash.
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        matches!(self.kind, TagKind::Unknown(_))
+    }
+
+    /// Splits a run of tags such as `\b1\pos(10,20)` into single tags.
+    pub(crate) fn parse_all(src: &str) -> Vec<Tag> {
+        split_tags(src)
+            .into_iter()
+            .map(|raw| Tag {
+                kind: TagKind::parse(&raw[1..]),
+                raw: Some(raw.to_string()),
+            })
+            .collect()
+    }
+}
+
+impl From<TagKind> for Tag {
+    fn from(kind: TagKind) -> Self {
+        Tag::new(kind)
+    }
+}
+
+impl Parser for Tag {
+    fn parse(src: &str) -> crate::Result<Self> {
+        let invalid = || Error::parse_error::<Tag>(format!("invalid tag {}", src));
+        if !src.starts_with('\\') {
+            return Err(invalid());
+        }
+        let mut tags = Tag::parse_all(src);
+        if tags.len() != 1 {
+            return Err(invalid());
+        }
+        Ok(tags.remove(0))
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.raw {
+            Some(raw) => write!(f, "{}", raw),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+/// An entry of an override block: either a tag or free text, which renderers ignore and
+/// scripts commonly use for comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverrideItem {
+    Tag(Tag),
+    Comment(String),
+}
+
+impl OverrideItem {
+    pub(crate) fn parse_block(src: &str) -> Vec<OverrideItem> {
+        let mut items = vec![];
+        let tags_start = src.find('\\').unwrap_or(src.len());
+        if tags_start > 0 {
+            items.push(OverrideItem::Comment(src[..tags_start].to_string()));
+        }
+        items.extend(
+            Tag::parse_all(&src[tags_start..])
+                .into_iter()
+                .map(OverrideItem::Tag),
+        );
+        items
+    }
+
+    pub fn as_tag(&self) -> Option<&Tag> {
+        match self {
+            OverrideItem::Tag(tag) => Some(tag),
+            OverrideItem::Comment(_) => None,
+        }
+    }
+
+    pub fn as_tag_mut(&mut self) -> Option<&mut Tag> {
+        match self {
+            OverrideItem::Tag(tag) => Some(tag),
+            OverrideItem::Comment(_) => None,
+        }
+    }
+}
+
+impl Display for OverrideItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrideItem::Tag(tag) => write!(f, "{}", tag),
+            OverrideItem::Comment(comment) => write!(f, "{}", comment),
+        }
+    }
+}
+
+/// Splits `src`, which must start with a backslash or be empty, at every backslash that is not
+/// nested inside parentheses.
+fn split_tags(src: &str) -> Vec<&str> {
+    let mut tags = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    for (i, c) in src.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            '\\' if depth == 0 && i > start => {
+                tags.push(&src[start..i]);
+                start = i;
+            }
+            _ => {}
+        }
+    }
+    if start < src.len() {
+        tags.push(&src[start..]);
+    }
+    tags
+}
+
+fn index_of(name: &str) -> u8 {
+    name.as_bytes()
+        .first()
+        .filter(|b| b.is_ascii_digit())
+        .map(|b| b - b'0')
+        .unwrap_or(1)
+}
+
+fn opt<T>(arg: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Option<T>> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        Some(None)
+    } else {
+        parse(arg).map(Some)
+    }
+}
+
+fn parse_int(src: &str) -> Option<i64> {
+    src.parse().ok()
+}
+
+/// Only finite numbers, so that tags stay equal to themselves.
+fn parse_float(src: &str) -> Option<f64> {
+    src.parse().ok().filter(|value: &f64| value.is_finite())
+}
+
+fn parse_bool(src: &str) -> Option<bool> {
+    match src {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_color(src: &str) -> Option<Color> {
+    Color::parse(src).ok()
+}
+
+fn parse_alpha(src: &str) -> Option<u8> {
+    let hex = src.strip_prefix("&H")?;
+    let hex = hex.strip_suffix('&').unwrap_or(hex);
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Returns the comma separated arguments of a parenthesised tag. A missing closing parenthesis
+/// is tolerated as renderers do.
+fn args(arg: &str) -> Option<Vec<&str>> {
+    let inner = arg.trim_start().strip_prefix('(')?;
+    let inner = inner.strip_suffix(')').unwrap_or(inner);
+    Some(inner.split(',').map(str::trim).collect())
+}
+
+fn floats<const N: usize>(args: &[&str]) -> Option<[f64; N]> {
+    if args.len() != N {
+        return None;
+    }
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = parse_float(arg)?;
+    }
+    Some(values)
+}
+
+fn ints<const N: usize>(args: &[&str]) -> Option<[i64; N]> {
+    if args.len() != N {
+        return None;
+    }
+    let mut values = [0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = parse_int(arg)?;
+    }
+    Some(values)
+}
+
+fn parse_clip(args: &[&str]) -> Option<ClipShape> {
+    match args {
+        [x1, y1, x2, y2] => Some(ClipShape::Rect {
+            x1: parse_float(x1)?,
+            y1: parse_float(y1)?,
+            x2: parse_float(x2)?,
+            y2: parse_float(y2)?,
+        }),
+        [scale, drawing] => Some(ClipShape::Vector {
+            scale: Some(parse_int(scale)?),
+            drawing: drawing.to_string(),
+        }),
+        [drawing] if !drawing.is_empty() => Some(ClipShape::Vector {
+            scale: None,
+            drawing: drawing.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+fn parse_transform(arg: &str) -> Option<TagKind> {
+    let inner = arg.trim_start().strip_prefix('(')?;
+    let inner = inner.strip_suffix(')').unwrap_or(inner);
+    let tags_start = inner.find('\\').unwrap_or(inner.len());
+    let numbers: Vec<&str> = inner[..tags_start]
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    let (times, accel) = match numbers.as_slice() {
+        [] => (None, None),
+        [accel] => (None, Some(parse_float(accel)?)),
+        [t1, t2] => (Some((parse_int(t1)?, parse_int(t2)?)), None),
+        [t1, t2, accel] => (
+            Some((parse_int(t1)?, parse_int(t2)?)),
+            Some(parse_float(accel)?),
+        ),
+        _ => return None,
+    };
+    Some(TagKind::Transform {
+        times,
+        accel,
+        tags: Tag::parse_all(&inner[tags_start..]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(src: &str) -> TagKind {
+        Tag::parse(src).unwrap().into_kind()
+    }
+
+    #[test]
+    fn test_parse_simple_tags() {
+        assert_eq!(kind("\\b1"), TagKind::Bold(Some(1)));
+        assert_eq!(kind("\\b700"), TagKind::Bold(Some(700)));
+        assert_eq!(kind("\\i"), TagKind::Italic(None));
+        assert_eq!(kind("\\fs20.5"), TagKind::FontSize(Some(20.5)));
+        assert_eq!(kind("\\fscx120"), TagKind::FontScaleX(Some(120.0)));
+        assert_eq!(kind("\\fsp2"), TagKind::FontSpacing(Some(2.0)));
+        assert_eq!(kind("\\frz-15"), TagKind::FontRotationZ(Some(-15.0)));
+        assert_eq!(kind("\\fr15"), TagKind::FontRotation(Some(15.0)));
+        assert_eq!(kind("\\bord3"), TagKind::Border(Some(3.0)));
+        assert_eq!(kind("\\be1"), TagKind::BlurEdges(Some(1.0)));
+        assert_eq!(kind("\\blur0.6"), TagKind::Blur(Some(0.6)));
+        assert_eq!(kind("\\an8"), TagKind::Alignment(Some(8)));
+        assert_eq!(kind("\\a6"), TagKind::LegacyAlignment(Some(6)));
+        assert_eq!(kind("\\a6").alignment(), Some(Alignment::TopCenter));
+        assert_eq!(kind("\\an6").alignment(), Some(Alignment::MiddleRight));
+        assert_eq!(kind("\\an").alignment(), None);
+        assert_eq!(
+            TagKind::from_alignment(Alignment::MiddleRight, Version::V4),
+            kind("\\a11")
+        );
+        assert_eq!(kind("\\p1"), TagKind::Drawing(1));
+        assert_eq!(kind("\\rSign"), TagKind::Reset(Some("Sign".to_string())));
+        assert_eq!(
+            kind("\\fnSource Han Sans"),
+            TagKind::FontName(Some("Source Han Sans".to_string()))
+        );
+        assert_eq!(
+            kind("\\kf25"),
+            TagKind::Karaoke {
+                kind: KaraokeKind::Fill,
+                duration: Some(25)
+            }
+        );
+        assert_eq!(
+            kind("\\K10"),
+            TagKind::Karaoke {
+                kind: KaraokeKind::Sweep,
+                duration: Some(10)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        assert_eq!(
+            kind("\\c&H0000FF&"),
+            TagKind::Color {
+                index: 1,
+                color: Some(Color::new(255, 0, 0, 0))
+            }
+        );
+        assert_eq!(
+            kind("\\3c&HFFFFFF&"),
+            TagKind::Color {
+                index: 3,
+                color: Some(Color::new(255, 255, 255, 0))
+            }
+        );
+        assert_eq!(
+            kind("\\alpha&H80&"),
+            TagKind::Alpha {
+                index: None,
+                alpha: Some(0x80)
+            }
+        );
+        assert_eq!(
+            kind("\\2a&HFF&"),
+            TagKind::Alpha {
+                index: Some(2),
+                alpha: Some(0xFF)
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_function_tags() {
+        assert_eq!(
+            kind("\\pos(10, 20.5)"),
+            TagKind::Position { x: 10.0, y: 20.5 }
+        );
+        assert_eq!(
+            kind("\\move(0,0,100,100,200,800)"),
+            TagKind::Move {
+                x1: 0.0,
+                y1: 0.0,
+                x2: 100.0,
+                y2: 100.0,
+                times: Some((200, 800))
+            }
+        );
+        assert_eq!(
+            kind("\\fad(150,300)"),
+            TagKind::Fade {
+                fade_in: 150,
+                fade_out: 300
+            }
+        );
+        assert_eq!(
+            kind("\\fade(255,0,255,0,100,900,1000)"),
+            TagKind::FadeComplex {
+                alphas: [255, 0, 255],
+                times: [0, 100, 900, 1000]
+            }
+        );
+        assert_eq!(
+            kind("\\iclip(1,2,3,4)"),
+            TagKind::Clip {
+                inverse: true,
+                shape: ClipShape::Rect {
+                    x1: 1.0,
+                    y1: 2.0,
+                    x2: 3.0,
+                    y2: 4.0
+                }
+            }
+        );
+        assert_eq!(
+            kind("\\clip(2,m 0 0 l 10 0 10 10)"),
+            TagKind::Clip {
+                inverse: false,
+                shape: ClipShape::Vector {
+                    scale: Some(2),
+                    drawing: "m 0 0 l 10 0 10 10".to_string()
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        let TagKind::Transform { times, accel, tags } = kind("\\t(0,500,0.5,\\fs40\\c&HFF&)")
+        else {
+            panic!("expected transform");
+        };
+        assert_eq!(times, Some((0, 500)));
+        assert_eq!(accel, Some(0.5));
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].kind(), &TagKind::FontSize(Some(40.0)));
+        let TagKind::Transform { times, accel, tags } = kind("\\t(\\frz360)") else {
+            panic!("expected transform");
+        };
+        assert_eq!((times, accel), (None, None));
+        assert_eq!(tags[0].kind(), &TagKind::FontRotationZ(Some(360.0)));
+    }
+
+    #[test]
+    fn test_unknown_and_invalid_tags() {
+        assert_eq!(kind("\\xyz12"), TagKind::Unknown("xyz12".to_string()));
+        assert_eq!(kind("\\pos(1)"), TagKind::Unknown("pos(1)".to_string()));
+        assert!(Tag::parse("b1").is_err());
+        assert!(Tag::parse("\\b1\\i1").is_err());
+        assert_eq!(kind("\\fsnan"), TagKind::Unknown("fsnan".to_string()));
+        assert_eq!(
+            kind("\\pos(inf,0)"),
+            TagKind::Unknown("pos(inf,0)".to_string())
+        );
+        assert!(Tag::parse("é").is_err());
+        assert!(Tag::parse("é\\b1").is_err());
+    }
+
+    #[test]
+    fn test_render_tags() {
+        let mut tag = Tag::parse("\\pos( 10 , 20 )").unwrap();
+        assert_eq!(tag.to_string(), "\\pos( 10 , 20 )");
+        *tag.kind_mut() = TagKind::Position { x: 15.0, y: 20.0 };
+        assert_eq!(tag.to_string(), "\\pos(15,20)");
+        assert_eq!(
+            Tag::new(TagKind::Alpha {
+                index: Some(1),
+                alpha: Some(0x0A)
+            })
+            .to_string(),
+            "\\1a&H0A&"
+        );
+        let transform = Tag::new(TagKind::Transform {
+            times: Some((0, 100)),
+            accel: None,
+            tags: vec![Tag::new(TagKind::Blur(Some(2.0)))],
+        });
+        assert_eq!(transform.to_string(), "\\t(0,100,\\blur2)");
+    }
+}
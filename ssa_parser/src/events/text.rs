@@ -1,28 +1,233 @@
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
 
+use crate::events::tag::{OverrideItem, Tag};
 use crate::parser::Parser;
 
-#[derive(Debug, Clone, Hash)]
-pub struct Text(String);
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Segment {
+    /// Text outside of override blocks, escapes such as `\N` and `\h` are kept as written.
+    Plain(String),
+    /// The content of a `{...}` block.
+    Override(Vec<OverrideItem>),
+}
+
+impl Segment {
+    pub fn as_plain(&self) -> Option<&str> {
+        match self {
+            Segment::Plain(s) => Some(s),
+            Segment::Override(_) => None,
+        }
+    }
+
+    pub fn as_override(&self) -> Option<&Vec<OverrideItem>> {
+        match self {
+            Segment::Plain(_) => None,
+            Segment::Override(items) => Some(items),
+        }
+    }
+
+    pub fn as_override_mut(&mut self) -> Option<&mut Vec<OverrideItem>> {
+        match self {
+            Segment::Plain(_) => None,
+            Segment::Override(items) => Some(items),
+        }
+    }
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Plain(s) => write!(f, "{}", s),
+            Segment::Override(items) => {
+                write!(f, "{{")?;
+                for item in items {
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Text {
+    segments: Vec<Segment>,
+}
+
+impl Text {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Self { segments }
+    }
+
+    /// All tags of all override blocks, tags nested inside `\t` are not visited.
+    pub fn tags(&self) -> impl Iterator<Item = &Tag> {
+        self.segments
+            .iter()
+            .filter_map(Segment::as_override)
+            .flatten()
+            .filter_map(OverrideItem::as_tag)
+    }
+
+    pub fn tags_mut(&mut self) -> impl Iterator<Item = &mut Tag> {
+        self.segments
+            .iter_mut()
+            .filter_map(Segment::as_override_mut)
+            .flatten()
+            .filter_map(OverrideItem::as_tag_mut)
+    }
 
-impl Text {}
+    /// The text with all override blocks removed.
+    pub fn plain_text(&self) -> String {
+        self.segments.iter().filter_map(Segment::as_plain).collect()
+    }
+
+    fn push_plain(&mut self, src: &str) {
+        if src.is_empty() {
+            return;
+        }
+        match self.segments.last_mut() {
+            Some(Segment::Plain(plain)) => plain.push_str(src),
+            _ => self.segments.push(Segment::Plain(src.to_string())),
+        }
+    }
+}
 
 impl Parser for Text {
     fn parse(src: &str) -> crate::Result<Self> {
-        Ok(Self(src.to_string()))
+        let mut text = Text::default();
+        let mut rest = src;
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}').map(|pos| open + pos) else {
+                break;
+            };
+            text.push_plain(&rest[..open]);
+            text.segments
+                .push(Segment::Override(OverrideItem::parse_block(
+                    &rest[open + 1..close],
+                )));
+            rest = &rest[close + 1..];
+        }
+        text.push_plain(rest);
+        Ok(text)
     }
 }
 
 impl Display for Text {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        for segment in &self.segments {
+            write!(f, "{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hashes the plain text, comments and tags as written, which texts that are equal share.
+impl Hash for Text {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for segment in &self.segments {
+            match segment {
+                Segment::Plain(text) => text.hash(state),
+                Segment::Override(items) => {
+                    for item in items {
+                        match item {
+                            OverrideItem::Tag(tag) => tag.raw().hash(state),
+                            OverrideItem::Comment(comment) => comment.hash(state),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Deref for Text {
+    type Target = Vec<Segment>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.segments
     }
 }
 
-impl PartialEq for Text {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+impl DerefMut for Text {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.segments
     }
 }
 
-impl Eq for Text {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::tag::TagKind;
+
+    #[test]
+    fn test_parse_text() -> crate::Result<()> {
+        let text = Text::parse("{\\an8\\pos(960,50)}Hello{\\i1}World{\\i0}!")?;
+        assert_eq!(text.len(), 6);
+        assert_eq!(text.plain_text(), "HelloWorld!");
+        let kinds: Vec<_> = text.tags().map(Tag::kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &TagKind::Alignment(Some(8)),
+                &TagKind::Position { x: 960.0, y: 50.0 },
+                &TagKind::Italic(Some(true)),
+                &TagKind::Italic(Some(false)),
+            ]
+        );
+        let text = Text::parse("{\\fsnan}a")?;
+        assert_eq!(text, text.clone());
+        let hash = |text: &Text| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            text.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&text), hash(&text.clone()));
+        assert_ne!(hash(&text), hash(&Text::parse("{\\fs20}a")?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> crate::Result<()> {
+        let sources = [
+            "",
+            "plain text\\Nsecond line",
+            "{comment only}",
+            "{note: fix later\\b1}bold{\\b0} text",
+            "{\\k20}ka{\\kf15}ra{\\ko10}o{\\K5}ke",
+            "{\\t(0,500,\\fscx120\\fscy120)\\clip(m 0 0 l 100 0 100 100)\\xyz(1,2)}x",
+            "{\\pos( 10 , 20 )\\fad(200,200)\\1c&H00FF00&\\3a&H80&}spaced",
+            "unclosed {\\b1 brace",
+            "stray } brace {}",
+            "{\\fs20}{\\fs30}back to back",
+            "{\\p1}m 0 0 l 10 0 10 10{\\p0}",
+        ];
+        for src in sources {
+            let text = Text::parse(src)?;
+            assert_eq!(text.to_string(), src);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_keep_comments_and_unknown_tags() -> crate::Result<()> {
+        let text = Text::parse("{TL note\\foo12}a")?;
+        let items = text[0].as_override().unwrap();
+        assert_eq!(items[0], OverrideItem::Comment("TL note".to_string()));
+        assert!(items[1].as_tag().unwrap().is_unknown());
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_tag() -> crate::Result<()> {
+        let mut text = Text::parse("{\\fs 20\\b1}a")?;
+        for tag in text.tags_mut() {
+            if let TagKind::FontSize(size) = tag.kind_mut() {
+                *size = Some(40.0);
+            }
+        }
+        assert_eq!(text.to_string(), "{\\fs40\\b1}a");
+        Ok(())
+    }
+}
@@ -1,21 +1,80 @@
-use encoding_rs::UTF_8;
-use std::fmt::Write;
+use encoding_rs::{Encoding, UTF_8};
+use itertools::Itertools;
+use std::collections::HashSet;
+use std::fmt::{Display, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Read};
+use std::iter::Peekable;
 use strum::VariantNames;
 
 use super::{events::Events, script_info::ScriptInfo, styles::V4Styles};
-use crate::script_info::ScriptType;
+use crate::layout::{split_lines, Layout, LineLayout, RawText, Section, SectionKind};
+use crate::script_info::{Key, ScriptType};
 use crate::{
+    color::YCbCrMatrix,
+    convert::Loss,
+    diagnostic::{Diagnostic, DiagnosticCode, Severity},
+    encoding::EncodeWriter,
     error::Error,
-    events::EventFormat,
+    events::{
+        tag::{Tag, TagKind},
+        EventFormat,
+    },
     fonts::Fonts,
     graphics::Graphics,
-    parser::{Context, SsaParser},
+    lint::{Lint, LintConfig},
+    parser::{Context, Parser, SsaParser},
+    resample::AspectRatio,
     styles::StyleFormat,
+    value::Value,
     version::Version,
 };
 use std::{path::Path, str::FromStr};
 
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct WriteOptions {
+    /// Reproduce the parsed source byte for byte, only values edited since parsing are
+    /// rendered again.
+    pub lossless: bool,
+    /// Line ending used for every line. Defaults to `\n`, or to the line endings of the source
+    /// when writing losslessly.
+    pub line_ending: Option<LineEnding>,
+    /// Encoding of the written bytes, defaults to [`File::encoding`].
+    pub encoding: Option<&'static Encoding>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ReadOptions {
+    /// Decode with this encoding instead of detecting it.
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl WriteOptions {
+    pub fn lossless() -> Self {
+        Self {
+            lossless: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct File {
     pub version: Version,
     pub script: ScriptInfo,
@@ -23,133 +82,608 @@ pub struct File {
     pub events: Events,
     pub fonts: Fonts,
     pub graphics: Graphics,
+    /// The encoding the file was read with, used again when writing it.
+    pub encoding: &'static Encoding,
+    pub(crate) layout: Layout,
 }
 
+impl Default for File {
+    fn default() -> Self {
+        Self {
+            version: Default::default(),
+            script: Default::default(),
+            styles: Default::default(),
+            events: Default::default(),
+            fonts: Default::default(),
+            graphics: Default::default(),
+            encoding: UTF_8,
+            layout: Default::default(),
+        }
+    }
+}
+
+impl PartialEq for File {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.script == other.script
+            && self.styles == other.styles
+            && self.events == other.events
+            && self.fonts == other.fonts
+            && self.graphics == other.graphics
+    }
+}
+
+impl Eq for File {}
+
 impl File {
     pub fn new() -> Self {
         Default::default()
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(ssa_str: impl AsRef<str>) -> crate::Result<Self> {
-        Self::parse(ssa_str.as_ref().as_bytes())
+        Self::from_bytes_with(
+            ssa_str.as_ref().as_bytes(),
+            &ReadOptions {
+                encoding: Some(UTF_8),
+            },
+        )
+    }
+
+    pub fn from_bytes(ssa_bytes: &[u8]) -> crate::Result<Self> {
+        Self::from_bytes_with(ssa_bytes, &ReadOptions::default())
+    }
+
+    pub fn from_bytes_with(ssa_bytes: &[u8], options: &ReadOptions) -> crate::Result<Self> {
+        Self::decode_with(ssa_bytes, options, SsaParser::default()).map(|(file, _)| file)
+    }
+
+    /// Parses leniently: lines that cannot be parsed are reported as [`Diagnostic`]s and kept
+    /// as raw text, so that lossless writing still reproduces them.
+    pub fn from_str_lenient(ssa_str: impl AsRef<str>) -> crate::Result<(Self, Vec<Diagnostic>)> {
+        Self::from_bytes_lenient(
+            ssa_str.as_ref().as_bytes(),
+            &ReadOptions {
+                encoding: Some(UTF_8),
+            },
+        )
+    }
+
+    /// Like [`File::from_str_lenient`], diagnostic spans are byte ranges in the decoded text.
+    pub fn from_bytes_lenient(
+        ssa_bytes: &[u8],
+        options: &ReadOptions,
+    ) -> crate::Result<(Self, Vec<Diagnostic>)> {
+        Self::decode_with(ssa_bytes, options, SsaParser::lenient())
+    }
+
+    fn decode_with(
+        ssa_bytes: &[u8],
+        options: &ReadOptions,
+        parser: SsaParser,
+    ) -> crate::Result<(Self, Vec<Diagnostic>)> {
+        let encoding = options
+            .encoding
+            .unwrap_or_else(|| crate::encoding::detect(ssa_bytes));
+        let (ssa_str, bom) = crate::encoding::decode(ssa_bytes, encoding)?;
+        let (mut file, diagnostics) = Self::parse(&ssa_str, parser)?;
+        file.encoding = encoding;
+        file.layout.bom = bom;
+        Ok((file, diagnostics))
+    }
+
+    /// Reads everything from `reader`, e.g. a socket, an archive entry or stdin. The encoding is
+    /// detected like [`File::from_bytes`] does, so the input is buffered before parsing.
+    pub fn from_reader(reader: impl Read) -> crate::Result<Self> {
+        Self::from_reader_with(reader, &ReadOptions::default())
+    }
+
+    pub fn from_reader_with(mut reader: impl Read, options: &ReadOptions) -> crate::Result<Self> {
+        let mut ssa_bytes = vec![];
+        reader.read_to_end(&mut ssa_bytes)?;
+        Self::from_bytes_with(&ssa_bytes, options)
     }
 
     pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::from_file_with(path, &ReadOptions::default())
+    }
+
+    pub fn from_file_with(path: impl AsRef<Path>, options: &ReadOptions) -> crate::Result<Self> {
         let ssa_bytes = std::fs::read(path)?;
-        Self::parse(&ssa_bytes)
+        Self::from_bytes_with(&ssa_bytes, options)
     }
 
     pub fn to_string(&self) -> crate::Result<String> {
+        self.to_string_with(&WriteOptions::default())
+    }
+
+    pub fn to_string_with(&self, options: &WriteOptions) -> crate::Result<String> {
         let mut ssa = String::new();
-        writeln!(ssa, "[Script Info]")?;
-        writeln!(ssa, "{}", self.script)?;
-        match self.version {
-            Version::V4 => {
-                writeln!(ssa, "[V4 Styles]")?;
-            }
-            Version::V4Plus => {
-                writeln!(ssa, "[V4+ Styles]")?;
-            }
+        let mut writer = LineWriter::new(&mut ssa, options, &self.layout);
+        if options.lossless {
+            self.write_lossless(&mut writer)?;
+        } else {
+            self.write_canonical(&mut writer)?;
         }
-        writeln!(ssa, "{}", self.styles)?;
-        writeln!(ssa, "[Events]")?;
-        writeln!(ssa, "{}", self.events)?;
         Ok(ssa)
     }
 
+    /// Renders the file and encodes it with [`WriteOptions::encoding`] or [`File::encoding`].
+    pub fn to_bytes_with(&self, options: &WriteOptions) -> crate::Result<Vec<u8>> {
+        self.write_with(vec![], options)
+    }
+
+    pub fn write<W: io::Write>(&self, out: W) -> crate::Result<W> {
+        self.write_with(out, &WriteOptions::default())
+    }
+
+    /// Renders and encodes the file into `out` piece by piece, like [`File::to_bytes_with`]
+    /// without holding the whole output in memory. Returns `out` once everything is flushed.
+    pub fn write_with<W: io::Write>(&self, out: W, options: &WriteOptions) -> crate::Result<W> {
+        let encoding = options.encoding.unwrap_or(self.encoding);
+        let mut out = EncodeWriter::new(out, encoding);
+        let mut writer = LineWriter::new(&mut out, options, &self.layout);
+        let result = if options.lossless {
+            self.write_lossless(&mut writer)
+        } else {
+            self.write_canonical(&mut writer)
+        };
+        out.finish(result)
+    }
+
     pub fn write_to(&self, path: impl AsRef<Path>) -> crate::Result<()> {
-        std::fs::write(path, self.to_string()?)?;
+        self.write_to_with(path, &WriteOptions::default())
+    }
+
+    pub fn write_to_with(
+        &self,
+        path: impl AsRef<Path>,
+        options: &WriteOptions,
+    ) -> crate::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_with(io::BufWriter::new(file), options)?;
+        Ok(())
+    }
+
+    /// Rescales styles, margins and override tags from the current `PlayResX` and `PlayResY`
+    /// to `width` x `height`, see [`crate::resample`].
+    pub fn resample(&mut self, width: i64, height: i64, aspect: AspectRatio) -> crate::Result<()> {
+        crate::resample::resample(self, width, height, aspect)
+    }
+
+    /// Converts the colours of styles and colour tags so that they look the same under `matrix`
+    /// and sets it as the `YCbCr Matrix`, see [`YCbCrMatrix::convert`]. Scripts without the
+    /// property are taken to be TV.601, like renderers do.
+    pub fn convert_matrix(&mut self, matrix: YCbCrMatrix) -> crate::Result<()> {
+        let from = match self.script.get_property(Key::YCbCrMatrix) {
+            Some(value) => YCbCrMatrix::parse(&value.to_string())?,
+            None => YCbCrMatrix::Tv601,
+        };
+        for (_, style) in self.styles.iter_mut() {
+            for format in style.iter().map(|(format, _)| format).collect_vec() {
+                if let Some(color) = style.get_mut(format).and_then(Value::as_color_mut) {
+                    *color = from.convert(*color, matrix);
+                }
+            }
+        }
+        for event in self.events.iter_mut() {
+            if let Some(text) = event
+                .get_mut(EventFormat::Text)
+                .and_then(Value::as_text_mut)
+            {
+                for tag in text.tags_mut() {
+                    convert_tag_colors(tag, from, matrix);
+                }
+            }
+        }
+        self.script.set_ycbcr_matrix(matrix);
         Ok(())
     }
 
-    fn parse(ssa_bytes: &[u8]) -> crate::Result<Self> {
-        let (ssa_str, _, had_errors) = UTF_8.decode(ssa_bytes);
-        if had_errors {
-            return Err(Error::InvalidUTF8Encoding);
+    /// Converts the script to SSA v4 or ASS v4+, returning what a V4 script cannot keep, see
+    /// [`crate::convert`].
+    pub fn convert_to(&mut self, version: Version) -> crate::Result<Vec<Loss>> {
+        crate::convert::convert_to(self, version)
+    }
+
+    /// Checks the script against the rules enabled in `config`, see [`crate::lint`].
+    pub fn lint(&self, config: &LintConfig) -> Vec<Lint> {
+        crate::lint::lint(self, config)
+    }
+
+    fn styles_header(&self) -> &'static str {
+        match self.version {
+            Version::V4 => "[V4 Styles]",
+            Version::V4Plus => "[V4+ Styles]",
         }
-        let mut version = Version::V4Plus;
-        let mut parser = SsaParser::default();
-        let mut lines_iter = ssa_str.lines().into_iter();
-        while let Some(line) = lines_iter.next() {
-            let line = line.trim();
-            if line.is_empty() {
+    }
+
+    fn write_canonical<W: Write>(&self, w: &mut LineWriter<W>) -> std::fmt::Result {
+        w.line("[Script Info]")?;
+        for line in self.script.to_string().lines() {
+            w.line(line)?;
+        }
+        w.line("")?;
+        w.line(self.styles_header())?;
+        for line in self.styles.to_string().lines() {
+            w.line(line)?;
+        }
+        w.line("")?;
+        w.line("[Events]")?;
+        w.line(format_args!(
+            "Format: {}",
+            self.events.order().iter().join(", ")
+        ))?;
+        for event in self.events.iter() {
+            w.line(event)?;
+        }
+        w.line("")?;
+        self.write_attachments(w)?;
+        for section in &self.layout.sections {
+            if section.kind != SectionKind::Unknown {
+                continue;
+            }
+            w.line(format_args!("[{}]", section.name().unwrap_or_default()))?;
+            let lines = section
+                .lines
+                .iter()
+                .filter_map(|line| match line {
+                    LineLayout::Raw(raw) => Some(raw.text.trim_end()),
+                    _ => None,
+                })
+                .collect_vec();
+            let len = lines.len() - lines.iter().rev().take_while(|l| l.is_empty()).count();
+            for line in &lines[..len] {
+                w.line(line)?;
+            }
+            w.line("")?;
+        }
+        Ok(())
+    }
+
+    fn write_lossless<W: Write>(&self, w: &mut LineWriter<W>) -> std::fmt::Result {
+        if self.layout.bom {
+            w.out.write_char('\u{FEFF}')?;
+        }
+        let (mut script_written, mut styles_written, mut events_written) = (false, false, false);
+        let attachments_changed =
+            hash_of(&(&self.fonts, &self.graphics)) != self.layout.attachments_hash;
+        let mut attachments_written = false;
+        for section in &self.layout.sections {
+            match (&section.header, section.kind) {
+                (Some(_), SectionKind::Fonts | SectionKind::Graphics) if attachments_changed => {}
+                (Some(_), SectionKind::Styles(version)) if version != self.version => {
+                    w.line(self.styles_header())?
+                }
+                (Some(header), _) => w.raw(header)?,
+                (None, _) => {}
+            }
+            match section.kind {
+                SectionKind::ScriptInfo => {
+                    script_written = true;
+                    self.write_script_info_lossless(w, section)?;
+                }
+                SectionKind::Styles(_) => {
+                    styles_written = true;
+                    let order = self.styles.order().iter().join(", ");
+                    self.write_items_lossless(w, section, &order, |w| {
+                        for (_, style) in self.styles.iter() {
+                            let raw = style.raw.as_deref();
+                            w.item(raw.map(|raw| (&raw.leading, raw.eol)), |out| {
+                                style.write_lossless(out)
+                            })?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                SectionKind::Events => {
+                    events_written = true;
+                    let order = self.events.order().iter().join(", ");
+                    self.write_items_lossless(w, section, &order, |w| {
+                        for event in self.events.iter() {
+                            let raw = event.raw.as_deref();
+                            w.item(raw.map(|raw| (&raw.leading, raw.eol)), |out| {
+                                event.write_lossless(out)
+                            })?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                SectionKind::Fonts | SectionKind::Graphics if attachments_changed => {
+                    if !attachments_written {
+                        attachments_written = true;
+                        self.write_attachments(w)?;
+                    }
+                }
+                _ => {
+                    for line in &section.lines {
+                        if let LineLayout::Raw(raw) = line {
+                            w.raw(raw)?;
+                        }
+                    }
+                }
+            }
+        }
+        if !script_written {
+            w.line("[Script Info]")?;
+            for line in self.script.to_string().lines() {
+                w.line(line)?;
+            }
+            w.line("")?;
+        }
+        if !styles_written && self.styles.iter().next().is_some() {
+            w.line(self.styles_header())?;
+            for line in self.styles.to_string().lines() {
+                w.line(line)?;
+            }
+            w.line("")?;
+        }
+        if !events_written && !self.events.is_empty() {
+            w.line("[Events]")?;
+            w.line(format_args!(
+                "Format: {}",
+                self.events.order().iter().join(", ")
+            ))?;
+            for event in self.events.iter() {
+                w.line(event)?;
+            }
+            w.line("")?;
+        }
+        if attachments_changed && !attachments_written {
+            self.write_attachments(w)?;
+        }
+        Ok(())
+    }
+
+    fn write_attachments<W: Write>(&self, w: &mut LineWriter<W>) -> std::fmt::Result {
+        for (header, key, attachments) in [
+            ("[Fonts]", "fontname", &self.fonts.fonts),
+            ("[Graphics]", "filename", &self.graphics.graphics),
+        ] {
+            if attachments.is_empty() {
                 continue;
             }
+            w.line(header)?;
+            for attachment in attachments {
+                w.line(format_args!("{}: {}", key, attachment.name))?;
+                for line in attachment.encoded_lines() {
+                    w.line(line)?;
+                }
+            }
+            w.line("")?;
+        }
+        Ok(())
+    }
+
+    fn write_script_info_lossless<W: Write>(
+        &self,
+        w: &mut LineWriter<W>,
+        section: &Section,
+    ) -> std::fmt::Result {
+        let source_comments = section
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                LineLayout::Comment(raw) => Some(comment_text(&raw.text)),
+                _ => None,
+            })
+            .collect_vec();
+        let comments = self
+            .script
+            .get_comments()
+            .map(|comments| comments.iter().map(|c| c.to_string()).collect_vec())
+            .unwrap_or_default();
+        let comments_changed = source_comments != comments;
+        let mut comments_written = false;
+        let source_keys: HashSet<&str> = section
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                LineLayout::Property { key, .. } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        let insert_at = section
+            .lines
+            .iter()
+            .rposition(|line| !matches!(line, LineLayout::Raw(raw) if raw.text.trim().is_empty()))
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let write_new = |w: &mut LineWriter<W>, comments_written: &mut bool| {
+            if comments_changed && !*comments_written {
+                *comments_written = true;
+                for comment in &comments {
+                    w.line(format_args!("; {}", comment))?;
+                }
+            }
+            for (key, value) in self.script.iter() {
+                let implicit = self
+                    .layout
+                    .implicit
+                    .iter()
+                    .any(|(k, v)| k == key && v == value);
+                if key == Key::Comment.as_ref() || source_keys.contains(key) || implicit {
+                    continue;
+                }
+                let mut line = String::new();
+                ScriptInfo::write_property(&mut line, key, value)?;
+                w.line(line)?;
+            }
+            Ok::<_, std::fmt::Error>(())
+        };
+        for (i, line) in section.lines.iter().enumerate() {
+            if i == insert_at {
+                write_new(w, &mut comments_written)?;
+            }
+            match line {
+                LineLayout::Comment(raw) => {
+                    if !comments_changed {
+                        w.raw(raw)?;
+                    } else if !comments_written {
+                        comments_written = true;
+                        for comment in &comments {
+                            w.line(format_args!("; {}", comment))?;
+                        }
+                    }
+                }
+                LineLayout::Property { key, value, raw } => match self.script.get_property(key) {
+                    Some(current) if Some(current) == value.as_ref() => w.raw(raw)?,
+                    Some(current) => {
+                        let mut line = String::new();
+                        ScriptInfo::write_property(&mut line, key, current)?;
+                        w.line_with(line, raw.eol)?;
+                    }
+                    None => {}
+                },
+                LineLayout::Raw(raw) => w.raw(raw)?,
+                LineLayout::Format { .. } | LineLayout::Items => {}
+            }
+        }
+        if insert_at == section.lines.len() {
+            write_new(w, &mut comments_written)?;
+        }
+        Ok(())
+    }
+
+    fn write_items_lossless<W: Write>(
+        &self,
+        w: &mut LineWriter<W>,
+        section: &Section,
+        order: &str,
+        write_items: impl Fn(&mut LineWriter<W>) -> std::fmt::Result,
+    ) -> std::fmt::Result {
+        for line in &section.lines {
+            match line {
+                LineLayout::Format { order: source, raw } if source == order => w.raw(raw)?,
+                LineLayout::Format { raw, .. } => {
+                    w.line_with(format_args!("Format: {}", order), raw.eol)?
+                }
+                LineLayout::Items => write_items(w)?,
+                LineLayout::Raw(raw) | LineLayout::Comment(raw) => w.raw(raw)?,
+                LineLayout::Property { raw, .. } => w.raw(raw)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn parse(
+        ssa_str: &str,
+        mut parser: SsaParser,
+    ) -> crate::Result<(Self, Vec<Diagnostic>)> {
+        let mut version = Version::V4Plus;
+        parser.layout.line_ending = match split_lines(ssa_str).map(|(_, eol)| eol).next() {
+            Some("\r\n") => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        };
+        parser
+            .layout
+            .sections
+            .push(Section::new(SectionKind::Preamble, None));
+        let mut lines_iter = split_lines(ssa_str).peekable();
+        while let Some((src, eol)) = lines_iter.next() {
+            parser.advance(src, eol);
+            let line = src.trim();
             if line.starts_with('[') && line.ends_with(']') {
+                parser.flush_attachment()?;
+                let header = RawText::new(src, eol);
                 let section = line[1..line.len() - 1].trim().to_lowercase();
                 match section.as_str() {
                     "script info" => {
                         parser.context = Context::ParseScriptInfo;
+                        parser.push_section(Section::new(SectionKind::ScriptInfo, Some(header)));
                         continue;
                     }
-                    "v4 styles" => {
-                        version = Version::V4;
-                        parser.context = Context::ParseStyles;
-                        let header = lines_iter.next().ok_or(Error::ParseError {
-                            ty: "header",
-                            msg: "missing v4 styles header".to_string(),
-                        })?;
-                        let order = Self::parse_style_header(header)?;
-                        parser.styles = V4Styles::new(order)?;
-                        continue;
-                    }
-                    "v4+ styles" => {
-                        version = Version::V4Plus;
+                    "v4 styles" | "v4+ styles" => {
+                        version = if section == "v4 styles" {
+                            Version::V4
+                        } else {
+                            Version::V4Plus
+                        };
                         parser.context = Context::ParseStyles;
-                        let header = lines_iter.next().ok_or(Error::ParseError {
-                            ty: "header",
-                            msg: "missing v4+ styles header".to_string(),
-                        })?;
-                        let order = Self::parse_style_header(header)?;
-                        parser.styles = V4Styles::new(order)?;
+                        let mut layout = Section::new(SectionKind::Styles(version), Some(header));
+                        let styles = Self::parse_format(
+                            &mut parser,
+                            &mut lines_iter,
+                            &mut layout,
+                            &section,
+                            |format| Self::parse_style_header(format).and_then(V4Styles::new),
+                            |styles| styles.order().iter().join(", "),
+                        )?;
+                        parser.styles = styles.unwrap_or_default();
+                        layout.lines.push(LineLayout::Items);
+                        parser.push_section(layout);
                         continue;
                     }
                     "events" => {
                         parser.context = Context::ParseEvents;
-                        let header = lines_iter.next().ok_or(Error::ParseError {
-                            ty: "header",
-                            msg: "missing events header".to_string(),
-                        })?;
-                        let order = Self::parse_event_header(header)?;
-                        parser.events = Events::new(order);
+                        let mut layout = Section::new(SectionKind::Events, Some(header));
+                        let events = Self::parse_format(
+                            &mut parser,
+                            &mut lines_iter,
+                            &mut layout,
+                            &section,
+                            |format| Self::parse_event_header(format).map(Events::new),
+                            |events| events.order().iter().join(", "),
+                        )?;
+                        parser.events = events.unwrap_or_default();
+                        layout.lines.push(LineLayout::Items);
+                        parser.push_section(layout);
                         continue;
                     }
                     "fonts" => {
                         parser.context = Context::ParseFonts;
+                        parser.push_section(Section::new(SectionKind::Fonts, Some(header)));
                         continue;
                     }
                     "graphics" => {
                         parser.context = Context::ParseGraphics;
+                        parser.push_section(Section::new(SectionKind::Graphics, Some(header)));
+                        continue;
+                    }
+                    _ => {
+                        parser.context = Context::ParseUnknown;
+                        parser.push_section(Section::new(SectionKind::Unknown, Some(header)));
                         continue;
                     }
-                    _ => {}
                 }
             }
             match parser.context {
-                Context::None => {}
                 Context::ParseScriptInfo => {
-                    parser.parse_script_info(line)?;
+                    parser.parse_script_info(src)?;
                 }
                 Context::ParseStyles => {
-                    parser.parse_styles(line)?;
+                    parser.parse_styles(src)?;
                 }
                 Context::ParseEvents => {
-                    parser.parse_events(line)?;
+                    parser.parse_events(src)?;
                 }
                 Context::ParseFonts => {
-                    parser.parse_fonts(line)?;
+                    parser
+                        .parse_fonts(line)
+                        .map_err(|error| error.at_line(parser.line))?;
+                    parser.push_line(LineLayout::Raw(RawText::new(src, eol)));
                 }
                 Context::ParseGraphics => {
-                    parser.parse_graphics(line)?;
+                    parser
+                        .parse_graphics(line)
+                        .map_err(|error| error.at_line(parser.line))?;
+                    parser.push_line(LineLayout::Raw(RawText::new(src, eol)));
+                }
+                Context::None | Context::ParseUnknown => {
+                    parser.push_line(LineLayout::Raw(RawText::new(src, eol)));
                 }
             }
         }
+        parser.flush_attachment()?;
+        parser.flush_pending();
         let SsaParser {
             mut script_info,
             styles,
             events,
             fonts,
             graphics,
+            mut layout,
+            diagnostics,
             ..
         } = parser;
         if script_info.get_script_type().is_none() {
@@ -161,7 +695,13 @@ impl File {
                     script_info.set_script_type(ScriptType::V4Plus);
                 }
             }
+            if let Some(script_type) = script_info.get_property(Key::ScriptType) {
+                layout
+                    .implicit
+                    .push((Key::ScriptType.to_string(), script_type.clone()));
+            }
         }
+        layout.attachments_hash = hash_of(&(&fonts, &graphics));
         let file = File {
             version,
             script: script_info,
@@ -169,20 +709,84 @@ impl File {
             events,
             fonts,
             graphics,
+            encoding: UTF_8,
+            layout,
+        };
+        Ok((file, diagnostics.unwrap_or_default()))
+    }
+
+    /// Reads the `Format:` line following the header of a styles or events section. Parsing
+    /// leniently, a missing or invalid line is reported and `None` returned so that the default
+    /// order is used instead.
+    fn parse_format<'a, T>(
+        parser: &mut SsaParser,
+        lines: &mut Peekable<impl Iterator<Item = (&'a str, &'static str)>>,
+        layout: &mut Section,
+        section: &str,
+        parse: impl Fn(&str) -> crate::Result<T>,
+        order: impl Fn(&T) -> String,
+    ) -> crate::Result<Option<T>> {
+        let is_format = |line: &str| {
+            line.split_once(':')
+                .is_some_and(|(key, _)| key.trim().eq_ignore_ascii_case("format"))
+        };
+        let lenient = parser.diagnostics.is_some();
+        let header = layout
+            .header
+            .as_ref()
+            .map_or("", |header| header.text.as_str());
+        match lines.peek() {
+            Some((format, _)) if !lenient || is_format(format) => {}
+            None if !lenient => {
+                let error = Error::ParseError {
+                    ty: "header",
+                    msg: format!("missing {} format", section),
+                };
+                return Err(error.at_line(parser.line));
+            }
+            _ => {
+                parser.diagnose(
+                    header,
+                    Severity::Error,
+                    DiagnosticCode::MissingFormat,
+                    0..header.trim_end().len(),
+                    format!("missing {} format", section),
+                );
+                return Ok(None);
+            }
+        }
+        let Some((format, eol)) = lines.next() else {
+            return Ok(None);
         };
-        Ok(file)
+        parser.advance(format, eol);
+        let raw = RawText::new(format, eol);
+        match parse(format) {
+            Ok(value) => {
+                layout.lines.push(LineLayout::Format {
+                    order: order(&value),
+                    raw,
+                });
+                Ok(Some(value))
+            }
+            Err(error) => {
+                let span = 0..format.trim_end().len();
+                parser.recover(format, error, DiagnosticCode::InvalidFormat, span)?;
+                layout.lines.push(LineLayout::Raw(raw));
+                Ok(None)
+            }
+        }
     }
 
-    fn parse_style_header(header: &str) -> crate::Result<Vec<StyleFormat>> {
+    pub(crate) fn parse_style_header(header: &str) -> crate::Result<Vec<StyleFormat>> {
         match header.find(':') {
             Some(pos) => {
-                if header[..pos].trim().to_ascii_lowercase() != "format" {
+                if !header[..pos].trim().eq_ignore_ascii_case("format") {
                     return Err(Error::invalid_type("format"));
                 }
                 let order = header[pos + 1..]
                     .split(',')
                     .map(|s| s.trim())
-                    .map(|s| StyleFormat::from_str(s))
+                    .map(StyleFormat::from_str)
                     .collect::<Result<Vec<_>, _>>()
                     .map_err(|error| {
                         Error::invalid_type(format!(
@@ -199,16 +803,16 @@ impl File {
         }
     }
 
-    fn parse_event_header(header: &str) -> crate::Result<Vec<EventFormat>> {
+    pub(crate) fn parse_event_header(header: &str) -> crate::Result<Vec<EventFormat>> {
         match header.find(':') {
             Some(pos) => {
-                if header[..pos].trim().to_ascii_lowercase() != "format" {
+                if !header[..pos].trim().eq_ignore_ascii_case("format") {
                     return Err(Error::invalid_type("format"));
                 }
                 let order = header[pos + 1..]
                     .split(',')
                     .map(|s| s.trim())
-                    .map(|s| EventFormat::from_str(s))
+                    .map(EventFormat::from_str)
                     .collect::<Result<Vec<_>, _>>()
                     .map_err(|error| {
                         Error::invalid_type(format!(
@@ -223,3 +827,412 @@ impl File {
         }
     }
 }
+
+fn convert_tag_colors(tag: &mut Tag, from: YCbCrMatrix, to: YCbCrMatrix) {
+    let kind = match tag.kind() {
+        TagKind::Color {
+            index,
+            color: Some(color),
+        } => TagKind::Color {
+            index: *index,
+            color: Some(from.convert(*color, to)),
+        },
+        TagKind::Transform { times, accel, tags } => {
+            let mut tags = tags.clone();
+            for tag in tags.iter_mut() {
+                convert_tag_colors(tag, from, to);
+            }
+            TagKind::Transform {
+                times: *times,
+                accel: *accel,
+                tags,
+            }
+        }
+        _ => return,
+    };
+    if kind != *tag.kind() {
+        *tag.kind_mut() = kind;
+    }
+}
+
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn comment_text(line: &str) -> String {
+    let line = line.trim();
+    line.strip_prefix(';')
+        .or_else(|| line.strip_prefix("!:"))
+        .unwrap_or(line)
+        .trim()
+        .to_string()
+}
+
+/// Writes lines with either the requested line ending or the one they were parsed with.
+struct LineWriter<'a, W> {
+    out: &'a mut W,
+    eol: &'static str,
+    force_eol: bool,
+}
+
+impl<'a, W: Write> LineWriter<'a, W> {
+    fn new(out: &'a mut W, options: &WriteOptions, layout: &Layout) -> Self {
+        let eol = match (options.line_ending, options.lossless) {
+            (Some(line_ending), _) => line_ending.as_str(),
+            (None, true) => layout.line_ending.as_str(),
+            (None, false) => LineEnding::Lf.as_str(),
+        };
+        Self {
+            out,
+            eol,
+            force_eol: options.line_ending.is_some(),
+        }
+    }
+
+    fn line(&mut self, content: impl Display) -> std::fmt::Result {
+        write!(self.out, "{}{}", content, self.eol)
+    }
+
+    fn line_with(&mut self, content: impl Display, eol: &'static str) -> std::fmt::Result {
+        write!(self.out, "{}{}", content, self.eol_for(eol))
+    }
+
+    fn raw(&mut self, raw: &RawText) -> std::fmt::Result {
+        write!(self.out, "{}{}", raw.text, self.eol_for(raw.eol))
+    }
+
+    fn item(
+        &mut self,
+        raw: Option<(&Vec<RawText>, &'static str)>,
+        write: impl FnOnce(&mut W) -> std::fmt::Result,
+    ) -> std::fmt::Result {
+        match raw {
+            Some((leading, eol)) => {
+                for line in leading {
+                    self.raw(line)?;
+                }
+                write(self.out)?;
+                write!(self.out, "{}", self.eol_for(eol))
+            }
+            None => {
+                write(self.out)?;
+                write!(self.out, "{}", self.eol)
+            }
+        }
+    }
+
+    /// The last line of a file may have no terminator, which is kept as is.
+    fn eol_for(&self, eol: &'static str) -> &'static str {
+        if self.force_eol && !eol.is_empty() {
+            self.eol
+        } else {
+            eol
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{File, LineEnding, ReadOptions, WriteOptions};
+    use crate::attachment::Attachment;
+    use crate::color::YCbCrMatrix;
+    use crate::diagnostic::{DiagnosticCode, Severity};
+    use crate::events::EventFormat;
+    use crate::styles::StyleFormat;
+    use crate::value::Value;
+    use encoding_rs::{GBK, UTF_16BE, UTF_16LE, UTF_8};
+
+    const SOURCE: &str = "\u{FEFF}[Script Info]\r\n\
+; Script generated by Aegisub\r\n\
+!: legacy comment\r\n\
+Title: Round trip\r\n\
+PlayResX:1920\r\n\
+PlayResY: 1080\r\n\
+ScaledBorderAndShadow: Yes\r\n\
+YCbCr Matrix: TV.709\r\n\
+\r\n\
+[Aegisub Project Garbage]\r\n\
+Video File: ep01.mkv\r\n\
+\r\n\
+[V4+ Styles]\r\n\
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\r\n\
+Style: Default,Arial,60,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100.00,100,0.10,0,1,2.0,0,2,10,10,10,1\r\n\
+; sign styles\r\n\
+Style: Sign,Arial,40,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,8,10,10,10,1\r\n\
+\r\n\
+[Events]\r\n\
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\n\
+Comment: 0,0:00:00.00,0:00:00.00,Default,,0000,0000,0000,,{\\an8}note, with comma\r\n\
+Dialogue: 0,0:00:01.50,0:00:04.00,Default,,0,0,0,,Hello\\NWorld  \r\n\
+\r\n\
+[Aegisub Extradata]\r\n\
+Data: 1,key,value\r\n\
+\r\n\
+[Fonts]\r\n\
+fontname: a.ttf\r\n\
+!!!!\r\n";
+
+    #[test]
+    fn test_lossless_round_trip() -> crate::Result<()> {
+        let file = File::from_str(SOURCE)?;
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, SOURCE);
+        let no_script_type = "[Script Info]\nTitle: x\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,a";
+        let file = File::from_str(no_script_type)?;
+        assert_eq!(
+            file.to_string_with(&WriteOptions::lossless())?,
+            no_script_type
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lossless_only_renders_edits() -> crate::Result<()> {
+        let mut file = File::from_str(SOURCE)?;
+        file.styles
+            .get_mut("Default")
+            .unwrap()
+            .set(StyleFormat::Fontsize, 72);
+        file.events[1].set(EventFormat::Layer, 1);
+        file.script.set_play_res_y(720);
+        file.script.set_wrap_style(0);
+        let expected = SOURCE
+            .replace("Arial,60,", "Arial,72,")
+            .replace("Dialogue: 0,", "Dialogue: 1,")
+            .replace("PlayResY: 1080", "PlayResY: 720")
+            .replace(
+                "YCbCr Matrix: TV.709\r\n",
+                "YCbCr Matrix: TV.709\r\nWrapStyle: 0\r\n",
+            );
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, expected);
+        file.events.remove(0);
+        file.script.remove_property("Title");
+        let expected = expected.replace("Title: Round trip\r\n", "").replace(
+            "Comment: 0,0:00:00.00,0:00:00.00,Default,,0000,0000,0000,,{\\an8}note, with comma\r\n",
+            "",
+        );
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lossless_line_ending_override() -> crate::Result<()> {
+        let file = File::from_str(SOURCE)?;
+        let options = WriteOptions {
+            lossless: true,
+            line_ending: Some(LineEnding::Lf),
+            ..Default::default()
+        };
+        assert_eq!(file.to_string_with(&options)?, SOURCE.replace("\r\n", "\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_keeps_unknown_sections() -> crate::Result<()> {
+        let file = File::from_str(SOURCE)?;
+        let ssa = file.to_string()?;
+        assert!(ssa.contains("[Aegisub Project Garbage]\nVideo File: ep01.mkv\n"));
+        assert!(ssa.contains("[Aegisub Extradata]\nData: 1,key,value\n"));
+        assert!(ssa.contains("; legacy comment\n"));
+        let reparsed = File::from_str(&ssa)?;
+        assert_eq!(reparsed.script, file.script);
+        assert_eq!(reparsed.styles, file.styles);
+        assert_eq!(reparsed.events, file.events);
+        assert_eq!(
+            reparsed.script.get_property("YCbCr Matrix"),
+            Some(&Value::Str("TV.709".to_string()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_encoding() -> crate::Result<()> {
+        let source = "[Script Info]\r\nTitle: 字幕测试\r\n\r\n[Events]\r\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\nDialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,你好，世界！这是一个用于检测编码的中文字幕文件。\r\n";
+        let mut utf16: Vec<u8> = b"\xFF\xFE".to_vec();
+        utf16.extend(source.encode_utf16().flat_map(u16::to_le_bytes));
+        let file = File::from_bytes(&utf16)?;
+        assert_eq!(file.encoding, UTF_16LE);
+        assert_eq!(file.script.get_title(), Some("字幕测试"));
+        assert_eq!(file.to_bytes_with(&WriteOptions::lossless())?, utf16);
+        // canonical output keeps the byte order mark so that it is read back as UTF-16
+        let canonical = file.to_bytes_with(&WriteOptions::default())?;
+        assert_eq!(&canonical[..2], b"\xFF\xFE");
+        let reread = File::from_bytes(&canonical)?;
+        assert_eq!(reread.encoding, UTF_16LE);
+        assert_eq!(reread.script.get_title(), Some("字幕测试"));
+        let options = WriteOptions {
+            encoding: Some(UTF_16BE),
+            ..Default::default()
+        };
+        let reread = File::from_bytes(&file.to_bytes_with(&options)?)?;
+        assert_eq!(reread.encoding, UTF_16BE);
+        assert_eq!(reread.events, file.events);
+
+        let (gbk, _, _) = GBK.encode(source);
+        let file = File::from_bytes(&gbk)?;
+        assert_eq!(file.encoding, GBK);
+        assert_eq!(file.to_bytes_with(&WriteOptions::lossless())?, gbk.to_vec());
+        let options = WriteOptions {
+            lossless: true,
+            encoding: Some(UTF_8),
+            ..Default::default()
+        };
+        assert_eq!(file.to_bytes_with(&options)?, source.as_bytes());
+
+        let options = ReadOptions {
+            encoding: Some(UTF_8),
+        };
+        assert!(File::from_bytes_with(&gbk, &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reader_writer() -> crate::Result<()> {
+        let mut source =
+            String::from("[Script Info]\nTitle: 字幕\n\n[Events]\nFormat: Start, End, Text\n");
+        for i in 0..500 {
+            source.push_str(&format!(
+                "Dialogue: 0:00:00.00,0:00:01.00,第{}行，你好，世界！今天天气很好。\n",
+                i
+            ));
+        }
+        let (gbk, _, _) = GBK.encode(&source);
+        let file = File::from_reader(&gbk[..])?;
+        assert_eq!(file.encoding, GBK);
+        assert_eq!(
+            file.write_with(vec![], &WriteOptions::lossless())?,
+            gbk.to_vec()
+        );
+        let options = WriteOptions {
+            line_ending: Some(LineEnding::CrLf),
+            encoding: Some(UTF_8),
+            ..Default::default()
+        };
+        let out = file.write_with(vec![], &options)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            file.to_string_with(&options)?
+        );
+        let options = WriteOptions {
+            encoding: Some(encoding_rs::SHIFT_JIS),
+            ..Default::default()
+        };
+        let mut file = file;
+        file.script.set_title("한국어");
+        assert!(file.write_with(vec![], &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_keeps_bad_lines() -> crate::Result<()> {
+        let source = "[Script Info]\n\
+PlayResX: wide\n\
+\n\
+[V4+ Styles]\n\
+Format: Name, PrimaryColour\n\
+Style: Default,&H00FFFFFF\n\
+Style: Bad,&Hxyz\n\
+Style: Default,&H000000FF\n\
+\n\
+[Events]\n\
+Format: Layer, Start, End, Style, Text\n\
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,ok\n\
+Dialogue: 0,0:00:0x.00,0:00:02.00,Default,bad\n\
+Banner: 1,2\n\
+Dialogue: 0,0:00:03.00\n";
+        let (file, diagnostics) = File::from_str_lenient(source)?;
+        let found = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.severity, d.code))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (2, 11, Severity::Error, DiagnosticCode::InvalidProperty),
+                (7, 12, Severity::Error, DiagnosticCode::InvalidStyle),
+                (8, 8, Severity::Warning, DiagnosticCode::DuplicateStyle),
+                (13, 13, Severity::Error, DiagnosticCode::InvalidEvent),
+                (14, 1, Severity::Error, DiagnosticCode::UnknownEventType),
+                (15, 23, Severity::Warning, DiagnosticCode::MissingFields),
+            ]
+        );
+        assert_eq!(&source[diagnostics[1].span.clone()], "&Hxyz");
+        assert_eq!(file.events.len(), 2);
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, source);
+
+        let error = File::from_str(source).unwrap_err();
+        assert_eq!(error.line(), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lenient_missing_format() -> crate::Result<()> {
+        let source = "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Begin\nDialogue: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,a\n\n[V4+ Styles]\n";
+        let (file, diagnostics) = File::from_str_lenient(source)?;
+        assert_eq!(diagnostics[0].code, DiagnosticCode::InvalidFormat);
+        assert_eq!(diagnostics[0].line, 5);
+        assert_eq!(diagnostics[1].code, DiagnosticCode::MissingFormat);
+        assert_eq!(diagnostics[1].line, 8);
+        assert_eq!(file.events.len(), 1);
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, source);
+        assert_eq!(File::from_str(source).unwrap_err().line(), Some(5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_attachments() -> crate::Result<()> {
+        let source =
+            "[Script Info]\nScriptType: v4.00+\n\n[Fonts]\nfontname: a_0.ttf\n47&O\n47%\n\n";
+        let mut file = File::from_str(source)?;
+        assert_eq!(file.fonts.find("a_0.ttf").unwrap().data, b"ManMa");
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, source);
+        assert!(file
+            .to_string()?
+            .ends_with("[Fonts]\nfontname: a_0.ttf\n47&O47%\n\n"));
+
+        file.graphics
+            .push(Attachment::new("logo.png", b"Man".to_vec()));
+        let ssa = file.to_string_with(&WriteOptions::lossless())?;
+        assert!(ssa.ends_with("[Graphics]\nfilename: logo.png\n47&O\n\n"));
+        assert_eq!(File::from_str(&ssa)?, file);
+
+        let dir =
+            std::env::temp_dir().join(format!("ssa_parser_attachments_{}", std::process::id()));
+        let paths = file.fonts.extract_to(&dir)?;
+        let mut reloaded = File::new();
+        reloaded.fonts.embed(&paths[0])?;
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(reloaded.fonts, file.fonts);
+
+        let (file, diagnostics) = File::from_str_lenient(source.replace("47%", "47~"))?;
+        assert!(file.fonts.is_empty());
+        assert_eq!(diagnostics[0].code, DiagnosticCode::InvalidAttachment);
+        assert_eq!(diagnostics[0].line, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_matrix() -> crate::Result<()> {
+        let source = "[Script Info]\nScriptType: v4.00+\nYCbCr Matrix: TV.601\n\n[V4+ Styles]\n\
+             Format: Name, PrimaryColour, OutlineColour\nStyle: Default,&H003C78C8,&H00646464\n\n\
+             [Events]\nFormat: Start, End, Style, Text\n\
+             Dialogue: 0:00:00.00,0:00:01.00,Default,{\\c&H3C78C8&\\t(\\3c&H3C78C8&)\\b1}a\n";
+        let mut file = File::from_str(source)?;
+        file.convert_matrix(YCbCrMatrix::Tv709)?;
+        assert_eq!(file.script.get_ycbcr_matrix(), Some(YCbCrMatrix::Tv709));
+        let expected = source
+            .replace("TV.601", "TV.709")
+            .replace("&H003C78C8", "&H00387CD0")
+            .replace("&H3C78C8&", "&H387CD0&");
+        assert_eq!(file.to_string_with(&WriteOptions::lossless())?, expected);
+
+        file.script
+            .add_property("YCbCr Matrix", "TV.2020".to_string());
+        assert!(file.convert_matrix(YCbCrMatrix::Tv601).is_err());
+        file.script.remove_property("YCbCr Matrix");
+        file.convert_matrix(YCbCrMatrix::None)?;
+        assert_eq!(file.script.get_ycbcr_matrix(), Some(YCbCrMatrix::None));
+        Ok(())
+    }
+}
@@ -1,12 +1,41 @@
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+use crate::attachment::{self, Attachment};
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Fonts {
-    pub fonts: Vec<String>,
+    pub fonts: Vec<Attachment>,
+}
+
+impl Fonts {
+    pub fn find(&self, name: &str) -> Option<&Attachment> {
+        self.fonts.iter().find(|attachment| attachment.name == name)
+    }
+
+    /// Embeds the font file at `path`, replacing an attachment of the same name.
+    pub fn embed(&mut self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let attachment = Attachment::from_path(path)?;
+        match self.fonts.iter_mut().find(|a| a.name == attachment.name) {
+            Some(existing) => *existing = attachment,
+            None => self.fonts.push(attachment),
+        }
+        Ok(())
+    }
+
+    /// Writes every attachment to `dir`, creating it if needed.
+    pub fn extract_to(&self, dir: impl AsRef<Path>) -> crate::Result<Vec<PathBuf>> {
+        attachment::write_all(&self.fonts, dir.as_ref())
+    }
 }
 
 impl Deref for Fonts {
-    type Target = Vec<String>;
+    type Target = Vec<Attachment>;
 
     fn deref(&self) -> &Self::Target {
         &self.fonts
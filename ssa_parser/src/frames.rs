@@ -0,0 +1,390 @@
+use std::path::Path;
+
+use crate::{error::Error, timestamp::Timestamp};
+
+/// Frames a v1 timecode file may cover, over 46 hours at 60 fps. Every frame of the ranges is
+/// stored, so larger ones would allocate without bound.
+const MAX_V1_FRAMES: i64 = 10_000_000;
+
+/// Which time of a frame is meant, following the rounding rules of Aegisub.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Boundary {
+    /// The time the frame is first shown.
+    Exact,
+    /// The start time of an event that begins on the frame, halfway between the frame and the
+    /// one before it.
+    Start,
+    /// The end time of an event that ends on the frame, halfway between the frame and the one
+    /// after it.
+    End,
+}
+
+/// The time every frame of a video is shown at, either for a constant frame rate or read from
+/// an mkvmerge timecode file. Times are in milliseconds like in the timecode files.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FrameTimes {
+    /// Start of every known frame, only the first one for a constant frame rate.
+    timecodes: Vec<i64>,
+    /// Frame rate used before the first and after the last known frame.
+    numerator: i64,
+    denominator: i64,
+}
+
+impl FrameTimes {
+    /// A constant frame rate of `numerator / denominator`, e.g. `24000 / 1001`.
+    pub fn constant(numerator: u32, denominator: u32) -> crate::Result<Self> {
+        if numerator == 0 || denominator == 0 {
+            return Err(Error::parse_error::<FrameTimes>(format!(
+                "invalid frame rate {}/{}",
+                numerator, denominator
+            )));
+        }
+        Ok(Self {
+            timecodes: vec![0],
+            numerator: numerator.into(),
+            denominator: denominator.into(),
+        })
+    }
+
+    pub fn from_fps(fps: f64) -> crate::Result<Self> {
+        let mut times = Self::constant(1, 1)?;
+        times.set_fps(fps)?;
+        Ok(times)
+    }
+
+    fn set_fps(&mut self, fps: f64) -> crate::Result<()> {
+        if !valid_fps(fps) {
+            return Err(Error::parse_error::<FrameTimes>(format!(
+                "invalid frame rate {}",
+                fps
+            )));
+        }
+        self.denominator = 1_000_000;
+        self.numerator = (fps * self.denominator as f64).round() as i64;
+        Ok(())
+    }
+
+    /// Parses an mkvmerge timecode file of format v1, v2 or v4.
+    pub fn from_timecodes(src: &str) -> crate::Result<Self> {
+        let mut lines = src
+            .lines()
+            .map(str::trim)
+            .zip(1..)
+            .filter(|(line, _)| !line.is_empty());
+        let header = lines.next().map_or("", |(line, _)| line);
+        let version = header.strip_prefix('#').map(str::trim).and_then(|header| {
+            header
+                .strip_prefix("timecode format v")
+                .or_else(|| header.strip_prefix("timestamp format v"))
+        });
+        let lines = lines.filter(|(line, _)| !line.starts_with('#'));
+        match version {
+            Some("1") => Self::parse_v1(lines),
+            Some("2") | Some("4") => Self::parse_v2(lines),
+            _ => Err(Error::parse_error::<FrameTimes>(format!(
+                "unknown timecode format {}",
+                header
+            ))
+            .at_line(1)),
+        }
+    }
+
+    pub fn from_timecodes_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::from_timecodes(&std::fs::read_to_string(path)?)
+    }
+
+    /// `Assume <fps>` followed by `start,end,fps` lines overriding the rate of frame ranges.
+    fn parse_v1<'a>(mut lines: impl Iterator<Item = (&'a str, usize)>) -> crate::Result<Self> {
+        let invalid = |line: &str, number: usize| {
+            Error::parse_error::<FrameTimes>(format!("invalid timecode line {}", line))
+                .at_line(number)
+        };
+        let (assume, number) = lines
+            .next()
+            .ok_or_else(|| Error::parse_error::<FrameTimes>("missing Assume line"))?;
+        let fps = assume
+            .get(..6)
+            .filter(|key| key.eq_ignore_ascii_case("assume"))
+            .and_then(|_| assume[6..].trim().parse::<f64>().ok())
+            .ok_or_else(|| invalid(assume, number))?;
+        let mut times = Self::from_fps(fps).map_err(|error| error.at_line(number))?;
+        let mut ranges = vec![];
+        for (line, number) in lines {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let [start, end, fps] = fields[..] else {
+                return Err(invalid(line, number));
+            };
+            let (Ok(start), Ok(end), Ok(fps)) =
+                (start.parse::<i64>(), end.parse::<i64>(), fps.parse::<f64>())
+            else {
+                return Err(invalid(line, number));
+            };
+            if start < 0 || end < start || end >= MAX_V1_FRAMES || !valid_fps(fps) {
+                return Err(invalid(line, number));
+            }
+            ranges.push((start, end, fps, number));
+        }
+        ranges.sort_by_key(|range| range.0);
+        let mut time = 0.0_f64;
+        let mut frame = 0;
+        times.timecodes.clear();
+        for (start, end, range_fps, number) in ranges {
+            if start < frame {
+                return Err(Error::parse_error::<FrameTimes>(format!(
+                    "frame range {}-{} overlaps another range",
+                    start, end
+                ))
+                .at_line(number));
+            }
+            for (until, fps) in [(start - 1, fps), (end, range_fps)] {
+                while frame <= until {
+                    times.timecodes.push((time + 0.5).floor() as i64);
+                    time += 1000.0 / fps;
+                    frame += 1;
+                }
+            }
+        }
+        times.timecodes.push((time + 0.5).floor() as i64);
+        Ok(times)
+    }
+
+    /// One start time per frame, possibly out of order for v4.
+    fn parse_v2<'a>(lines: impl Iterator<Item = (&'a str, usize)>) -> crate::Result<Self> {
+        let mut timecodes = vec![];
+        for (line, number) in lines {
+            let time = line.parse::<f64>().map_err(|error| {
+                Error::parse_float_error(error, format!("invalid timecode {}", line))
+                    .at_line(number)
+            })?;
+            timecodes.push(time.round() as i64);
+        }
+        timecodes.sort_unstable();
+        let (Some(first), Some(last)) = (timecodes.first(), timecodes.last()) else {
+            return Err(Error::parse_error::<FrameTimes>("no timecodes"));
+        };
+        if timecodes.len() < 2 || first == last {
+            return Err(Error::parse_error::<FrameTimes>(
+                "at least two different timecodes are needed",
+            ));
+        }
+        Ok(Self {
+            numerator: (timecodes.len() as i64 - 1) * 1000,
+            denominator: last - first,
+            timecodes,
+        })
+    }
+
+    /// Whether the frames have a constant rate rather than coming from a timecode file.
+    pub fn is_constant(&self) -> bool {
+        self.timecodes.len() == 1
+    }
+
+    /// The frame rate, averaged over the known frames of a timecode file.
+    pub fn fps(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Number of frames with a known time, frames past them use [`FrameTimes::fps`].
+    pub fn len(&self) -> usize {
+        self.timecodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timecodes.is_empty()
+    }
+
+    /// The time of `frame` in milliseconds.
+    pub fn millis_at(&self, frame: i64, boundary: Boundary) -> i64 {
+        match boundary {
+            Boundary::Exact => self.exact_millis(frame),
+            Boundary::Start => {
+                let prev = self.exact_millis(frame - 1);
+                let cur = self.exact_millis(frame);
+                prev + (cur - prev + 1) / 2
+            }
+            Boundary::End => {
+                let cur = self.exact_millis(frame);
+                let next = self.exact_millis(frame + 1);
+                cur + (next - cur + 1) / 2
+            }
+        }
+    }
+
+    /// The time of `frame` rounded to centiseconds.
+    pub fn time_at(&self, frame: i64, boundary: Boundary) -> Timestamp {
+        Timestamp::from_millis(self.millis_at(frame, boundary))
+    }
+
+    /// The frame shown at `millis`, or the first and last frame of an event that starts or
+    /// ends at `millis`.
+    pub fn frame_at_millis(&self, millis: i64, boundary: Boundary) -> i64 {
+        match boundary {
+            Boundary::Exact => self.exact_frame(millis),
+            Boundary::Start => self.exact_frame(millis - 1) + 1,
+            Boundary::End => self.exact_frame(millis - 1),
+        }
+    }
+
+    pub fn frame_at(&self, time: Timestamp, boundary: Boundary) -> i64 {
+        self.frame_at_millis(time.millis(), boundary)
+    }
+
+    /// Moves `time` to the frame with the same number in `to`, keeping its position within
+    /// the frame.
+    pub fn convert(&self, time: Timestamp, to: &FrameTimes) -> Timestamp {
+        let millis = time.millis();
+        let frame = self.exact_frame(millis);
+        let (start, end) = (self.exact_millis(frame), self.exact_millis(frame + 1));
+        let (to_start, to_end) = (to.exact_millis(frame), to.exact_millis(frame + 1));
+        let offset = if end > start {
+            ((millis - start) as f64 / (end - start) as f64 * (to_end - to_start) as f64).round()
+        } else {
+            0.0
+        };
+        Timestamp::from_millis(to_start + offset as i64)
+    }
+
+    fn exact_millis(&self, frame: i64) -> i64 {
+        let last = self.timecodes.len() as i64 - 1;
+        let (base, frames) = if frame < 0 {
+            (self.timecodes[0], frame)
+        } else if frame > last {
+            (self.timecodes[last as usize], frame - last)
+        } else {
+            return self.timecodes[frame as usize];
+        };
+        let scaled = frames as i128 * 1000 * self.denominator as i128;
+        let numerator = self.numerator as i128;
+        base + (2 * scaled + numerator).div_euclid(2 * numerator) as i64
+    }
+
+    fn exact_frame(&self, millis: i64) -> i64 {
+        let last = self.timecodes.len() - 1;
+        let (first_time, last_time) = (self.timecodes[0], self.timecodes[last]);
+        let estimate = |base: i64, frame: i64| {
+            let scaled = (millis - base) as i128 * self.numerator as i128;
+            frame + scaled.div_euclid(1000 * self.denominator as i128) as i64
+        };
+        let mut frame = if millis < first_time {
+            estimate(first_time, 0)
+        } else if millis >= last_time {
+            estimate(last_time, last as i64)
+        } else {
+            return self.timecodes.partition_point(|&time| time <= millis) as i64 - 1;
+        };
+        while self.exact_millis(frame + 1) <= millis {
+            frame += 1;
+        }
+        while self.exact_millis(frame) > millis {
+            frame -= 1;
+        }
+        frame
+    }
+}
+
+fn valid_fps(fps: f64) -> bool {
+    fps > 0.0 && fps < 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventFormat;
+
+    #[test]
+    fn test_constant_frame_rate() -> crate::Result<()> {
+        let times = FrameTimes::constant(24000, 1001)?;
+        let exact = (0..4)
+            .map(|frame| times.millis_at(frame, Boundary::Exact))
+            .collect::<Vec<_>>();
+        assert_eq!(exact, vec![0, 42, 83, 125]);
+        assert_eq!(times.millis_at(1, Boundary::Start), 21);
+        assert_eq!(times.millis_at(0, Boundary::End), 21);
+        assert_eq!(times.frame_at_millis(21, Boundary::Start), 1);
+        assert_eq!(times.frame_at_millis(21, Boundary::End), 0);
+        assert_eq!(times.frame_at_millis(41, Boundary::Exact), 0);
+        assert_eq!(times.frame_at_millis(42, Boundary::Exact), 1);
+        for frame in -50..5000 {
+            let start = times.time_at(frame, Boundary::Start);
+            assert_eq!(times.frame_at(start, Boundary::Start), frame);
+            let end = times.time_at(frame, Boundary::End);
+            assert_eq!(times.frame_at(end, Boundary::End), frame);
+        }
+        assert!(FrameTimes::constant(0, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_timecode_files() -> crate::Result<()> {
+        let v2 = FrameTimes::from_timecodes("# timecode format v2\n0\n40\n80.4\n120\n")?;
+        assert!(!v2.is_constant());
+        assert_eq!(v2.millis_at(2, Boundary::Exact), 80);
+        assert_eq!(v2.millis_at(5, Boundary::Exact), 200);
+        assert_eq!(v2.frame_at_millis(79, Boundary::Exact), 1);
+        assert_eq!(v2.frame_at_millis(1000, Boundary::Exact), 25);
+        let v4 = FrameTimes::from_timecodes("# timestamp format v4\n40\n0\n120\n80\n")?;
+        assert_eq!(v4.millis_at(3, Boundary::Exact), 120);
+
+        let v1 = FrameTimes::from_timecodes("# timecode format v1\nAssume 25\n# fast\n2,3,50\n")?;
+        let exact = (0..7)
+            .map(|frame| v1.millis_at(frame, Boundary::Exact))
+            .collect::<Vec<_>>();
+        assert_eq!(exact, vec![0, 40, 80, 100, 120, 160, 200]);
+
+        let error = FrameTimes::from_timecodes("# timecode format v2\n0\nforty\n").unwrap_err();
+        assert_eq!(error.line(), Some(3));
+        let error = FrameTimes::from_timecodes("# timecode format v1\nAssume 25\n0,5,30\n3,4,50\n")
+            .unwrap_err();
+        assert_eq!(error.line(), Some(4));
+        for range in ["0,5,nan", "0,5,inf", "0,10000000000,25"] {
+            let src = format!("# timecode format v1\nAssume 25\n{}\n", range);
+            assert_eq!(
+                FrameTimes::from_timecodes(&src).unwrap_err().line(),
+                Some(3)
+            );
+        }
+        assert!(FrameTimes::from_timecodes("0\n40\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snap_and_convert_events() -> crate::Result<()> {
+        let mut file = crate::file::File::from_str(
+            "[Events]\nFormat: Start, End, Text\n\
+             Dialogue: 0:00:01.00,0:00:02.00,a\n\
+             Dialogue: 0:00:02.00,0:00:02.00,b\n",
+        )?;
+        let ntsc = FrameTimes::constant(24000, 1001)?;
+        file.events.snap_to_frames(&ntsc);
+        let times = |file: &crate::file::File| {
+            file.events
+                .iter()
+                .map(|event| {
+                    let start = event.get(EventFormat::Start).unwrap().to_string();
+                    let end = event.get(EventFormat::End).unwrap().to_string();
+                    (start, end)
+                })
+                .collect::<Vec<_>>()
+        };
+        let snapped = times(&file);
+        assert_eq!(snapped[0], ("0:00:00.98".into(), "0:00:01.98".into()));
+        assert_eq!(snapped[1].0, snapped[1].1);
+        file.events.snap_to_frames(&ntsc);
+        assert_eq!(times(&file), snapped);
+
+        let pal = FrameTimes::constant(25, 1)?;
+        file.events.convert_framerate(&ntsc, &pal);
+        file.events.convert_framerate(&pal, &ntsc);
+        assert_eq!(times(&file), snapped);
+        let mut events = crate::file::File::from_str(
+            "[Events]\nFormat: Start, End\nDialogue: 0:00:01.00,0:00:02.02\n",
+        )?
+        .events;
+        events.convert_framerate(&pal, &ntsc);
+        assert_eq!(
+            events[0].get(EventFormat::Start),
+            Some(&Timestamp::from_centis(104).into())
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,43 @@
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+
+use crate::attachment::{self, Attachment};
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Graphics {
-    pub graphics: Vec<String>,
+    pub graphics: Vec<Attachment>,
+}
+
+impl Graphics {
+    pub fn find(&self, name: &str) -> Option<&Attachment> {
+        self.graphics
+            .iter()
+            .find(|attachment| attachment.name == name)
+    }
+
+    /// Embeds the image file at `path`, replacing an attachment of the same name.
+    pub fn embed(&mut self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let attachment = Attachment::from_path(path)?;
+        match self.graphics.iter_mut().find(|a| a.name == attachment.name) {
+            Some(existing) => *existing = attachment,
+            None => self.graphics.push(attachment),
+        }
+        Ok(())
+    }
+
+    /// Writes every attachment to `dir`, creating it if needed.
+    pub fn extract_to(&self, dir: impl AsRef<Path>) -> crate::Result<Vec<PathBuf>> {
+        attachment::write_all(&self.graphics, dir.as_ref())
+    }
 }
 
 impl Deref for Graphics {
-    type Target = Vec<String>;
+    type Target = Vec<Attachment>;
 
     fn deref(&self) -> &Self::Target {
         &self.graphics
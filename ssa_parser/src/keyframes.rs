@@ -0,0 +1,196 @@
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Sorted frame numbers of the scene changes of a video.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keyframes {
+    frames: Vec<i64>,
+    /// Frame rate stated by Aegisub keyframe files, `None` when unknown.
+    fps: Option<f64>,
+}
+
+impl Keyframes {
+    pub fn new(frames: impl IntoIterator<Item = i64>) -> Self {
+        let mut frames = frames.into_iter().collect::<Vec<_>>();
+        frames.sort_unstable();
+        frames.dedup();
+        Self { frames, fps: None }
+    }
+
+    pub fn fps(&self) -> Option<f64> {
+        self.fps
+    }
+
+    /// Parses keyframes detected from the first line: Aegisub keyframe files, XviD, SCXviD and
+    /// ffmpeg first pass stats, DivX stats, x264 stats, or a plain list of frame numbers.
+    pub fn parse(src: &str) -> crate::Result<Self> {
+        let header = src.lines().next().unwrap_or_default().trim();
+        if header.starts_with("# keyframe format v1") {
+            Self::parse_aegisub(src)
+        } else if header.starts_with("# XviD 2pass stat file")
+            || header.starts_with("# ffmpeg 2-pass log file, using xvid codec")
+            || header.starts_with("# avconv 2-pass log file, using xvid codec")
+        {
+            Ok(Self::parse_xvid(src))
+        } else if header.starts_with("##map version") {
+            Ok(Self::parse_divx(src))
+        } else if header.starts_with("#options:") {
+            Self::parse_x264(src)
+        } else {
+            Self::parse_list(src)
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// `# keyframe format v1`, an `fps` line and one frame number per line.
+    fn parse_aegisub(src: &str) -> crate::Result<Self> {
+        let mut fps = None;
+        let mut frames = vec![];
+        for (line, number) in src.lines().map(str::trim).zip(1..) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("fps") {
+                let value = value.trim().parse::<f64>().map_err(|error| {
+                    Error::parse_float_error(error, format!("invalid fps {}", value.trim()))
+                        .at_line(number)
+                })?;
+                fps = Some(value).filter(|fps| *fps > 0.0);
+                continue;
+            }
+            frames.push(parse_frame(line, number)?);
+        }
+        Ok(Self {
+            fps,
+            ..Self::new(frames)
+        })
+    }
+
+    /// One line per frame starting with its type, `i` for keyframes.
+    fn parse_xvid(src: &str) -> Self {
+        let mut frames = vec![];
+        let mut count = 0;
+        for line in src.lines().map(str::trim) {
+            match line.chars().next().map(|c| c.to_ascii_lowercase()) {
+                Some('i') => {
+                    frames.push(count);
+                    count += 1;
+                }
+                Some('p' | 'b') => count += 1,
+                _ => {}
+            }
+        }
+        Self::new(frames)
+    }
+
+    /// Frame lines start with their type after the header, `I` for keyframes.
+    fn parse_divx(src: &str) -> Self {
+        let mut frames = vec![];
+        let mut count = 0;
+        for line in src
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+        {
+            match line.chars().next() {
+                Some('I') => {
+                    frames.push(count);
+                    count += 1;
+                }
+                Some('P' | 'B') => count += 1,
+                _ => {}
+            }
+        }
+        Self::new(frames)
+    }
+
+    /// Lines like `in:12 out:12 type:I ...`, keyframes have the type `I` or `i`.
+    fn parse_x264(src: &str) -> crate::Result<Self> {
+        let mut frames = vec![];
+        for (line, number) in src.lines().zip(1..) {
+            let mut frame = None;
+            let mut keyframe = false;
+            for field in line.split_whitespace() {
+                if let Some(value) = field.strip_prefix("in:") {
+                    frame = Some(parse_frame(value, number)?);
+                } else if let Some(value) = field.strip_prefix("type:") {
+                    keyframe = value.eq_ignore_ascii_case("i");
+                }
+            }
+            if let Some(frame) = frame.filter(|_| keyframe) {
+                frames.push(frame);
+            }
+        }
+        Ok(Self::new(frames))
+    }
+
+    /// One frame number per line, `#` starts a comment.
+    fn parse_list(src: &str) -> crate::Result<Self> {
+        let mut frames = vec![];
+        for (line, number) in src.lines().zip(1..) {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if !line.is_empty() {
+                frames.push(parse_frame(line, number)?);
+            }
+        }
+        Ok(Self::new(frames))
+    }
+}
+
+fn parse_frame(src: &str, line: usize) -> crate::Result<i64> {
+    src.parse::<i64>()
+        .map_err(|error| {
+            Error::parse_int_error(error, format!("invalid frame number {}", src)).at_line(line)
+        })
+        .and_then(|frame| {
+            if frame < 0 {
+                let msg = format!("negative frame number {}", frame);
+                return Err(Error::parse_error::<Keyframes>(msg).at_line(line));
+            }
+            Ok(frame)
+        })
+}
+
+impl Deref for Keyframes {
+    type Target = [i64];
+
+    fn deref(&self) -> &Self::Target {
+        &self.frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframe_formats() -> crate::Result<()> {
+        let aegisub = Keyframes::parse("# keyframe format v1\nfps 23.976\n0\n240\n120\n")?;
+        assert_eq!(&aegisub[..], &[0, 120, 240]);
+        assert_eq!(aegisub.fps(), Some(23.976));
+        let xvid = Keyframes::parse(
+            "# XviD 2pass stat file (core version 1.3.2)\n# Please do not modify this file\n\n\
+             i 1 0 0 0 0 0 0\np 1 0 0 0 0 0 0\nb 1 0 0 0 0 0 0\ni 1 0 0 0 0 0 0\n",
+        )?;
+        assert_eq!(&xvid[..], &[0, 3]);
+        let divx = Keyframes::parse("##map version 1.0\nI 0\nP 1\nP 2\nI 3\n")?;
+        assert_eq!(&divx[..], &[0, 3]);
+        let x264 = Keyframes::parse(
+            "#options: 1920x1080 fps=24000/1001\n\
+             in:0 out:0 type:I dur:2 cpbdur:2 q:21.00\n\
+             in:2 out:1 type:P dur:2 cpbdur:2 q:24.00\n\
+             in:1 out:2 type:b dur:2 cpbdur:2 q:26.00\n\
+             in:3 out:3 type:i dur:2 cpbdur:2 q:21.00\n",
+        )?;
+        assert_eq!(&x264[..], &[0, 3]);
+        let list = Keyframes::parse("12\n# scene\n5 # cut\n")?;
+        assert_eq!(&list[..], &[5, 12]);
+        assert_eq!(Keyframes::parse("1\nx\n").unwrap_err().line(), Some(2));
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt::Write;
+
+use crate::{file::LineEnding, value::Value, version::Version};
+
+/// How a parsed file was laid out, kept so that it can be written back byte for byte.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Layout {
+    pub(crate) bom: bool,
+    pub(crate) line_ending: LineEnding,
+    pub(crate) sections: Vec<Section>,
+    /// Script info properties filled in by the parser that were not part of the source.
+    pub(crate) implicit: Vec<(String, Value)>,
+    /// Hash of the parsed fonts and graphics, their sections are rendered again once it changes.
+    pub(crate) attachments_hash: u64,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Section {
+    pub(crate) kind: SectionKind,
+    pub(crate) header: Option<RawText>,
+    pub(crate) lines: Vec<LineLayout>,
+}
+
+impl Section {
+    pub(crate) fn new(kind: SectionKind, header: Option<RawText>) -> Self {
+        Self {
+            kind,
+            header,
+            lines: vec![],
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        let header = self.header.as_ref()?.text.trim();
+        header.strip_prefix('[')?.strip_suffix(']')
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum SectionKind {
+    Preamble,
+    ScriptInfo,
+    Styles(Version),
+    Events,
+    Fonts,
+    Graphics,
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum LineLayout {
+    Raw(RawText),
+    Comment(RawText),
+    Property {
+        key: String,
+        value: Option<Value>,
+        raw: RawText,
+    },
+    Format {
+        order: String,
+        raw: RawText,
+    },
+    /// Where the styles or events of the section are written.
+    Items,
+}
+
+/// A source line without its terminator.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct RawText {
+    pub(crate) text: String,
+    pub(crate) eol: &'static str,
+}
+
+impl RawText {
+    pub(crate) fn new(text: impl Into<String>, eol: &'static str) -> Self {
+        Self {
+            text: text.into(),
+            eol,
+        }
+    }
+}
+
+/// The source of a `Style:` or event line. Every field keeps its original spelling, which is
+/// written back as long as it still parses to the current value.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RawLine<F> {
+    pub(crate) leading: Vec<RawText>,
+    pub(crate) key: String,
+    pub(crate) prefix: String,
+    pub(crate) fields: Vec<RawField<F>>,
+    pub(crate) suffix: String,
+    pub(crate) eol: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RawField<F> {
+    pub(crate) format: F,
+    pub(crate) src: String,
+}
+
+impl<F: PartialEq + Copy> RawLine<F> {
+    /// Splits `src` into the text before the first field, the fields themselves and anything
+    /// after the last field. At most `n` fields are split off, the last one keeps its commas
+    /// when `keep_tail` is set.
+    pub(crate) fn split<'a>(
+        key: &str,
+        src: &'a str,
+        n: usize,
+        keep_tail: bool,
+    ) -> (Self, Vec<&'a str>) {
+        let after_key = src.find(':').map(|pos| pos + 1).unwrap_or(0);
+        let body_start = src.len() - src[after_key..].trim_start().len();
+        let body_end = src.trim_end().len().max(body_start);
+        let body = &src[body_start..body_end];
+        let mut fields: Vec<&str> = if keep_tail {
+            body.splitn(n, ',').collect()
+        } else {
+            body.split(',').collect()
+        };
+        let mut suffix = String::new();
+        if fields.len() > n {
+            let consumed: usize = fields[..n].iter().map(|f| f.len() + 1).sum();
+            suffix.push_str(&body[consumed - 1..]);
+            fields.truncate(n);
+        }
+        suffix.push_str(&src[body_end..]);
+        let raw = Self {
+            leading: vec![],
+            key: key.to_string(),
+            prefix: src[..body_start].to_string(),
+            fields: vec![],
+            suffix,
+            eol: "",
+        };
+        (raw, fields)
+    }
+
+    pub(crate) fn push(&mut self, format: F, src: &str) {
+        self.fields.push(RawField {
+            format,
+            src: src.to_string(),
+        });
+    }
+
+    /// Writes the line, rendering fields whose value changed since parsing with `render`.
+    pub(crate) fn write<W: Write>(
+        &self,
+        out: &mut W,
+        key: &str,
+        values: &[(F, Option<Value>)],
+        parse: impl Fn(&F, &str) -> Option<Value>,
+        render: impl Fn(&F, Option<&Value>) -> String,
+    ) -> std::fmt::Result {
+        if self.key.eq_ignore_ascii_case(key) {
+            write!(out, "{}", self.prefix)?;
+        } else {
+            let indent = &self.prefix[..self.prefix.len() - self.prefix.trim_start().len()];
+            write!(out, "{}{}: ", indent, key)?;
+        }
+        for (i, (format, value)) in values.iter().enumerate() {
+            let field = self.fields.get(i).filter(|field| field.format == *format);
+            if field.is_none() && value.is_none() && i >= self.fields.len() {
+                continue;
+            }
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            match field {
+                Some(field) if parse(format, &field.src).as_ref() == value.as_ref() => {
+                    write!(out, "{}", field.src)?
+                }
+                _ => write!(out, "{}", render(format, value.as_ref()))?,
+            }
+        }
+        write!(out, "{}", self.suffix)
+    }
+}
+
+/// Splits text into lines, keeping track of each line's terminator.
+pub(crate) fn split_lines(src: &str) -> impl Iterator<Item = (&str, &'static str)> {
+    src.split_inclusive('\n').map(|line| {
+        if let Some(line) = line.strip_suffix("\r\n") {
+            (line, "\r\n")
+        } else if let Some(line) = line.strip_suffix('\n') {
+            (line, "\n")
+        } else {
+            (line, "")
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_lines() {
+        let lines: Vec<_> = split_lines("a\r\nb\n\nc").collect();
+        assert_eq!(
+            lines,
+            vec![("a", "\r\n"), ("b", "\n"), ("", "\n"), ("c", "")]
+        );
+    }
+
+    #[test]
+    fn test_split_raw_line() {
+        let (raw, fields) = RawLine::<u8>::split("Style", "  Style:  a,b,c,d  ", 3, false);
+        assert_eq!(raw.prefix, "  Style:  ");
+        assert_eq!(fields, vec!["a", "b", "c"]);
+        assert_eq!(raw.suffix, ",d  ");
+        let (raw, fields) = RawLine::<u8>::split("Dialogue", "Dialogue: 0,a,b, c", 3, true);
+        assert_eq!(raw.prefix, "Dialogue: ");
+        assert_eq!(fields, vec!["0", "a", "b, c"]);
+        assert_eq!(raw.suffix, "");
+    }
+}
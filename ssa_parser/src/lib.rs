@@ -1,86 +1,57 @@
 use crate::error::Error;
 use crate::parser::Parser;
+use crate::timestamp::Timestamp;
 use std::time::Duration;
 
+pub mod alignment;
+pub mod attachment;
+pub mod borrowed;
+pub mod color;
+pub mod convert;
+pub mod diagnostic;
+pub mod encoding;
 pub mod error;
 pub mod events;
 pub mod file;
 pub mod fonts;
+pub mod frames;
 pub mod graphics;
+pub mod keyframes;
+mod layout;
+pub mod lint;
 pub mod parser;
+pub mod resample;
+pub mod resync;
+#[cfg(feature = "serde")]
+pub mod schema;
 pub mod script_info;
+pub mod srt;
+pub mod stream;
 pub mod styles;
+pub mod timestamp;
+pub mod timing;
 pub mod value;
 pub mod version;
+pub mod webvtt;
+
+pub use encoding_rs;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub fn format_duration(duration: &Duration) -> String {
-    let total_millis = duration.as_millis();
-    let hours = total_millis / 3600_000;
-    let minutes = (total_millis % 3600_000) / 60_000;
-    let seconds = (total_millis % 60_000) / 1_000;
-    let millis_seconds = total_millis % 1_000;
-    format!(
-        "{}:{:02}:{:02}.{:02}",
-        hours, minutes, seconds, millis_seconds
-    )
+    Timestamp::from(*duration).to_string()
 }
 
 impl Parser for Duration {
     fn parse(src: &str) -> crate::Result<Self> {
-        let split1: Vec<_> = src.split(".").collect();
-        if split1.len() != 2 {
-            return Err(Error::parse_error::<Duration>(format!(
-                "invalid duration format {}",
-                src
-            )));
-        }
-        let split2: Vec<_> = split1[0].split(":").collect();
-        if split2.len() != 3 {
-            return Err(Error::parse_error::<Duration>(format!(
-                "invalid duration format {}",
-                src
-            )));
-        }
-        let h = split2[0]
-            .parse::<u64>()
-            .map_err(|e| Error::parse_int_error(e, split2[0]))?;
-        if h >= 24 {
+        let timestamp = Timestamp::parse(src)?;
+        if timestamp.is_negative() {
             return Err(Error::parse_error::<Duration>(format!(
-                "hour {} out of range",
+                "negative duration {}",
                 src
             )));
         }
-        let m = split2[1]
-            .parse::<u64>()
-            .map_err(|e| Error::parse_int_error(e, split2[1]))?;
-        if m >= 60 {
-            return Err(Error::parse_error::<Duration>(format!(
-                "minute {} out of range",
-                src
-            )));
-        }
-        let s = split2[2]
-            .parse::<u64>()
-            .map_err(|e: std::num::ParseIntError| Error::parse_int_error(e, split2[2]))?;
-        if s >= 60 {
-            return Err(Error::parse_error::<Duration>(format!(
-                "second {} out of range",
-                src
-            )));
-        }
-        let ms = split1[1]
-            .parse::<u64>()
-            .map_err(|e| Error::parse_int_error(e, split1[1]))?;
-        if ms >= 1000 {
-            return Err(Error::parse_error::<Duration>(format!(
-                "millisecond {} out of range",
-                src
-            )));
-        }
-        let duration = Duration::from_millis(h * 3600_000 + m * 60_000 + s * 1_000 + ms);
-        Ok(duration)
+        Ok(timestamp.to_duration())
     }
 }
 
@@ -94,6 +65,10 @@ mod test {
     fn test_format_duration() {
         let z: Duration = Duration::ZERO;
         assert_eq!(format_duration(&z), "0:00:00.00");
+        let d = Duration::from_millis(1500);
+        assert_eq!(format_duration(&d), "0:00:01.50");
+        let d = Duration::from_millis(61_234);
+        assert_eq!(format_duration(&d), "0:01:01.23");
     }
 
     #[test]
@@ -111,8 +86,15 @@ mod test {
         let d = Duration::parse("08:60:9.88");
         assert!(d.is_err());
         let d = Duration::parse("24:59:9.88");
-        assert!(d.is_err());
+        assert_eq!(d.unwrap(), Duration::from_millis(89_949_880));
+        // longer fractions are rounded to centiseconds
         let d = Duration::parse("24:59:9.1188");
+        assert_eq!(d.unwrap(), Duration::from_millis(89_949_120));
+        let d = Duration::parse("9223372036854775:00:00.00");
+        assert!(d.is_err());
+        let d = Duration::parse("0:00:01.5");
+        assert_eq!(d.unwrap(), Duration::from_millis(1_500));
+        let d = Duration::parse("-0:00:01.00");
         assert!(d.is_err());
     }
 }
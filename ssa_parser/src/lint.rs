@@ -0,0 +1,470 @@
+//! Quality checks over a parsed [`File`], see [`File::lint`].
+//!
+//! Every problem is reported as a [`Lint`] naming its [`Rule`] by a stable id such as
+//! `missing-style`. Rules can be turned off, given another severity or told to skip some
+//! styles through a [`LintConfig`], which deserialises from TOML or JSON with the `serde`
+//! feature:
+//!
+//! ```toml
+//! max-cps = 17
+//! max-line-length = 40
+//!
+//! [rules.unused-style]
+//! enabled = false
+//!
+//! [rules.line-length]
+//! severity = "error"
+//! ignore-styles = ["Signs", "OP"]
+//! ```
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+
+use crate::{
+    diagnostic::Severity,
+    events::{
+        index::TimeIndex,
+        tag::{Tag, TagKind},
+        Event, EventFormat, EventType,
+    },
+    file::File,
+    timestamp::Timestamp,
+    value::Value,
+};
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+    strum::VariantNames,
+)]
+#[strum(serialize_all = "kebab-case")]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Rule {
+    /// A dialogue line using a style that is not defined.
+    MissingStyle,
+    /// A dialogue line ending before it starts.
+    EndBeforeStart,
+    /// A dialogue line starting and ending at the same time.
+    ZeroDuration,
+    /// Dialogue lines of the same style and layer shown at the same time.
+    Overlap,
+    /// A dialogue line with more characters per second than [`LintConfig::max_cps`].
+    Cps,
+    /// A line of text longer than [`LintConfig::max_line_length`].
+    LineLength,
+    /// A `{` without `}` or the other way round.
+    UnbalancedBraces,
+    /// An override tag renderers do not know.
+    UnknownTag,
+    /// A style whose name is already used by an earlier style.
+    DuplicateStyle,
+    /// A style no dialogue line uses.
+    UnusedStyle,
+}
+
+impl Rule {
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            Rule::MissingStyle | Rule::EndBeforeStart => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case", deny_unknown_fields)
+)]
+pub struct RuleConfig {
+    pub enabled: bool,
+    /// Replaces [`Rule::default_severity`].
+    pub severity: Option<Severity>,
+    /// Styles that are not checked, for events the style of the event.
+    pub ignore_styles: Vec<String>,
+}
+
+static DEFAULT_RULE: RuleConfig = RuleConfig {
+    enabled: true,
+    severity: None,
+    ignore_styles: Vec::new(),
+};
+
+impl Default for RuleConfig {
+    fn default() -> Self {
+        DEFAULT_RULE.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, rename_all = "kebab-case", deny_unknown_fields)
+)]
+pub struct LintConfig {
+    /// Letters and digits per second above which [`Rule::Cps`] reports a line, 20 by default.
+    pub max_cps: f64,
+    /// Characters per line above which [`Rule::LineLength`] reports a line, 42 by default.
+    pub max_line_length: usize,
+    /// Rules not listed are enabled with their default severity.
+    pub rules: BTreeMap<Rule, RuleConfig>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            max_cps: 20.0,
+            max_line_length: 42,
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn rule(&self, rule: Rule) -> &RuleConfig {
+        self.rules.get(&rule).unwrap_or(&DEFAULT_RULE)
+    }
+}
+
+/// A rule violation.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// Position of the event in [`crate::events::Events`], `None` for problems of styles.
+    pub event: Option<usize>,
+    /// The style of the event or the style with the problem.
+    pub style: String,
+    pub message: String,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.event {
+            Some(position) => write!(f, "event {}", position)?,
+            None => write!(f, "style {}", self.style)?,
+        }
+        write!(f, ": {}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+struct Linter<'a> {
+    file: &'a File,
+    config: &'a LintConfig,
+    lints: Vec<Lint>,
+}
+
+impl Linter<'_> {
+    fn enabled(&self, rule: Rule) -> bool {
+        self.config.rule(rule).enabled
+    }
+
+    fn report(&mut self, rule: Rule, event: Option<usize>, style: &str, message: String) {
+        let config = self.config.rule(rule);
+        if !config.enabled || config.ignore_styles.iter().any(|s| s == style) {
+            return;
+        }
+        self.lints.push(Lint {
+            rule,
+            severity: config.severity.unwrap_or(rule.default_severity()),
+            event,
+            style: style.to_string(),
+            message,
+        });
+    }
+
+    fn styles(&mut self) {
+        let mut used = HashSet::new();
+        let file = self.file;
+        for event in dialogue(file).map(|(_, event)| event) {
+            used.insert(style(event));
+            let tags = event
+                .get(EventFormat::Text)
+                .and_then(Value::as_text)
+                .into_iter()
+                .flat_map(|text| text.tags());
+            for tag in tags {
+                if let TagKind::Reset(Some(name)) = tag.kind() {
+                    used.insert(name.as_str());
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        for (name, _) in file.styles.iter() {
+            if !seen.insert(name) {
+                let message = format!("style {} is defined more than once", name);
+                self.report(Rule::DuplicateStyle, None, name, message);
+            } else if !used.contains(name) {
+                let message = format!("style {} is not used by any dialogue line", name);
+                self.report(Rule::UnusedStyle, None, name, message);
+            }
+        }
+    }
+
+    fn events(&mut self) {
+        let file = self.file;
+        let index = self
+            .enabled(Rule::Overlap)
+            .then(|| TimeIndex::dialogue(&file.events));
+        for (position, event) in dialogue(file) {
+            let name = style(event);
+            if file.styles.get(name).is_none() {
+                let message = format!("style {} is not defined", name);
+                self.report(Rule::MissingStyle, Some(position), name, message);
+            }
+            let (start, end) = (
+                time(event, EventFormat::Start),
+                time(event, EventFormat::End),
+            );
+            if end < start {
+                let message = format!("ends at {} before it starts at {}", end, start);
+                self.report(Rule::EndBeforeStart, Some(position), name, message);
+            } else if end == start {
+                let message = format!("starts and ends at {}", start);
+                self.report(Rule::ZeroDuration, Some(position), name, message);
+            }
+            if let Some(index) = &index {
+                for other in index.overlapping(start..end) {
+                    let earlier = &file.events[other];
+                    if other < position && style(earlier) == name && layer(earlier) == layer(event)
+                    {
+                        let message = format!("overlaps event {}", other);
+                        self.report(Rule::Overlap, Some(position), name, message);
+                    }
+                }
+            }
+            self.text(position, event, end - start);
+        }
+    }
+
+    fn text(&mut self, position: usize, event: &Event, duration: Timestamp) {
+        let style = style(event);
+        let Some(text) = event.get(EventFormat::Text).and_then(Value::as_text) else {
+            return;
+        };
+        if !balanced(&text.to_string()) {
+            let message = "unbalanced override block braces".to_string();
+            self.report(Rule::UnbalancedBraces, Some(position), style, message);
+        }
+        let mut drawing = false;
+        for tag in text.tags().flat_map(with_nested) {
+            match tag.kind() {
+                TagKind::Unknown(_) => {
+                    let message = format!("unknown override tag {}", tag);
+                    self.report(Rule::UnknownTag, Some(position), style, message);
+                }
+                TagKind::Drawing(scale) => drawing |= *scale > 0,
+                _ => {}
+            }
+        }
+        if drawing {
+            return;
+        }
+        let plain = text.plain_text().replace("\\h", " ");
+        let lines = plain.split("\\N").flat_map(|line| line.split("\\n"));
+        let longest = lines.map(|line| line.chars().count()).max().unwrap_or(0);
+        if longest > self.config.max_line_length {
+            let message = format!(
+                "{} characters on one line, more than {}",
+                longest, self.config.max_line_length
+            );
+            self.report(Rule::LineLength, Some(position), style, message);
+        }
+        let characters = plain
+            .replace("\\N", "")
+            .replace("\\n", "")
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .count();
+        if duration > Timestamp::ZERO {
+            let cps = characters as f64 / duration.as_secs_f64();
+            if cps > self.config.max_cps {
+                let message = format!(
+                    "{:.1} characters per second, more than {}",
+                    cps, self.config.max_cps
+                );
+                self.report(Rule::Cps, Some(position), style, message);
+            }
+        }
+    }
+}
+
+fn dialogue(file: &File) -> impl Iterator<Item = (usize, &Event)> {
+    file.events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| event.event_type() == EventType::Dialogue)
+}
+
+fn style(event: &Event) -> &str {
+    event
+        .get(EventFormat::Style)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+fn layer(event: &Event) -> i64 {
+    event
+        .get(EventFormat::Layer)
+        .and_then(Value::as_int)
+        .unwrap_or_default()
+}
+
+fn time(event: &Event, format: EventFormat) -> Timestamp {
+    event
+        .get(format)
+        .and_then(Value::as_timestamp)
+        .unwrap_or_default()
+}
+
+/// The tag followed by the tags inside it if it is a `\t`.
+fn with_nested(tag: &Tag) -> Vec<&Tag> {
+    let mut tags = vec![tag];
+    if let TagKind::Transform { tags: nested, .. } = tag.kind() {
+        tags.extend(nested.iter().flat_map(with_nested));
+    }
+    tags
+}
+
+/// Whether every `{` is closed by a `}` before the next `{`.
+fn balanced(text: &str) -> bool {
+    let mut open = false;
+    for c in text.chars() {
+        match c {
+            '{' if open => return false,
+            '}' if !open => return false,
+            '{' | '}' => open = !open,
+            _ => {}
+        }
+    }
+    !open
+}
+
+pub(crate) fn lint(file: &File, config: &LintConfig) -> Vec<Lint> {
+    let mut linter = Linter {
+        file,
+        config,
+        lints: vec![],
+    };
+    linter.styles();
+    linter.events();
+    linter.lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(events: &str) -> File {
+        let src = format!(
+            "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\n\
+             Style: Default,Arial,40\nStyle: Signs,Arial,40\nStyle: Default,Verdana,40\n\
+             Style: Unused,Arial,40\n\n[Events]\nFormat: Layer, Start, End, Style, Text\n{}",
+            events
+        );
+        File::from_str(src).unwrap()
+    }
+
+    #[test]
+    fn test_lint() {
+        let file = script(
+            "Dialogue: 0,0:00:01.00,0:00:03.00,Default,Hello\n\
+             Dialogue: 0,0:00:02.00,0:00:04.00,Default,{\\foo}Overlap\n\
+             Dialogue: 1,0:00:02.00,0:00:04.00,Default,{\\t(\\bar1)}Other layer\n\
+             Dialogue: 0,0:00:05.00,0:00:04.00,Missing,Backwards\n\
+             Dialogue: 0,0:00:05.00,0:00:05.00,Signs,{\\b1 unbalanced\n\
+             Dialogue: 0,0:00:06.00,0:00:07.00,Signs,A line of text far longer than the limit\\Nshort\n\
+             Dialogue: 0,0:00:08.00,0:00:08.50,Signs,{\\p1}m 0 0 l 100 0 100 100 0 100 200 200\n\
+             Comment: 0,0:00:02.00,0:00:04.00,Unused,Comments are not checked {\n",
+        );
+        let lints = |config: &LintConfig| {
+            file.lint(config)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        let mut config = LintConfig::default();
+        assert_eq!(
+            lints(&config),
+            [
+                "style Default: warning[duplicate-style]: style Default is defined more than once",
+                "style Unused: warning[unused-style]: style Unused is not used by any dialogue line",
+                "event 1: warning[overlap]: overlaps event 0",
+                "event 1: warning[unknown-tag]: unknown override tag \\foo",
+                "event 2: warning[unknown-tag]: unknown override tag \\bar1",
+                "event 3: error[missing-style]: style Missing is not defined",
+                "event 3: error[end-before-start]: ends at 0:00:04.00 before it starts at 0:00:05.00",
+                "event 4: warning[zero-duration]: starts and ends at 0:00:05.00",
+                "event 4: warning[unbalanced-braces]: unbalanced override block braces",
+                "event 5: warning[cps]: 37.0 characters per second, more than 20",
+            ]
+        );
+
+        config.max_cps = 40.0;
+        config.max_line_length = 20;
+        config.rules.insert(
+            Rule::UnknownTag,
+            RuleConfig {
+                enabled: false,
+                ..Default::default()
+            },
+        );
+        config.rules.insert(
+            Rule::ZeroDuration,
+            RuleConfig {
+                severity: Some(Severity::Error),
+                ignore_styles: vec!["Default".to_string()],
+                ..Default::default()
+            },
+        );
+        config.rules.insert(
+            Rule::DuplicateStyle,
+            RuleConfig {
+                ignore_styles: vec!["Default".to_string()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            lints(&config),
+            [
+                "style Unused: warning[unused-style]: style Unused is not used by any dialogue line",
+                "event 1: warning[overlap]: overlaps event 0",
+                "event 3: error[missing-style]: style Missing is not defined",
+                "event 3: error[end-before-start]: ends at 0:00:04.00 before it starts at 0:00:05.00",
+                "event 4: error[zero-duration]: starts and ends at 0:00:05.00",
+                "event 4: warning[unbalanced-braces]: unbalanced override block braces",
+                "event 5: warning[line-length]: 40 characters on one line, more than 20",
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lint_config_json() -> Result<(), serde_json::Error> {
+        let config: LintConfig = serde_json::from_str(
+            r#"{ "max-cps": 15, "rules": { "overlap": { "enabled": false, "ignore-styles": ["Signs"] } } }"#,
+        )?;
+        assert_eq!(config.max_cps, 15.0);
+        assert_eq!(config.max_line_length, 42);
+        assert!(!config.rule(Rule::Overlap).enabled);
+        assert_eq!(config.rule(Rule::Overlap).ignore_styles, ["Signs"]);
+        assert!(config.rule(Rule::Cps).enabled);
+        assert!(serde_json::from_str::<LintConfig>(r#"{ "rules": { "nope": {} } }"#).is_err());
+        Ok(())
+    }
+}
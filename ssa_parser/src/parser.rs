@@ -1,12 +1,16 @@
+use std::ops::Range;
 use std::str::FromStr;
 
 use crate::{
+    attachment::{uudecode, Attachment},
+    diagnostic::{Diagnostic, DiagnosticCode, Severity},
     error::Error,
     events::{Event, EventType, Events},
     fonts::Fonts,
     graphics::Graphics,
+    layout::{Layout, LineLayout, RawLine, RawText, Section},
     script_info::{Collisions, Key, ScriptInfo, ScriptType},
-    styles::{Style, V4Styles},
+    styles::{Style, StyleFormat, V4Styles},
     value::Value,
 };
 
@@ -22,125 +26,359 @@ pub(crate) struct SsaParser {
     pub(crate) events: Events,
     pub(crate) fonts: Fonts,
     pub(crate) graphics: Graphics,
+    pub(crate) layout: Layout,
+    /// Blank and comment lines waiting to be attached to the next style or event.
+    pub(crate) pending: Vec<RawText>,
+    /// Terminator of the line being parsed.
+    pub(crate) eol: &'static str,
+    /// 1-based number of the line being parsed.
+    pub(crate) line: usize,
+    /// Byte offset of the line being parsed.
+    pub(crate) offset: usize,
+    next_offset: usize,
+    /// Encoded data of the attachment being parsed.
+    encoded: String,
+    /// Line and span of the name of the attachment being parsed.
+    attachment: (usize, Range<usize>),
+    /// Problems found so far, `None` when parsing strictly.
+    pub(crate) diagnostics: Option<Vec<Diagnostic>>,
 }
 
 impl SsaParser {
+    pub(crate) fn lenient() -> Self {
+        Self {
+            diagnostics: Some(vec![]),
+            ..Default::default()
+        }
+    }
+
+    /// Moves on to the next source line.
+    pub(crate) fn advance(&mut self, src: &str, eol: &'static str) {
+        self.line += 1;
+        self.offset = self.next_offset;
+        self.next_offset += src.len() + eol.len();
+        self.eol = eol;
+    }
+
+    /// Records `error` when parsing leniently, the caller then keeps the line as raw text.
+    /// Parsing strictly returns the error annotated with the current line instead.
+    pub(crate) fn recover(
+        &mut self,
+        src: &str,
+        error: Error,
+        code: DiagnosticCode,
+        span: Range<usize>,
+    ) -> crate::Result<()> {
+        if self.diagnostics.is_none() {
+            return Err(error.at_line(self.line));
+        }
+        self.diagnose(src, Severity::Error, code, span, error.to_string());
+        Ok(())
+    }
+
+    /// Records a diagnostic for `span` of the current line `src` when parsing leniently.
+    pub(crate) fn diagnose(
+        &mut self,
+        src: &str,
+        severity: Severity,
+        code: DiagnosticCode,
+        span: Range<usize>,
+        message: impl Into<String>,
+    ) {
+        let Some(diagnostics) = &mut self.diagnostics else {
+            return;
+        };
+        let column = src.get(..span.start).map_or(0, |s| s.chars().count()) + 1;
+        diagnostics.push(Diagnostic {
+            severity,
+            line: self.line,
+            column,
+            span: self.offset + span.start..self.offset + span.end,
+            code,
+            message: message.into(),
+        });
+    }
+
     pub(crate) fn parse_script_info(&mut self, src: &str) -> crate::Result<()> {
-        if src.starts_with(';') {
-            if let Some(comment) = src.strip_prefix(";") {
-                self.script_info.add_comment(comment.trim());
-            } else if let Some(comment) = src.strip_prefix("!") {
-                self.script_info.add_comment(comment.trim());
-            }
+        let raw = RawText::new(src, self.eol);
+        let line = src.trim();
+        if let Some(comment) = line.strip_prefix(';').or_else(|| line.strip_prefix("!:")) {
+            self.script_info.add_comment(comment.trim());
+            self.push_line(LineLayout::Comment(raw));
             return Ok(());
         }
-        if let Some(pos) = src.find(':') {
-            let key = &src[..pos];
-            let value = &src[pos + 1..];
-            match Key::from_str(key) {
-                Ok(key) => match key {
-                    Key::Comment => {}
-                    Key::Title
-                    | Key::OriginalScript
-                    | Key::OriginalTranslation
-                    | Key::OriginalEditing
-                    | Key::OriginalTiming
-                    | Key::SynchPoint
-                    | Key::ScriptUpdatedBy
-                    | Key::UpdateDetails => {
-                        self.script_info
-                            .add_property(key.to_string(), Value::Str(value.trim().to_string()));
-                    }
-                    Key::ScriptType => {
-                        self.script_info
-                            .set_script_type(ScriptType::parse(value.trim())?);
-                    }
-                    Key::Collisions => {
-                        self.script_info
-                            .set_collisions(Collisions::parse(value.trim())?);
-                    }
-                    Key::PlayResY | Key::PlayResX | Key::PlayDepth | Key::WrapStyle => {
-                        let value = value
-                            .trim()
-                            .parse::<i64>()
-                            .map_err(|error| Error::parse_int_error(error, value.trim()))?;
-                        self.script_info.add_property(key.to_string(), value);
-                    }
-                    Key::Timer => {
-                        let value = value
-                            .trim()
-                            .parse::<f64>()
-                            .map_err(|error| Error::parse_float_error(error, value.trim()))?;
-                        self.script_info.set_timer(value);
-                    }
-                    Key::ScaledBorderAndShadow => {
-                        let value = if value.trim().to_ascii_lowercase() == "yes" {
-                            true
-                        } else {
-                            false
-                        };
-                        self.script_info.set_scaled_border_and_shadow(value);
-                    }
-                },
-                Err(_) => {
-                    self.script_info
-                        .add_property(key, Value::Str(value.trim().to_string()));
+        let Some(pos) = line.find(':') else {
+            self.push_line(LineLayout::Raw(raw));
+            return Ok(());
+        };
+        let key = &line[..pos];
+        let value = &line[pos + 1..];
+        let key = match Key::from_str(key) {
+            Ok(key) => {
+                if let Err(error) = self.set_property(key, value.trim()) {
+                    let start = span_of(src, value.trim()).start;
+                    self.recover(
+                        src,
+                        error,
+                        DiagnosticCode::InvalidProperty,
+                        start..src.trim_end().len(),
+                    )?;
+                    self.push_line(LineLayout::Raw(raw));
+                    return Ok(());
                 }
+                key.to_string()
+            }
+            Err(_) => {
+                self.script_info
+                    .add_property(key, Value::Str(value.trim().to_string()));
+                key.to_string()
+            }
+        };
+        let value = self.script_info.get_property(&key).cloned();
+        self.push_line(LineLayout::Property { key, value, raw });
+        Ok(())
+    }
+
+    pub(crate) fn set_property(&mut self, key: Key, value: &str) -> crate::Result<()> {
+        match key {
+            Key::Comment => {}
+            Key::Title
+            | Key::OriginalScript
+            | Key::OriginalTranslation
+            | Key::OriginalEditing
+            | Key::OriginalTiming
+            | Key::SynchPoint
+            | Key::ScriptUpdatedBy
+            | Key::UpdateDetails
+            | Key::YCbCrMatrix => {
+                self.script_info
+                    .add_property(key.to_string(), Value::Str(value.to_string()));
+            }
+            Key::ScriptType => {
+                self.script_info.set_script_type(ScriptType::parse(value)?);
+            }
+            Key::Collisions => {
+                self.script_info.set_collisions(Collisions::parse(value)?);
+            }
+            Key::PlayResY
+            | Key::PlayResX
+            | Key::LayoutResX
+            | Key::LayoutResY
+            | Key::PlayDepth
+            | Key::WrapStyle => {
+                let value = value.parse::<i64>().map_err(|error| {
+                    Error::parse_int_error(error, format!("invalid {} {}", key, value))
+                })?;
+                self.script_info.add_property(key.to_string(), value);
+            }
+            Key::Timer => {
+                let value = value.parse::<f64>().map_err(|error| {
+                    Error::parse_float_error(error, format!("invalid {} {}", key, value))
+                })?;
+                self.script_info.set_timer(value);
+            }
+            Key::ScaledBorderAndShadow => {
+                let value = value.eq_ignore_ascii_case("yes");
+                self.script_info.set_scaled_border_and_shadow(value);
             }
         }
         Ok(())
     }
 
     pub(crate) fn parse_styles(&mut self, src: &str) -> crate::Result<()> {
-        if let Some(style_str) = src.strip_prefix("Style:").map(str::trim) {
-            let mut style = Style::new(&self.styles);
-            style_str
-                .split(',')
-                .zip(self.styles.order())
-                .try_for_each(|(value, format)| {
-                    let value = format.parse_value(value)?;
-                    style.set(*format, value);
-                    Ok::<_, Error>(())
-                })?;
-            self.styles.add(style)?;
+        if !src.trim_start().starts_with("Style:") {
+            self.pending.push(RawText::new(src, self.eol));
+            return Ok(());
+        }
+        let order = self.styles.order().to_vec();
+        let (mut raw, fields) = RawLine::split("Style", src, order.len(), false);
+        self.check_field_count(src, fields.len(), order.len());
+        let mut style = Style::new(&self.styles);
+        for (value, format) in fields.into_iter().zip(&order) {
+            raw.push(*format, value);
+            match format.parse_value(value) {
+                Ok(value) => style.set(*format, value),
+                Err(error) => {
+                    self.recover(
+                        src,
+                        error,
+                        DiagnosticCode::InvalidStyle,
+                        span_of(src, value),
+                    )?;
+                    self.pending.push(RawText::new(src, self.eol));
+                    return Ok(());
+                }
+            }
         }
+        let Some(name) = style.get(StyleFormat::Name).and_then(Value::as_str) else {
+            let span = 0..src.trim_end().len();
+            self.recover(
+                src,
+                Error::V4StyleNameNotFound,
+                DiagnosticCode::InvalidStyle,
+                span,
+            )?;
+            self.pending.push(RawText::new(src, self.eol));
+            return Ok(());
+        };
+        if self.styles.get(name).is_some() {
+            let message = format!("style {} is defined more than once", name);
+            let span = src
+                .find(name)
+                .map_or(0..0, |start| start..start + name.len());
+            self.diagnose(
+                src,
+                Severity::Warning,
+                DiagnosticCode::DuplicateStyle,
+                span,
+                message,
+            );
+        }
+        raw.leading = std::mem::take(&mut self.pending);
+        raw.eol = self.eol;
+        style.raw = Some(Box::new(raw));
+        self.styles.add(style)?;
         Ok(())
     }
 
     pub(crate) fn parse_events(&mut self, src: &str) -> crate::Result<()> {
-        if let Some(pos) = src.find(':') {
-            let event_type = EventType::from_str(&src[..pos]).map_err(|_| {
-                Error::parse_error::<EventType>(format!("invalid event type {}", &src[..pos]))
-            })?;
-            let mut event = Event::new(event_type, &self.events);
-            let event_data = &src[pos + 1..].trim();
-            let n = self.events.order().len();
-            event_data
-                .splitn(n, ',')
-                .zip(self.events.order())
-                .try_for_each(|(value, format)| {
-                    let value = format.parse_value(value)?;
-                    event.set(*format, value);
-                    Ok::<_, Error>(())
-                })?;
-            self.events.push(event);
+        let line = src.trim();
+        let Some(pos) = line.find(':').filter(|_| !line.starts_with(';')) else {
+            self.pending.push(RawText::new(src, self.eol));
+            return Ok(());
+        };
+        let key = &line[..pos];
+        let Ok(event_type) = EventType::from_str(key) else {
+            let error = Error::parse_error::<EventType>(format!("invalid event type {}", key));
+            self.recover(
+                src,
+                error,
+                DiagnosticCode::UnknownEventType,
+                span_of(src, key),
+            )?;
+            self.pending.push(RawText::new(src, self.eol));
+            return Ok(());
+        };
+        let order = self.events.order().to_vec();
+        let mut event = Event::new(event_type, &self.events);
+        let (mut raw, fields) = RawLine::split(key, src, order.len(), true);
+        self.check_field_count(src, fields.len(), order.len());
+        for (value, format) in fields.into_iter().zip(&order) {
+            raw.push(*format, value);
+            match format.parse_value(value) {
+                Ok(value) => event.set(*format, value),
+                Err(error) => {
+                    self.recover(
+                        src,
+                        error,
+                        DiagnosticCode::InvalidEvent,
+                        span_of(src, value),
+                    )?;
+                    self.pending.push(RawText::new(src, self.eol));
+                    return Ok(());
+                }
+            }
         }
+        raw.leading = std::mem::take(&mut self.pending);
+        raw.eol = self.eol;
+        event.raw = Some(Box::new(raw));
+        self.events.push(event);
         Ok(())
     }
 
+    fn check_field_count(&mut self, src: &str, found: usize, expected: usize) {
+        if found < expected {
+            let end = src.trim_end().len();
+            self.diagnose(
+                src,
+                Severity::Warning,
+                DiagnosticCode::MissingFields,
+                end..end,
+                format!("found {} fields, format expects {}", found, expected),
+            );
+        }
+    }
+
     pub(crate) fn parse_fonts(&mut self, src: &str) -> crate::Result<()> {
-        if let Some(font) = src.strip_prefix("fontname:").map(str::trim) {
-            self.fonts.push(font.to_string());
+        match src.strip_prefix("fontname:").map(str::trim) {
+            Some(font) => {
+                self.flush_attachment()?;
+                self.start_attachment(src);
+                self.fonts.push(Attachment::new(font, vec![]));
+            }
+            None => self.encoded.push_str(src),
         }
         Ok(())
     }
 
     pub(crate) fn parse_graphics(&mut self, src: &str) -> crate::Result<()> {
-        if let Some(file) = src.strip_prefix("filename:").map(str::trim) {
-            self.graphics.push(file.to_string());
+        match src.strip_prefix("filename:").map(str::trim) {
+            Some(file) => {
+                self.flush_attachment()?;
+                self.start_attachment(src);
+                self.graphics.push(Attachment::new(file, vec![]));
+            }
+            None => self.encoded.push_str(src),
         }
         Ok(())
     }
+
+    fn start_attachment(&mut self, src: &str) {
+        self.attachment = (self.line, self.offset..self.offset + src.len());
+    }
+
+    /// Decodes the data collected for the last attachment of the current section. Data that
+    /// cannot be decoded drops the attachment, the raw lines are still written losslessly.
+    pub(crate) fn flush_attachment(&mut self) -> crate::Result<()> {
+        let encoded = std::mem::take(&mut self.encoded);
+        let attachments = match self.context {
+            Context::ParseFonts => &mut self.fonts.fonts,
+            Context::ParseGraphics => &mut self.graphics.graphics,
+            _ => return Ok(()),
+        };
+        let Some(attachment) = attachments.last_mut() else {
+            return Ok(());
+        };
+        let error = match uudecode(&encoded) {
+            Ok(data) => {
+                attachment.data = data;
+                return Ok(());
+            }
+            Err(error) => error,
+        };
+        attachments.pop();
+        let (line, span) = self.attachment.clone();
+        let Some(diagnostics) = &mut self.diagnostics else {
+            return Err(error.at_line(line));
+        };
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            line,
+            column: 1,
+            span,
+            code: DiagnosticCode::InvalidAttachment,
+            message: error.to_string(),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn push_line(&mut self, line: LineLayout) {
+        if let Some(section) = self.layout.sections.last_mut() {
+            section.lines.push(line);
+        }
+    }
+
+    pub(crate) fn push_section(&mut self, section: Section) {
+        self.flush_pending();
+        self.layout.sections.push(section);
+    }
+
+    /// Trivia left at the end of a section belongs to the section rather than to an item.
+    pub(crate) fn flush_pending(&mut self) {
+        for raw in std::mem::take(&mut self.pending) {
+            self.push_line(LineLayout::Raw(raw));
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -152,6 +390,15 @@ pub(crate) enum Context {
     ParseEvents,
     ParseFonts,
     ParseGraphics,
+    ParseUnknown,
+}
+
+/// The byte range of `part`, which must be a subslice of `src`.
+pub(crate) fn span_of(src: &str, part: &str) -> Range<usize> {
+    let start = (part.as_ptr() as usize)
+        .saturating_sub(src.as_ptr() as usize)
+        .min(src.len());
+    start..(start + part.len()).min(src.len())
 }
 
 pub fn parse_i64(src: &str) -> crate::Result<Value> {
@@ -234,7 +481,7 @@ mod tests {
         let mut parser = SsaParser::default();
         parser.parse_events("Dialogue: 0,0:00:00.00,0:00:05.00,Default,,0,0,0,,Hello, World!")?;
         parser.parse_events("Dialogue: 0,0:00:05.00,0:00:10.00,Default,,0,0,0,,你好，世界！")?;
-        let event = parser.events.get(0).unwrap();
+        let event = parser.events.first().unwrap();
         assert_eq!(
             event
                 .get(EventFormat::Layer)
@@ -257,8 +504,8 @@ mod tests {
         let mut parser = SsaParser::default();
         parser.parse_fonts("fontname:Arial")?;
         parser.parse_fonts("fontname:华康方圆体W7")?;
-        assert_eq!(parser.fonts.get(0).unwrap(), "Arial");
-        assert_eq!(parser.fonts.get(1).unwrap(), "华康方圆体W7");
+        assert_eq!(parser.fonts.first().unwrap().name, "Arial");
+        assert_eq!(parser.fonts.get(1).unwrap().name, "华康方圆体W7");
         Ok(())
     }
 
@@ -267,8 +514,8 @@ mod tests {
         let mut parser = SsaParser::default();
         parser.parse_graphics("filename:logo.png")?;
         parser.parse_graphics("filename:background.jpg")?;
-        assert_eq!(parser.graphics.get(0).unwrap(), "logo.png");
-        assert_eq!(parser.graphics.get(1).unwrap(), "background.jpg");
+        assert_eq!(parser.graphics.first().unwrap().name, "logo.png");
+        assert_eq!(parser.graphics.get(1).unwrap().name, "background.jpg");
         Ok(())
     }
 
@@ -0,0 +1,364 @@
+//! Rescaling of a script to another resolution, like Aegisub's "Resample Resolution".
+
+use crate::{
+    error::Error,
+    events::{
+        tag::{ClipShape, Tag, TagKind},
+        text::{Segment, Text},
+        EventFormat,
+    },
+    file::File,
+    styles::{Style, StyleFormat},
+    value::Value,
+};
+
+/// How to fit the script when the aspect ratio of the resolution changes.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum AspectRatio {
+    /// Scales horizontally and vertically by their own factors, distorting the picture.
+    #[default]
+    Stretch,
+    /// Scales by the width ratio and centers vertically, leaving bars above and below.
+    Letterbox,
+    /// Scales by the height ratio and centers horizontally, leaving bars left and right.
+    Pillarbox,
+}
+
+/// Maps coordinates of the old resolution to the new one.
+#[derive(Debug, Clone, Copy)]
+struct Scale {
+    x: f64,
+    y: f64,
+    offset_x: f64,
+    offset_y: f64,
+}
+
+impl Scale {
+    fn new(from: (f64, f64), to: (f64, f64), aspect: AspectRatio) -> Self {
+        let (x, y) = (to.0 / from.0, to.1 / from.1);
+        match aspect {
+            AspectRatio::Stretch => Self {
+                x,
+                y,
+                offset_x: 0.0,
+                offset_y: 0.0,
+            },
+            AspectRatio::Letterbox => Self {
+                x,
+                y: x,
+                offset_x: 0.0,
+                offset_y: (to.1 - from.1 * x) / 2.0,
+            },
+            AspectRatio::Pillarbox => Self {
+                x: y,
+                y,
+                offset_x: (to.0 - from.0 * y) / 2.0,
+                offset_y: 0.0,
+            },
+        }
+    }
+
+    fn pos_x(&self, x: f64) -> f64 {
+        round(x * self.x + self.offset_x)
+    }
+
+    fn pos_y(&self, y: f64) -> f64 {
+        round(y * self.y + self.offset_y)
+    }
+
+    fn width(&self, width: f64) -> f64 {
+        round(width * self.x)
+    }
+
+    fn height(&self, height: f64) -> f64 {
+        round(height * self.y)
+    }
+
+    /// How much wider glyphs get relative to their height.
+    fn stretch(&self) -> f64 {
+        self.x / self.y
+    }
+
+    fn style(&self, style: &mut Style) {
+        let mut update = |format, map: &dyn Fn(f64) -> f64| {
+            let Some(value) = style.get_mut(format) else {
+                return;
+            };
+            match value {
+                Value::Int(v) => *v = map(*v as f64).round() as i64,
+                Value::Float(v) => *v = map(*v),
+                _ => {}
+            }
+        };
+        update(StyleFormat::Fontsize, &|v| self.height(v));
+        update(StyleFormat::Outline, &|v| self.height(v));
+        update(StyleFormat::Shadow, &|v| self.height(v));
+        update(StyleFormat::Spacing, &|v| self.width(v));
+        update(StyleFormat::ScaleX, &|v| round(v * self.stretch()));
+        update(StyleFormat::MarginL, &|v| self.pos_x(v));
+        update(StyleFormat::MarginR, &|v| self.pos_x(v));
+        update(StyleFormat::MarginV, &|v| self.pos_y(v));
+    }
+
+    fn text(&self, text: &mut Text) {
+        let mut drawing = false;
+        for segment in text.iter_mut() {
+            match segment {
+                Segment::Plain(plain) if drawing => *plain = self.drawing(plain, (0.0, 0.0)),
+                Segment::Plain(_) => {}
+                Segment::Override(items) => {
+                    for tag in items.iter_mut().filter_map(|item| item.as_tag_mut()) {
+                        if let TagKind::Drawing(scale) = tag.kind() {
+                            drawing = *scale > 0;
+                        }
+                        self.tag(tag);
+                    }
+                }
+            }
+        }
+    }
+
+    fn tag(&self, tag: &mut Tag) {
+        let size = |v: &Option<f64>, map: fn(&Self, f64) -> f64| v.map(|v| map(self, v));
+        let kind = match tag.kind().clone() {
+            TagKind::Position { x, y } => TagKind::Position {
+                x: self.pos_x(x),
+                y: self.pos_y(y),
+            },
+            TagKind::Origin { x, y } => TagKind::Origin {
+                x: self.pos_x(x),
+                y: self.pos_y(y),
+            },
+            TagKind::Move {
+                x1,
+                y1,
+                x2,
+                y2,
+                times,
+            } => TagKind::Move {
+                x1: self.pos_x(x1),
+                y1: self.pos_y(y1),
+                x2: self.pos_x(x2),
+                y2: self.pos_y(y2),
+                times,
+            },
+            TagKind::Clip { inverse, shape } => TagKind::Clip {
+                inverse,
+                shape: match shape {
+                    ClipShape::Rect { x1, y1, x2, y2 } => ClipShape::Rect {
+                        x1: self.pos_x(x1),
+                        y1: self.pos_y(y1),
+                        x2: self.pos_x(x2),
+                        y2: self.pos_y(y2),
+                    },
+                    ClipShape::Vector { scale, drawing } => {
+                        // vector clips are absolute, in units of 1 / 2^(scale - 1) pixels
+                        let unit = 2f64.powi(scale.unwrap_or(1).clamp(1, 32) as i32 - 1);
+                        let offset = (self.offset_x * unit, self.offset_y * unit);
+                        ClipShape::Vector {
+                            scale,
+                            drawing: self.drawing(&drawing, offset),
+                        }
+                    }
+                },
+            },
+            TagKind::FontSize(v) => TagKind::FontSize(size(&v, Self::height)),
+            TagKind::Border(v) => TagKind::Border(size(&v, Self::height)),
+            TagKind::BorderX(v) => TagKind::BorderX(size(&v, Self::width)),
+            TagKind::BorderY(v) => TagKind::BorderY(size(&v, Self::height)),
+            TagKind::Shadow(v) => TagKind::Shadow(size(&v, Self::height)),
+            TagKind::ShadowX(v) => TagKind::ShadowX(size(&v, Self::width)),
+            TagKind::ShadowY(v) => TagKind::ShadowY(size(&v, Self::height)),
+            TagKind::BlurEdges(v) => TagKind::BlurEdges(size(&v, Self::height)),
+            TagKind::Blur(v) => TagKind::Blur(size(&v, Self::height)),
+            TagKind::FontSpacing(v) => TagKind::FontSpacing(size(&v, Self::width)),
+            TagKind::FontScaleX(v) => TagKind::FontScaleX(v.map(|v| round(v * self.stretch()))),
+            TagKind::DrawingBaselineOffset(v) => TagKind::DrawingBaselineOffset(self.height(v)),
+            TagKind::Transform { times, accel, tags } => TagKind::Transform {
+                times,
+                accel,
+                tags: tags
+                    .into_iter()
+                    .map(|mut tag| {
+                        self.tag(&mut tag);
+                        tag
+                    })
+                    .collect(),
+            },
+            _ => return,
+        };
+        if kind != *tag.kind() {
+            *tag.kind_mut() = kind;
+        }
+    }
+
+    /// Scales the coordinates of drawing commands such as `m 0 0 l 100 0`, moving them by
+    /// `offset` in drawing units.
+    fn drawing(&self, src: &str, offset: (f64, f64)) -> String {
+        let mut out = Vec::new();
+        let mut axis = 0;
+        for token in src.split_whitespace() {
+            match token.parse::<f64>() {
+                Ok(v) => {
+                    let v = if axis % 2 == 0 {
+                        round(v * self.x + offset.0)
+                    } else {
+                        round(v * self.y + offset.1)
+                    };
+                    out.push(v.to_string());
+                    axis += 1;
+                }
+                Err(_) => {
+                    out.push(token.to_string());
+                    axis = 0;
+                }
+            }
+        }
+        out.join(" ")
+    }
+}
+
+/// Rounds to hundredths, which is as precise as renderers care about.
+fn round(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0 + 0.0
+}
+
+/// The resolution scripts are rendered at when `PlayResX` or `PlayResY` is missing, following
+/// libass.
+fn play_res(file: &File) -> (i64, i64) {
+    match (file.script.get_play_res_x(), file.script.get_play_res_y()) {
+        (Some(x), Some(y)) => (x, y),
+        (Some(1280), None) => (1280, 1024),
+        (Some(x), None) => (x, x * 3 / 4),
+        (None, Some(1024)) => (1280, 1024),
+        (None, Some(y)) => (y * 4 / 3, y),
+        (None, None) => (384, 288),
+    }
+}
+
+pub(crate) fn resample(
+    file: &mut File,
+    width: i64,
+    height: i64,
+    aspect: AspectRatio,
+) -> crate::Result<()> {
+    let (from_x, from_y) = play_res(file);
+    if width <= 0 || height <= 0 || from_x <= 0 || from_y <= 0 {
+        return Err(Error::parse_error::<AspectRatio>(format!(
+            "cannot resample {}x{} to {}x{}",
+            from_x, from_y, width, height
+        )));
+    }
+    let scale = Scale::new(
+        (from_x as f64, from_y as f64),
+        (width as f64, height as f64),
+        aspect,
+    );
+    file.script.set_play_res_x(width);
+    file.script.set_play_res_y(height);
+    if file.script.get_layout_res_x().is_some() || file.script.get_layout_res_y().is_some() {
+        file.script.set_layout_res_x(width);
+        file.script.set_layout_res_y(height);
+    }
+    for (_, style) in file.styles.iter_mut() {
+        scale.style(style);
+    }
+    for event in file.events.iter_mut() {
+        for (format, map) in [
+            (EventFormat::MarginL, Scale::pos_x as fn(&Scale, f64) -> f64),
+            (EventFormat::MarginR, Scale::pos_x),
+            (EventFormat::MarginV, Scale::pos_y),
+        ] {
+            // a margin of 0 means the margin of the style
+            if let Some(margin) = event
+                .get_mut(format)
+                .and_then(Value::as_int_mut)
+                .filter(|margin| **margin != 0)
+            {
+                *margin = map(&scale, *margin as f64).round() as i64;
+            }
+        }
+        if let Some(text) = event
+            .get_mut(EventFormat::Text)
+            .and_then(Value::as_text_mut)
+        {
+            scale.text(text);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::WriteOptions;
+
+    fn script(res: &str, events: &str) -> File {
+        File::from_str(format!(
+            "[Script Info]\nScriptType: v4.00+\n{}\n\n[V4+ Styles]\n\
+             Format: Name, Fontname, Fontsize, ScaleX, ScaleY, Spacing, Outline, Shadow, MarginL, MarginR, MarginV\n\
+             Style: Default,Arial,40,100,100,1.5,2,1,20,20,30\n\n\
+             [Events]\nFormat: Layer, Start, End, Style, MarginL, MarginR, MarginV, Text\n{}",
+            res, events
+        ))
+        .unwrap()
+    }
+
+    fn lines(file: &File) -> Vec<String> {
+        let out = file.to_string_with(&WriteOptions::lossless()).unwrap();
+        out.lines()
+            .filter(|line| line.starts_with("Style:") || line.starts_with("Dialogue:"))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_resample() -> crate::Result<()> {
+        let events = "Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,10,0,\
+             {\\pos(640,360)\\fs20\\bord2\\xshad1\\fsp2\\clip(0,0,320,180)}a\
+             {\\t(0,500,\\fs30\\blur1)\\move(0,0,100,100,0,500)\\org(10,20)}b\n\
+             Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,0,0,\
+             {\\iclip(2,m 0 0 l 100 0 100 50)\\p1}m 0 0 l 10 0 10 10.5{\\p0}x\n";
+        let mut file = script("PlayResX: 1280\nPlayResY: 720\nLayoutResX: 1280", events);
+        file.resample(1920, 1080, AspectRatio::Stretch)?;
+        assert_eq!(file.script.get_play_res_x(), Some(1920));
+        assert_eq!(file.script.get_play_res_y(), Some(1080));
+        assert_eq!(file.script.get_layout_res_x(), Some(1920));
+        assert_eq!(file.script.get_layout_res_y(), Some(1080));
+        assert_eq!(
+            lines(&file),
+            [
+                "Style: Default,Arial,60,100,100,2.25,3,1.5,30,30,45",
+                "Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,15,0,\
+                 {\\pos(960,540)\\fs30\\bord3\\xshad1.5\\fsp3\\clip(0,0,480,270)}a\
+                 {\\t(0,500,\\fs45\\blur1.5)\\move(0,0,150,150,0,500)\\org(15,30)}b",
+                "Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,0,0,\
+                 {\\iclip(2,m 0 0 l 150 0 150 75)\\p1}m 0 0 l 15 0 15 15.75{\\p0}x",
+            ]
+        );
+
+        // 4:3 into 16:9 with bars left and right, offset by 240
+        let events = "Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,0,0,\
+             {\\pos(320,240)\\iclip(2,m 0 0 l 10 10)\\p1}m 0 0 l 10 10\n";
+        let mut file = script("PlayResX: 640\nPlayResY: 480", events);
+        file.resample(1920, 1080, AspectRatio::Pillarbox)?;
+        assert_eq!(file.script.get_layout_res_x(), None);
+        assert_eq!(
+            lines(&file),
+            [
+                "Style: Default,Arial,90,100,100,3.38,4.5,2.25,285,285,68",
+                "Dialogue: 0,0:00:00.00,0:00:01.00,Default,0,0,0,\
+                 {\\pos(960,540)\\iclip(2,m 480 0 l 502.5 22.5)\\p1}m 0 0 l 22.5 22.5",
+            ]
+        );
+        let mut stretched = script("PlayResX: 640\nPlayResY: 480", events);
+        stretched.resample(1920, 1080, AspectRatio::Stretch)?;
+        assert_eq!(
+            lines(&stretched)[0],
+            "Style: Default,Arial,90,133.33,100,4.5,4.5,2.25,60,60,68"
+        );
+        assert!(file.resample(0, 1080, AspectRatio::Letterbox).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Resynchronisation of events whose timing drifts, e.g. after a framerate change or a
+//! re-encode at another speed, where a constant shift is not enough.
+
+use crate::{
+    error::Error,
+    events::{
+        tag::{KaraokeKind, TagKind},
+        Event, EventFormat, Events,
+    },
+    timestamp::Timestamp,
+    value::Value,
+};
+
+/// A piecewise-linear time map through two or more `(old, new)` anchor points. Times before
+/// the first or after the last anchor follow the nearest segment.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Resync {
+    /// Sorted by both old and new time.
+    anchors: Vec<(Timestamp, Timestamp)>,
+}
+
+impl Resync {
+    /// Fails with fewer than two distinct anchors or when the anchors do not keep the order of
+    /// the times they map.
+    pub fn new(anchors: impl IntoIterator<Item = (Timestamp, Timestamp)>) -> crate::Result<Self> {
+        let mut anchors = anchors.into_iter().collect::<Vec<_>>();
+        anchors.sort_unstable();
+        anchors.dedup();
+        if anchors.len() < 2 {
+            return Err(Error::parse_error::<Resync>(
+                "at least two anchors are needed",
+            ));
+        }
+        for pair in anchors.windows(2) {
+            let [(old_a, new_a), (old_b, new_b)] = [pair[0], pair[1]];
+            if old_a == old_b || new_a >= new_b {
+                return Err(Error::parse_error::<Resync>(format!(
+                    "anchor {} -> {} conflicts with {} -> {}",
+                    old_a, new_a, old_b, new_b
+                )));
+            }
+        }
+        Ok(Self { anchors })
+    }
+
+    /// Anchors the start of event `from` in `events` to the start of event `to` in
+    /// `reference`, a correctly timed script such as another translation of the same video.
+    pub fn from_events(
+        events: &Events,
+        reference: &Events,
+        pairs: impl IntoIterator<Item = (usize, usize)>,
+    ) -> crate::Result<Self> {
+        let start = |events: &Events, position: usize| {
+            events
+                .get(position)
+                .and_then(|event| event.get(EventFormat::Start))
+                .and_then(Value::as_timestamp)
+                .ok_or_else(|| {
+                    Error::parse_error::<Resync>(format!("no event start at {}", position))
+                })
+        };
+        let anchors = pairs
+            .into_iter()
+            .map(|(from, to)| Ok((start(events, from)?, start(reference, to)?)))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Self::new(anchors)
+    }
+
+    pub fn anchors(&self) -> &[(Timestamp, Timestamp)] {
+        &self.anchors
+    }
+
+    pub fn map(&self, time: Timestamp) -> Timestamp {
+        Timestamp::from_centis((self.map_millis(time.millis() as f64) / 10.0).round() as i64)
+    }
+
+    fn map_millis(&self, millis: f64) -> f64 {
+        let next = self
+            .anchors
+            .partition_point(|(old, _)| (old.millis() as f64) <= millis)
+            .clamp(1, self.anchors.len() - 1);
+        let (old_a, new_a) = self.anchors[next - 1];
+        let (old_b, new_b) = self.anchors[next];
+        let slope = (new_b - new_a).millis() as f64 / (old_b - old_a).millis() as f64;
+        new_a.millis() as f64 + (millis - old_a.millis() as f64) * slope
+    }
+
+    /// Maps the times of the tags of `event`, which are relative to its start or end, before
+    /// its own start and end are mapped.
+    fn map_tags(&self, event: &mut Event, start: Timestamp, end: Timestamp) {
+        let (start, end) = (start.millis() as f64, end.millis() as f64);
+        let (new_start, new_end) = (self.map_millis(start), self.map_millis(end));
+        let from_start = |t: i64| (self.map_millis(start + t as f64) - new_start).round() as i64;
+        let to_end = |t: i64| (new_end - self.map_millis(end - t as f64)).round() as i64;
+        // karaoke durations are centiseconds adding up from the start, their ends are mapped
+        // so that rounding does not accumulate
+        let mut karaoke = (0, 0);
+        let Some(text) = event
+            .get_mut(EventFormat::Text)
+            .and_then(Value::as_text_mut)
+        else {
+            return;
+        };
+        for tag in text.tags_mut() {
+            let kind = match tag.kind().clone() {
+                TagKind::Move {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    times: Some((t1, t2)),
+                } => TagKind::Move {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    times: Some((from_start(t1), from_start(t2))),
+                },
+                TagKind::Fade { fade_in, fade_out } => TagKind::Fade {
+                    fade_in: from_start(fade_in),
+                    fade_out: to_end(fade_out),
+                },
+                TagKind::FadeComplex { alphas, times } => TagKind::FadeComplex {
+                    alphas,
+                    times: times.map(from_start),
+                },
+                TagKind::Transform {
+                    times: Some((t1, t2)),
+                    accel,
+                    tags,
+                } => TagKind::Transform {
+                    times: Some((from_start(t1), from_start(t2))),
+                    accel,
+                    tags,
+                },
+                TagKind::Karaoke {
+                    kind,
+                    duration: Some(duration),
+                } => karaoke_tag(kind, duration, &mut karaoke, |centis| {
+                    self.map_millis(start + centis as f64 * 10.0) - new_start
+                }),
+                _ => continue,
+            };
+            if kind != *tag.kind() {
+                *tag.kind_mut() = kind;
+            }
+        }
+    }
+}
+
+fn karaoke_tag(
+    kind: KaraokeKind,
+    duration: i64,
+    (old_end, new_end): &mut (i64, i64),
+    map: impl Fn(i64) -> f64,
+) -> TagKind {
+    *old_end += duration;
+    let end = (map(*old_end) / 10.0).round() as i64;
+    let duration = (end - *new_end).max(0);
+    *new_end += duration;
+    TagKind::Karaoke {
+        kind,
+        duration: Some(duration),
+    }
+}
+
+pub(crate) fn resync(events: &mut Events, resync: &Resync) {
+    for event in events.iter_mut() {
+        let time = |format| {
+            event
+                .get(format)
+                .and_then(Value::as_timestamp)
+                .unwrap_or_default()
+        };
+        let (start, end) = (time(EventFormat::Start), time(EventFormat::End));
+        resync.map_tags(event, start, end);
+        for (format, time) in [(EventFormat::Start, start), (EventFormat::End, end)] {
+            if let Some(value) = event.get_mut(format).and_then(Value::as_timestamp_mut) {
+                *value = resync.map(time);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::text::Text, parser::Parser};
+
+    fn t(centis: i64) -> Timestamp {
+        Timestamp::from_centis(centis)
+    }
+
+    #[test]
+    fn test_resync_map() -> crate::Result<()> {
+        // slower by 4.2% up to the second anchor, then by more than twice
+        let resync = Resync::new([
+            (t(100_000), t(104_200)),
+            (t(0), t(0)),
+            (t(200_000), t(308_500)),
+        ])?;
+        assert_eq!(resync.anchors()[0], (t(0), t(0)));
+        assert_eq!(resync.map(t(50_000)), t(52_100));
+        assert_eq!(resync.map(t(150_000)), t(206_350));
+        assert_eq!(resync.map(t(-100)), t(-104));
+        assert_eq!(resync.map(t(300_000)), t(512_800));
+        assert!(Resync::new([(t(0), t(0))]).is_err());
+        assert!(Resync::new([(t(0), t(0)), (t(0), t(0))]).is_err());
+        assert!(Resync::new([(t(0), t(100)), (t(100), t(50))]).is_err());
+        assert!(Resync::new([(t(0), t(0)), (t(0), t(100))]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync_events() -> crate::Result<()> {
+        let event = |events: &Events, start: i64, end: i64, text: &str| -> crate::Result<Event> {
+            let mut event = Event::new(crate::events::EventType::Dialogue, events);
+            event.set(EventFormat::Start, t(start));
+            event.set(EventFormat::End, t(end));
+            event.set(EventFormat::Text, Text::parse(text)?);
+            Ok(event)
+        };
+        let mut events = Events::default();
+        let texts = [
+            (1000, 1200, "{\\move(0,0,10,10,100,1900)\\fad(200,300)}a"),
+            (
+                2000,
+                2300,
+                "{\\k50\\kf50\\ko100\\K100}a{\\t(0,1000,\\fs20)\\pos(1,2)}b",
+            ),
+            (4000, 4100, "{\\fade(255,0,255,0,100,900,1000)\\t(\\fs1)}c"),
+        ];
+        for (start, end, text) in texts {
+            let e = event(&events, start, end, text)?;
+            events.push(e);
+        }
+        let mut reference = Events::default();
+        for start in [1500, 5500] {
+            let e = event(&reference, start, start + 100, "")?;
+            reference.push(e);
+        }
+        // 5 seconds later and a third slower
+        let resync = Resync::from_events(&events, &reference, [(0, 0), (2, 1)])?;
+        assert_eq!(resync.anchors(), &[(t(1000), t(1500)), (t(4000), t(5500))]);
+        assert!(Resync::from_events(&events, &reference, [(0, 0), (3, 1)]).is_err());
+
+        events.resync(&resync);
+        let summary = events
+            .iter()
+            .map(|event| {
+                let time = |format| event.get(format).unwrap().as_timestamp().unwrap();
+                let text = event.get(EventFormat::Text).unwrap().as_text().unwrap();
+                format!(
+                    "{} {} {}",
+                    time(EventFormat::Start),
+                    time(EventFormat::End),
+                    text
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                "0:00:15.00 0:00:17.67 {\\move(0,0,10,10,133,2533)\\fad(267,400)}a",
+                "0:00:28.33 0:00:32.33 {\\k67\\kf66\\ko134\\K133}a{\\t(0,1333,\\fs20)\\pos(1,2)}b",
+                "0:00:55.00 0:00:56.33 {\\fade(255,0,255,0,133,1200,1333)\\t(\\fs1)}c",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,634 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! A [`File`] serialises to the following JSON:
+//!
+//! ```json
+//! {
+//!   "version": "v4.00+",
+//!   "encoding": "UTF-8",
+//!   "script_info": { ";": ["Script generated by Aegisub"], "Title": "Example", "PlayResX": 1920 },
+//!   "styles": {
+//!     "format": ["Name", "Fontname", "Fontsize", "PrimaryColour"],
+//!     "styles": { "Default": { "Fontname": "Arial", "Fontsize": 60, "PrimaryColour": "&H00FFFFFF" } }
+//!   },
+//!   "events": {
+//!     "format": ["Layer", "Start", "End", "Style", "Text"],
+//!     "events": [
+//!       { "type": "Dialogue", "Layer": 0, "Start": "0:00:01.50", "End": "0:00:04.00", "Style": "Default", "Text": "{\\i1}Hello" }
+//!     ]
+//!   },
+//!   "fonts": [{ "name": "font_0.ttf", "data": "<SSA UUEncoded data>" }],
+//!   "graphics": []
+//! }
+//! ```
+//!
+//! - Script info properties keep their order, comments are listed under `";"`.
+//! - Styles are keyed by name, so files with two styles of the same name fail to serialise.
+//!   Events are objects keyed by [`EventFormat`] names. Fields that are not set are left out.
+//! - Field values are written as numbers where the format uses numbers, and as they appear in a
+//!   script otherwise: timestamps as `H:MM:SS.CC` (`-` in front when negative), colours as
+//!   written, text with its override tags. When reading, `Start` and `End` may also be given as
+//!   milliseconds and any other field as a string in script syntax.
+//! - All top level keys are optional when reading.
+
+use std::collections::HashSet;
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_8};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeMap, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    attachment::{uudecode, uuencode, Attachment},
+    color::Color,
+    events::{effect::Effect, text::Text, Event, EventFormat, EventType, Events},
+    file::File,
+    fonts::Fonts,
+    graphics::Graphics,
+    parser::Parser,
+    script_info::ScriptInfo,
+    styles::{Style, StyleFormat, V4Styles},
+    timestamp::Timestamp,
+    value::Value,
+    version::Version,
+};
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_negative() {
+            serializer.collect_str(&format_args!("-{}", -*self))
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TimestampVisitor;
+
+        impl Visitor<'_> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "a timestamp or milliseconds")
+            }
+
+            fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Timestamp, E> {
+                Ok(Timestamp::from_millis(millis))
+            }
+
+            fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Timestamp, E> {
+                Ok(Timestamp::from_millis(millis as i64))
+            }
+
+            fn visit_str<E: de::Error>(self, src: &str) -> Result<Timestamp, E> {
+                Timestamp::parse(src).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+/// Serialises through [`Display`](std::fmt::Display) and deserialises through [`Parser`].
+macro_rules! string_serde {
+    ($($ty:ty),*) => {
+        $(
+            impl Serialize for $ty {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $ty {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let src = String::deserialize(deserializer)?;
+                    <$ty>::parse(&src).map_err(de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+string_serde!(Color, Effect, Text);
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Str(s) => serializer.serialize_str(s),
+            Value::Int(i) => serializer.serialize_i64(*i),
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::Boolean(b) => serializer.serialize_bool(*b),
+            Value::List(list) => list.serialize(serializer),
+            Value::Duration(duration) => serializer.serialize_u128(duration.as_millis()),
+            Value::Timestamp(timestamp) => timestamp.serialize(serializer),
+            Value::Effect(effect) => effect.serialize(serializer),
+            Value::Text(text) => text.serialize(serializer),
+            Value::Color(color) => color.serialize(serializer),
+        }
+    }
+}
+
+/// Reads JSON types as the matching [`Value`], strings stay [`Value::Str`]. Style and event
+/// fields are converted to the type of their format afterwards.
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "a string, number, boolean or list")
+            }
+
+            fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+                Ok(Value::Boolean(b))
+            }
+
+            fn visit_i64<E: de::Error>(self, i: i64) -> Result<Value, E> {
+                Ok(Value::Int(i))
+            }
+
+            fn visit_u64<E: de::Error>(self, u: u64) -> Result<Value, E> {
+                Ok(i64::try_from(u).map_or(Value::Float(u as f64), Value::Int))
+            }
+
+            fn visit_f64<E: de::Error>(self, f: f64) -> Result<Value, E> {
+                Ok(Value::Float(f))
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+                Ok(Value::Str(s.to_string()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut list = vec![];
+                while let Some(value) = seq.next_element()? {
+                    list.push(value);
+                }
+                Ok(Value::List(list))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// The entries of a JSON object in source order.
+struct Entries<V>(Vec<(String, V)>);
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Entries<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for EntriesVisitor<V> {
+            type Value = Entries<V>;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                write!(f, "an object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Entries<V>, A::Error> {
+                let mut entries = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor(PhantomData))
+    }
+}
+
+/// Converts a deserialised field to the type `parse` produces for its script syntax.
+fn typed_value<E: de::Error>(
+    value: Value,
+    parse: impl Fn(&str) -> crate::Result<Value>,
+) -> Result<Value, E> {
+    let src = match value {
+        Value::Str(s) => s,
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Boolean(b) => if b { "-1" } else { "0" }.to_string(),
+        value => return Err(E::custom(format!("unexpected field value {}", value))),
+    };
+    parse(&src).map_err(E::custom)
+}
+
+fn event_value<E: de::Error>(format: EventFormat, value: Value) -> Result<Value, E> {
+    match (format, value) {
+        (EventFormat::Start | EventFormat::End, Value::Int(millis)) => {
+            Ok(Timestamp::from_millis(millis).into())
+        }
+        (EventFormat::Marked, Value::Int(marked)) => Ok(marked.into()),
+        (format, value) => typed_value(value, |src| format.parse_value(src)),
+    }
+}
+
+fn parse_order<F: FromStr, E: de::Error>(names: &[String]) -> Result<Vec<F>, E> {
+    names
+        .iter()
+        .map(|name| F::from_str(name).map_err(|_| E::custom(format!("unknown field {}", name))))
+        .collect()
+}
+
+impl Serialize for ScriptInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScriptInfo {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Entries(properties) = Entries::<Value>::deserialize(deserializer)?;
+        let mut script_info = ScriptInfo::default();
+        for (key, value) in properties {
+            script_info.add_property(key, value);
+        }
+        Ok(script_info)
+    }
+}
+
+/// The fields of a style, without its name when it is the key of the style.
+struct StyleFields<'a> {
+    style: &'a Style,
+    with_name: bool,
+}
+
+impl Serialize for StyleFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (format, value) in self.style.iter() {
+            match value {
+                Some(_) if format == StyleFormat::Name && !self.with_name => {}
+                Some(value) => map.serialize_entry(&format.to_string(), value)?,
+                None => {}
+            }
+        }
+        map.end()
+    }
+}
+
+fn build_style<E: de::Error>(styles: &V4Styles, fields: Vec<(String, Value)>) -> Result<Style, E> {
+    let mut style = Style::new(styles);
+    for (name, value) in fields {
+        let format = StyleFormat::from_str(&name)
+            .ok()
+            .filter(|format| styles.order().contains(format))
+            .ok_or_else(|| E::custom(format!("field {} is not part of the format", name)))?;
+        style.set(format, typed_value(value, |src| format.parse_value(src))?);
+    }
+    Ok(style)
+}
+
+impl Serialize for Style {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StyleFields {
+            style: self,
+            with_name: true,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A single style uses the order of its fields as format, which must include `Name`.
+impl<'de> Deserialize<'de> for Style {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Entries(fields) = Entries::<Value>::deserialize(deserializer)?;
+        let names = fields
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let styles = V4Styles::new(parse_order(&names)?).map_err(de::Error::custom)?;
+        build_style(&styles, fields)
+    }
+}
+
+impl Serialize for V4Styles {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Named<'a>(&'a V4Styles);
+
+        impl Serialize for Named<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(None)?;
+                let mut names = HashSet::new();
+                for (name, style) in self.0.iter() {
+                    if !names.insert(name) {
+                        return Err(ser::Error::custom(format!("duplicate style {}", name)));
+                    }
+                    let fields = StyleFields {
+                        style,
+                        with_name: false,
+                    };
+                    map.serialize_entry(name, &fields)?;
+                }
+                map.end()
+            }
+        }
+
+        let order = self
+            .order()
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        let mut state = serializer.serialize_struct("V4Styles", 2)?;
+        state.serialize_field("format", &order)?;
+        state.serialize_field("styles", &Named(self))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for V4Styles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct StylesSchema {
+            format: Vec<String>,
+            #[serde(default)]
+            styles: Option<Entries<Entries<Value>>>,
+        }
+
+        let schema = StylesSchema::deserialize(deserializer)?;
+        let mut styles = V4Styles::new(parse_order(&schema.format)?).map_err(de::Error::custom)?;
+        for (name, Entries(fields)) in schema.styles.map(|s| s.0).unwrap_or_default() {
+            let mut style = build_style(&styles, fields)?;
+            style.set(StyleFormat::Name, name);
+            styles.add(style).map_err(de::Error::custom)?;
+        }
+        Ok(styles)
+    }
+}
+
+fn build_event<E: de::Error>(events: &Events, fields: Vec<(String, Value)>) -> Result<Event, E> {
+    let mut event_type = EventType::Dialogue;
+    let mut values = vec![];
+    for (name, value) in fields {
+        if name == "type" {
+            event_type = value
+                .as_str()
+                .and_then(|s| EventType::from_str(s).ok())
+                .ok_or_else(|| E::custom(format!("invalid event type {}", value)))?;
+            continue;
+        }
+        let format = EventFormat::from_str(&name)
+            .ok()
+            .filter(|format| events.order().contains(format))
+            .ok_or_else(|| E::custom(format!("field {} is not part of the format", name)))?;
+        values.push((format, event_value(format, value)?));
+    }
+    let mut event = Event::new(event_type, events);
+    for (format, value) in values {
+        event.set(format, value);
+    }
+    Ok(event)
+}
+
+impl Serialize for Event {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", &self.event_type().to_string())?;
+        for (format, value) in self.iter() {
+            if let Some(value) = value {
+                map.serialize_entry(&format.to_string(), value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// A single event uses the order of its fields as format. `type` defaults to `Dialogue`.
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Entries(fields) = Entries::<Value>::deserialize(deserializer)?;
+        let names = fields
+            .iter()
+            .filter(|(name, _)| name != "type")
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        build_event(&Events::new(parse_order(&names)?), fields)
+    }
+}
+
+impl Serialize for Events {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let order = self
+            .order()
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        let mut state = serializer.serialize_struct("Events", 2)?;
+        state.serialize_field("format", &order)?;
+        state.serialize_field("events", &self.events)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Events {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct EventsSchema {
+            format: Vec<String>,
+            #[serde(default)]
+            events: Vec<Entries<Value>>,
+        }
+
+        let schema = EventsSchema::deserialize(deserializer)?;
+        let mut events = Events::new(parse_order(&schema.format)?);
+        for Entries(fields) in schema.events {
+            let event = build_event(&events, fields)?;
+            events.push(event);
+        }
+        Ok(events)
+    }
+}
+
+impl Serialize for Attachment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Attachment", 2)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("data", &uuencode(&self.data))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Attachment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct AttachmentSchema {
+            name: String,
+            data: String,
+        }
+
+        let schema = AttachmentSchema::deserialize(deserializer)?;
+        let data = uudecode(&schema.data).map_err(de::Error::custom)?;
+        Ok(Attachment::new(schema.name, data))
+    }
+}
+
+#[derive(Serialize)]
+struct FileRef<'a> {
+    version: &'a Version,
+    encoding: &'a str,
+    script_info: &'a ScriptInfo,
+    styles: &'a V4Styles,
+    events: &'a Events,
+    fonts: &'a Fonts,
+    graphics: &'a Graphics,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct FileSchema {
+    version: Version,
+    encoding: Option<String>,
+    script_info: ScriptInfo,
+    styles: V4Styles,
+    events: Events,
+    fonts: Fonts,
+    graphics: Graphics,
+}
+
+impl Default for FileSchema {
+    fn default() -> Self {
+        let file = File::default();
+        Self {
+            version: file.version,
+            encoding: None,
+            script_info: file.script,
+            styles: file.styles,
+            events: file.events,
+            fonts: file.fonts,
+            graphics: file.graphics,
+        }
+    }
+}
+
+impl Serialize for File {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FileRef {
+            version: &self.version,
+            encoding: self.encoding.name(),
+            script_info: &self.script,
+            styles: &self.styles,
+            events: &self.events,
+            fonts: &self.fonts,
+            graphics: &self.graphics,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for File {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let schema = FileSchema::deserialize(deserializer)?;
+        let encoding = match schema.encoding {
+            Some(label) => Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| de::Error::custom(format!("unknown encoding {}", label)))?,
+            None => UTF_8,
+        };
+        Ok(File {
+            version: schema.version,
+            script: schema.script_info,
+            styles: schema.styles,
+            events: schema.events,
+            fonts: schema.fonts,
+            graphics: schema.graphics,
+            encoding,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::GBK;
+
+    const SOURCE: &str = "[Script Info]
+; comment
+Title: Serde
+PlayResX: 1920
+Timer: 100.0000
+ScaledBorderAndShadow: yes
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, ScaleX, Bold
+Style: Default,Arial,60,&H00FFFFFF,100,-1
+Style: Sign,Verdana,40,&H80112233,95.5,0
+
+[Events]
+Format: Layer, Start, End, Style, Effect, Text
+Comment: 0,0:00:00.00,0:00:01.00,Default,,note
+Dialogue: 1,0:00:01.50,0:00:04.00,Sign,Banner;30;1,{\\c&H0000FF&\\pos(10,20)}Hello\\NWorld
+
+[Fonts]
+fontname: a_0.ttf
+47&O47%
+";
+
+    #[test]
+    fn test_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::from_str(SOURCE)?;
+        file.encoding = GBK;
+        let json = serde_json::to_value(&file)?;
+        assert_eq!(json["version"], "v4.00+");
+        assert_eq!(json["encoding"], "GBK");
+        assert_eq!(json["script_info"][";"][0], "comment");
+        assert_eq!(json["script_info"]["ScaledBorderAndShadow"], true);
+        assert_eq!(json["styles"]["styles"]["Default"]["Fontsize"], 60);
+        assert_eq!(
+            json["styles"]["styles"]["Sign"]["PrimaryColour"],
+            "&H80112233"
+        );
+        assert!(json["styles"]["styles"]["Sign"].get("Name").is_none());
+        assert_eq!(json["events"]["events"][1]["type"], "Dialogue");
+        assert_eq!(json["events"]["events"][1]["Start"], "0:00:01.50");
+        assert_eq!(json["events"]["events"][1]["Effect"], "Banner;30;1");
+        assert_eq!(json["fonts"][0]["data"], "47&O47%");
+
+        let json = serde_json::to_string(&file)?;
+        let parsed: File = serde_json::from_str(&json)?;
+        assert_eq!(parsed, file);
+        assert_eq!(parsed.encoding, GBK);
+        assert_eq!(serde_json::to_string(&parsed)?, json);
+
+        let duplicate = SOURCE.replace("Style: Sign,", "Style: Default,");
+        let error = serde_json::to_string(&File::from_str(duplicate)?).unwrap_err();
+        assert_eq!(error.to_string(), "duplicate style Default");
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_loose_values() -> Result<(), Box<dyn std::error::Error>> {
+        let event: Event = serde_json::from_str(
+            r#"{"Start": 1500, "End": "-0:00:01.00", "Layer": "2", "Text": "{\\b1}a"}"#,
+        )?;
+        assert_eq!(
+            event.get(EventFormat::Start),
+            Some(&Timestamp::from_centis(150).into())
+        );
+        assert_eq!(
+            event.get(EventFormat::End),
+            Some(&Timestamp::from_centis(-100).into())
+        );
+        assert_eq!(event.get(EventFormat::Layer), Some(&Value::Int(2)));
+        assert_eq!(event.event_type(), EventType::Dialogue);
+        let json = serde_json::to_value(&event)?;
+        assert_eq!(json["End"], "-0:00:01.00");
+
+        let style: Style = serde_json::from_str(r#"{"Name": "A", "ScaleX": 100, "Bold": true}"#)?;
+        assert_eq!(style.get(StyleFormat::ScaleX), Some(&Value::Float(100.0)));
+        assert_eq!(style.get(StyleFormat::Bold), Some(&Value::Int(-1)));
+        assert!(serde_json::from_str::<Style>(r#"{"ScaleX": 100}"#).is_err());
+        assert!(
+            serde_json::from_str::<Events>(r#"{"format": ["Start"], "events": [{"End": 1}]}"#)
+                .is_err()
+        );
+        Ok(())
+    }
+}
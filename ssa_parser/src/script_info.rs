@@ -1,6 +1,6 @@
 use std::{collections::HashMap, fmt::Display};
 
-use crate::{parser::Parser, value::Value};
+use crate::{color::YCbCrMatrix, parser::Parser, value::Value};
 
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 pub struct ScriptInfo {
@@ -72,8 +72,7 @@ impl ScriptInfo {
     }
 
     pub fn get_comments(&self) -> Option<&Vec<Value>> {
-        self.get_property(Key::Comment)
-            .and_then(Value::as_list)
+        self.get_property(Key::Comment).and_then(Value::as_list)
     }
 
     pub fn get_comments_mut(&mut self) -> Option<&mut Vec<Value>> {
@@ -86,8 +85,7 @@ impl ScriptInfo {
     }
 
     pub fn get_title(&self) -> Option<&str> {
-        self.get_property(Key::Title)
-            .and_then(Value::as_str)
+        self.get_property(Key::Title).and_then(Value::as_str)
     }
 
     pub fn get_title_mut(&mut self) -> Option<&mut String> {
@@ -164,15 +162,11 @@ impl ScriptInfo {
     }
 
     pub fn set_synch_point(&mut self, synch_point: impl Into<String>) {
-        self.add_property(
-            Key::SynchPoint.to_string(),
-            Value::Str(synch_point.into()),
-        );
+        self.add_property(Key::SynchPoint.to_string(), Value::Str(synch_point.into()));
     }
 
     pub fn get_synch_point(&self) -> Option<&str> {
-        self.get_property(Key::SynchPoint)
-            .and_then(Value::as_str)
+        self.get_property(Key::SynchPoint).and_then(Value::as_str)
     }
 
     pub fn get_synch_point_mut(&mut self) -> Option<&mut String> {
@@ -240,13 +234,24 @@ impl ScriptInfo {
             .and_then(|s| Collisions::parse(s).ok())
     }
 
+    pub fn set_ycbcr_matrix(&mut self, matrix: YCbCrMatrix) {
+        self.add_property(Key::YCbCrMatrix.to_string(), Value::Str(matrix.to_string()));
+    }
+
+    /// The matrix the colours of the script are meant for, `None` when the property is missing
+    /// or names a matrix that is not known.
+    pub fn get_ycbcr_matrix(&self) -> Option<YCbCrMatrix> {
+        self.get_property(Key::YCbCrMatrix)
+            .and_then(Value::as_str)
+            .and_then(|s| YCbCrMatrix::parse(s).ok())
+    }
+
     pub fn set_play_res_y(&mut self, play_res_y: i64) {
         self.add_property(Key::PlayResY.to_string(), Value::Int(play_res_y));
     }
 
     pub fn get_play_res_y(&self) -> Option<i64> {
-        self.get_property(Key::PlayResY)
-            .and_then(Value::as_int)
+        self.get_property(Key::PlayResY).and_then(Value::as_int)
     }
 
     pub fn set_play_res_x(&mut self, play_res_x: i64) {
@@ -254,8 +259,23 @@ impl ScriptInfo {
     }
 
     pub fn get_play_res_x(&self) -> Option<i64> {
-        self.get_property(Key::PlayResX)
-            .and_then(Value::as_int)
+        self.get_property(Key::PlayResX).and_then(Value::as_int)
+    }
+
+    pub fn set_layout_res_x(&mut self, layout_res_x: i64) {
+        self.add_property(Key::LayoutResX.to_string(), Value::Int(layout_res_x));
+    }
+
+    pub fn get_layout_res_x(&self) -> Option<i64> {
+        self.get_property(Key::LayoutResX).and_then(Value::as_int)
+    }
+
+    pub fn set_layout_res_y(&mut self, layout_res_y: i64) {
+        self.add_property(Key::LayoutResY.to_string(), Value::Int(layout_res_y));
+    }
+
+    pub fn get_layout_res_y(&self) -> Option<i64> {
+        self.get_property(Key::LayoutResY).and_then(Value::as_int)
     }
 
     pub fn set_play_depth(&mut self, play_depth: i64) {
@@ -263,8 +283,7 @@ impl ScriptInfo {
     }
 
     pub fn get_play_depth(&self) -> Option<i64> {
-        self.get_property(Key::PlayDepth)
-            .and_then(Value::as_int)
+        self.get_property(Key::PlayDepth).and_then(Value::as_int)
     }
 
     pub fn set_timer(&mut self, timer: f64) {
@@ -272,8 +291,7 @@ impl ScriptInfo {
     }
 
     pub fn get_timer(&self) -> Option<f64> {
-        self.get_property(Key::Timer)
-            .and_then(Value::as_float)
+        self.get_property(Key::Timer).and_then(Value::as_float)
     }
 
     pub fn set_wrap_style(&mut self, wrap_style: i64) {
@@ -281,8 +299,7 @@ impl ScriptInfo {
     }
 
     pub fn get_wrap_style(&self) -> Option<i64> {
-        self.get_property(Key::WrapStyle)
-            .and_then(Value::as_int)
+        self.get_property(Key::WrapStyle).and_then(Value::as_int)
     }
 
     pub fn set_scaled_border_and_shadow(&mut self, scaled_border_and_shadow: bool) {
@@ -298,6 +315,25 @@ impl ScriptInfo {
     }
 }
 
+impl ScriptInfo {
+    pub(crate) fn write_property<W: std::fmt::Write>(
+        f: &mut W,
+        key: &str,
+        value: &Value,
+    ) -> std::fmt::Result {
+        if key == Key::ScaledBorderAndShadow.as_ref() {
+            let value = if value.as_bool().unwrap_or_default() {
+                "yes"
+            } else {
+                "no"
+            };
+            write!(f, "{}: {}", key, value)
+        } else {
+            write!(f, "{}: {}", key, value)
+        }
+    }
+}
+
 impl Display for ScriptInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (key, value) in &self.properties {
@@ -307,15 +343,9 @@ impl Display for ScriptInfo {
                         writeln!(f, "; {}", ele)?;
                     }
                 }
-            } else if key == Key::ScaledBorderAndShadow.as_ref() {
-                let value = if value.as_bool().unwrap_or_default() {
-                    "yes"
-                } else {
-                    "no"
-                };
-                writeln!(f, "{}: {}", key, value)?;
             } else {
-                writeln!(f, "{}: {}", key, value)?;
+                ScriptInfo::write_property(f, key, value)?;
+                writeln!(f)?;
             }
         }
         Ok(())
@@ -413,4 +443,10 @@ pub enum Key {
     WrapStyle,
     #[strum(serialize = "ScaledBorderAndShadow")]
     ScaledBorderAndShadow,
+    #[strum(serialize = "LayoutResX")]
+    LayoutResX,
+    #[strum(serialize = "LayoutResY")]
+    LayoutResY,
+    #[strum(serialize = "YCbCr Matrix")]
+    YCbCrMatrix,
 }
@@ -0,0 +1,372 @@
+use std::fmt::Write;
+
+use crate::{
+    color::{Color, ColorFormat},
+    error::Error,
+    events::{
+        effect::Effect,
+        tag::TagKind,
+        text::{Segment, Text},
+        Event, EventFormat, EventType,
+    },
+    file::File,
+    parser::Parser,
+    script_info::ScriptType,
+    styles::Style,
+    timestamp::Timestamp,
+    value::Value,
+};
+
+const DEFAULT_STYLE: &str =
+    "Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1";
+
+impl File {
+    /// Reads a SubRip file. Every cue becomes a `Dialogue` event using the `Default` style, and
+    /// the basic HTML formatting of SubRip is turned into override tags.
+    pub fn from_srt(srt: impl AsRef<str>) -> crate::Result<Self> {
+        let mut file = new_file()?;
+        let srt = srt.as_ref();
+        let srt = srt
+            .strip_prefix('\u{FEFF}')
+            .unwrap_or(srt)
+            .replace("\r\n", "\n");
+        for block in srt.split("\n\n") {
+            let mut lines = block.lines().skip_while(|line| line.trim().is_empty());
+            let Some(mut line) = lines.next() else {
+                continue;
+            };
+            if !line.contains("-->") {
+                line = lines.next().unwrap_or_default();
+            }
+            let (start, end) = parse_timing(line)?;
+            let text = lines.collect::<Vec<_>>().join("\\N");
+            let text = Text::parse(&html_to_tags(&text))?;
+            let event = new_event(&file, start, end, "Default", "", text);
+            file.events.push(event);
+        }
+        Ok(file)
+    }
+
+    /// Renders the dialogue events as SubRip cues ordered by start time. Comments and other
+    /// event types are skipped, overlapping events stay separate cues.
+    pub fn to_srt(&self) -> String {
+        let mut events: Vec<_> = self
+            .events
+            .iter()
+            .filter(|event| event.event_type() == EventType::Dialogue)
+            .collect();
+        events.sort_by_key(|event| time(event, EventFormat::Start));
+        let mut srt = String::new();
+        for (i, event) in events.into_iter().enumerate() {
+            let text = event
+                .get(EventFormat::Text)
+                .map(|text| match text {
+                    Value::Text(text) => tags_to_html(text, Markup::SubRip),
+                    text => text.to_string(),
+                })
+                .unwrap_or_default();
+            let _ = write!(
+                srt,
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                format_time(time(event, EventFormat::Start), ','),
+                format_time(time(event, EventFormat::End), ','),
+                text
+            );
+        }
+        srt
+    }
+}
+
+/// A file with a `Default` style to hold events imported from other subtitle formats.
+pub(crate) fn new_file() -> crate::Result<File> {
+    let mut file = File::new();
+    file.script.set_script_type(ScriptType::V4Plus);
+    let mut style = Style::new(&file.styles);
+    for (format, value) in file.styles.order().iter().zip(DEFAULT_STYLE.split(',')) {
+        style.set(*format, format.parse_value(value)?);
+    }
+    file.styles.add(style)?;
+    Ok(file)
+}
+
+pub(crate) fn new_event(
+    file: &File,
+    start: Timestamp,
+    end: Timestamp,
+    style: &str,
+    name: &str,
+    text: Text,
+) -> Event {
+    let mut event = Event::new(EventType::Dialogue, &file.events);
+    event.set(EventFormat::Layer, 0);
+    event.set(EventFormat::Start, start);
+    event.set(EventFormat::End, end);
+    event.set(EventFormat::Style, style);
+    event.set(EventFormat::Name, name);
+    event.set(EventFormat::MarginL, 0);
+    event.set(EventFormat::MarginR, 0);
+    event.set(EventFormat::MarginV, 0);
+    event.set(EventFormat::Effect, Effect::None);
+    event.set(EventFormat::Text, text);
+    event
+}
+
+pub(crate) fn time(event: &Event, format: EventFormat) -> Timestamp {
+    event
+        .get(format)
+        .and_then(Value::as_timestamp)
+        .unwrap_or_default()
+}
+
+pub(crate) fn format_time(time: Timestamp, separator: char) -> String {
+    let millis = time.millis().max(0);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis % 3_600_000 / 60_000,
+        millis % 60_000 / 1_000,
+        separator,
+        millis % 1_000
+    )
+}
+
+fn parse_timing(line: &str) -> crate::Result<(Timestamp, Timestamp)> {
+    let invalid = || Error::parse_error::<File>(format!("invalid srt timing {}", line));
+    let (start, end) = line.split_once("-->").ok_or_else(invalid)?;
+    let end = end.split_whitespace().next().ok_or_else(invalid)?;
+    let start = parse_time(start.trim()).ok_or_else(invalid)?;
+    let end = parse_time(end).ok_or_else(invalid)?;
+    Ok((start, end))
+}
+
+pub(crate) fn parse_time(src: &str) -> Option<Timestamp> {
+    let (hms, millis) = src.split_once([',', '.']).unwrap_or((src, "0"));
+    let mut parts = hms.split(':').rev();
+    let s: i64 = parts.next()?.trim().parse().ok()?;
+    let m: i64 = parts.next().map_or(Some(0), |m| m.trim().parse().ok())?;
+    let h: i64 = parts.next().map_or(Some(0), |h| h.trim().parse().ok())?;
+    let digits = millis.trim();
+    let scale = 10i64.pow(3u32.saturating_sub(digits.len() as u32));
+    let millis = digits.get(..digits.len().min(3))?.parse::<i64>().ok()? * scale;
+    Some(Timestamp::from_millis(
+        h * 3_600_000 + m * 60_000 + s * 1_000 + millis,
+    ))
+}
+
+/// Converts SubRip markup into ASS override tags, unsupported markup is kept as text.
+fn html_to_tags(src: &str) -> String {
+    let mut out = String::new();
+    let mut rest = src;
+    while let Some(open) = rest.find('<') {
+        let Some(close) = rest[open..].find('>').map(|pos| open + pos) else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        let tag = &rest[open + 1..close];
+        match html_tag_to_override(tag) {
+            Some(tags) => {
+                out.push('{');
+                out.push_str(&tags);
+                out.push('}');
+            }
+            None => out.push_str(&rest[open..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub(crate) fn html_tag_to_override(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_ascii_lowercase();
+    let (closing, name) = match tag.strip_prefix('/') {
+        Some(name) => (true, name.trim()),
+        None => (false, tag.as_str()),
+    };
+    let flag = if closing { 0 } else { 1 };
+    match name {
+        "b" | "i" | "u" | "s" => Some(format!("\\{}{}", name, flag)),
+        "font" if closing => Some("\\c".to_string()),
+        _ if name.starts_with("font") && !closing => {
+            let color = name.split_once("color")?.1;
+            let color = color
+                .trim_start()
+                .strip_prefix('=')?
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'');
+            let color = Color::from_css(color).ok()?.with_format(ColorFormat::Tag);
+            Some(format!("\\c{}", color))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Markup {
+    SubRip,
+    WebVtt,
+}
+
+/// Converts override tags into SubRip or WebVTT markup, tags without equivalent are dropped.
+pub(crate) fn tags_to_html(text: &Text, markup: Markup) -> String {
+    let mut html = Html::default();
+    let mut drawing = false;
+    for segment in text.iter() {
+        if let Segment::Plain(plain) = segment {
+            if !drawing {
+                let plain = match markup {
+                    Markup::SubRip => plain.to_string(),
+                    Markup::WebVtt => plain
+                        .replace('&', "&amp;")
+                        .replace('<', "&lt;")
+                        .replace('>', "&gt;"),
+                };
+                html.out.push_str(
+                    &plain
+                        .replace("\\N", "\n")
+                        .replace("\\n", "\n")
+                        .replace("\\h", "\u{00A0}"),
+                );
+            }
+        }
+        for tag in segment.as_override().into_iter().flatten() {
+            let Some(tag) = tag.as_tag() else {
+                continue;
+            };
+            match tag.kind() {
+                TagKind::Bold(weight) => html.set("b", None, weight.is_some_and(|w| w != 0)),
+                TagKind::Italic(on) => html.set("i", None, on.unwrap_or_default()),
+                TagKind::Underline(on) => html.set("u", None, on.unwrap_or_default()),
+                TagKind::Color { index: 1, color } if markup == Markup::SubRip => {
+                    let color = color.map(|color| color.to_hex());
+                    html.set("font", color.clone(), color.is_some());
+                }
+                TagKind::Reset(style) => {
+                    html.close_all();
+                    if let (Markup::WebVtt, Some(style)) = (markup, style) {
+                        html.open_tag(("c", Some(crate::webvtt::class_name(style))));
+                    }
+                }
+                TagKind::Drawing(scale) => drawing = *scale > 0,
+                _ => {}
+            }
+        }
+    }
+    html.close_all();
+    html.out
+}
+
+#[derive(Default)]
+struct Html {
+    out: String,
+    /// Open tags with their attribute, the color of `font` or the class of `c`, innermost last.
+    open: Vec<(&'static str, Option<String>)>,
+}
+
+impl Html {
+    fn set(&mut self, name: &'static str, color: Option<String>, on: bool) {
+        let position = self.open.iter().position(|(open, _)| *open == name);
+        if let Some(position) = position {
+            let reopen = self.open.split_off(position);
+            for (name, _) in reopen.iter().rev() {
+                let _ = write!(self.out, "</{}>", name);
+            }
+            for tag in reopen.into_iter().skip(1) {
+                self.open_tag(tag);
+            }
+        }
+        if on {
+            self.open_tag((name, color));
+        }
+    }
+
+    fn open_tag(&mut self, (name, attribute): (&'static str, Option<String>)) {
+        let _ = match (name, &attribute) {
+            ("font", Some(color)) => write!(self.out, "<font color=\"{}\">", color),
+            ("c", Some(class)) => write!(self.out, "<c.{}>", class),
+            _ => write!(self.out, "<{}>", name),
+        };
+        self.open.push((name, attribute));
+    }
+
+    fn close_all(&mut self) {
+        while let Some((name, _)) = self.open.pop() {
+            let _ = write!(self.out, "</{}>", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::styles::StyleFormat;
+
+    const SRT: &str = "1\r\n00:00:01,000 --> 00:00:04,500\r\n<i>Hello</i>\r\n<font color=\"#FF8000\">World</font>\r\n\r\n2\r\n00:00:03,000 --> 00:00:05,000 X1:10 X2:20\r\nOverlap <b>bold</b>\r\n";
+
+    #[test]
+    fn test_from_srt() -> crate::Result<()> {
+        let file = File::from_srt(SRT)?;
+        assert!(file.styles.get("Default").is_some());
+        assert_eq!(file.events.len(), 2);
+        let event = &file.events[0];
+        assert_eq!(
+            event.get(EventFormat::Start),
+            Some(&Timestamp::from_centis(100).into())
+        );
+        assert_eq!(
+            event.get(EventFormat::End),
+            Some(&Timestamp::from_centis(450).into())
+        );
+        assert_eq!(
+            event.get(EventFormat::Text).unwrap().to_string(),
+            "{\\i1}Hello{\\i0}\\N{\\c&H0080FF&}World{\\c}"
+        );
+        assert_eq!(
+            file.events[1].get(EventFormat::Text).unwrap().to_string(),
+            "Overlap {\\b1}bold{\\b0}"
+        );
+        assert!(
+            file.styles
+                .get("Default")
+                .unwrap()
+                .get(StyleFormat::Fontsize)
+                == Some(&20.into())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_srt_literal_angle_bracket() -> crate::Result<()> {
+        let file = File::from_srt("1\n00:00:01,000 --> 00:00:02,000\na < b\n")?;
+        assert_eq!(
+            file.events[0].get(EventFormat::Text).unwrap().to_string(),
+            "a < b"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_srt() -> crate::Result<()> {
+        let mut file = File::from_srt(SRT)?;
+        let mut comment = file.events[0].clone();
+        comment.set_event_type(EventType::Comment);
+        file.events.push(comment);
+        assert_eq!(
+            file.to_srt(),
+            "1\n00:00:01,000 --> 00:00:04,500\n<i>Hello</i>\n<font color=\"#FF8000\">World</font>\n\n\
+             2\n00:00:03,000 --> 00:00:05,000\nOverlap <b>bold</b>\n\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tags_to_html_nesting() -> crate::Result<()> {
+        let text = Text::parse("{\\b1\\i1}a{\\b0}b{\\r}c{\\u1\\p1}m 0 0{\\p0}d")?;
+        assert_eq!(
+            tags_to_html(&text, Markup::SubRip),
+            "<b><i>a</i></b><i>b</i>c<u>d</u>"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use std::io::{BufRead, Write};
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::{
+    error::Error,
+    events::{Event, Events},
+    file::{File, LineEnding, ReadOptions, WriteOptions},
+    parser::{Context, SsaParser},
+};
+
+/// Reads a script with constant memory for its events. Everything before the events is parsed
+/// up front into [`EventReader::header`], the events are then yielded one at a time.
+///
+/// Without [`ReadOptions::encoding`] the input is read as UTF-8 unless it starts with a byte
+/// order mark. UTF-16 cannot be streamed.
+pub struct EventReader<R> {
+    reader: R,
+    encoding: &'static Encoding,
+    header: File,
+    header_text: String,
+    parser: SsaParser,
+    buf: Vec<u8>,
+    /// The section header that ended the events, read before the iteration stopped.
+    next_section: Option<String>,
+    done: bool,
+}
+
+impl<R: BufRead> EventReader<R> {
+    pub fn new(reader: R) -> crate::Result<Self> {
+        Self::with_options(reader, &ReadOptions::default())
+    }
+
+    pub fn with_options(mut reader: R, options: &ReadOptions) -> crate::Result<Self> {
+        let start = reader.fill_buf()?;
+        let (bom_encoding, bom_len) = Encoding::for_bom(start).unzip();
+        if bom_encoding.is_some_and(|encoding| encoding != UTF_8) {
+            return Err(Error::parse_error::<EventReader<R>>(
+                "UTF-16 cannot be read as a stream",
+            ));
+        }
+        let bom = bom_encoding.is_some() && options.encoding.is_none_or(|e| e == UTF_8);
+        if bom {
+            reader.consume(bom_len.unwrap_or_default());
+        }
+        let mut stream = Self {
+            reader,
+            encoding: options.encoding.unwrap_or(UTF_8),
+            header: File::default(),
+            header_text: String::new(),
+            parser: SsaParser::default(),
+            buf: vec![],
+            next_section: None,
+            done: false,
+        };
+        let mut in_events = false;
+        while let Some(line) = stream.read_line()? {
+            stream.header_text.push_str(&line);
+            let trimmed = line.trim();
+            if in_events && !trimmed.is_empty() {
+                break;
+            }
+            in_events = trimmed.eq_ignore_ascii_case("[events]");
+        }
+        let (mut header, _) = File::parse(&stream.header_text, SsaParser::default())?;
+        header.encoding = stream.encoding;
+        header.layout.bom = bom;
+        stream.parser.events = Events::new(header.events.order().clone());
+        stream.parser.context = Context::ParseEvents;
+        stream.parser.line = stream.header_text.lines().count();
+        stream.header = header;
+        Ok(stream)
+    }
+
+    /// The script info, styles and every other section read before the events.
+    pub fn header(&self) -> &File {
+        &self.header
+    }
+
+    /// Reads the rest of the input and returns the whole script without its events, including
+    /// the sections that follow them.
+    pub fn finish(mut self) -> crate::Result<File> {
+        while self.next().is_some() {}
+        let mut text = std::mem::take(&mut self.header_text);
+        for raw in self.parser.pending.drain(..) {
+            text.push_str(&raw.text);
+            text.push_str(raw.eol);
+        }
+        text.extend(self.next_section.take());
+        while let Some(line) = self.read_line()? {
+            text.push_str(&line);
+        }
+        let (mut file, _) = File::parse(&text, SsaParser::default())?;
+        file.encoding = self.header.encoding;
+        file.layout.bom = self.header.layout.bom;
+        Ok(file)
+    }
+
+    /// The next line including its terminator.
+    fn read_line(&mut self) -> crate::Result<Option<String>> {
+        self.buf.clear();
+        if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+            return Ok(None);
+        }
+        let line = self
+            .encoding
+            .decode_without_bom_handling_and_without_replacement(&self.buf)
+            .ok_or_else(|| crate::encoding::decode_error(self.encoding))?;
+        Ok(Some(line.into_owned()))
+    }
+
+    fn next_event(&mut self) -> crate::Result<Option<Event>> {
+        while let Some(line) = self.read_line()? {
+            let (src, eol) = split_eol(&line);
+            let trimmed = src.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                self.next_section = Some(line);
+                return Ok(None);
+            }
+            self.parser.advance(src, eol);
+            self.parser.parse_events(src)?;
+            if let Some(event) = self.parser.events.pop() {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = crate::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let event = self.next_event().transpose();
+        if !matches!(event, Some(Ok(_))) {
+            self.done = true;
+        }
+        event
+    }
+}
+
+/// Writes a script whose events are passed one at a time. The sections before the events are
+/// written by [`EventWriter::new`] and the ones after them by [`EventWriter::finish`].
+pub struct EventWriter<W: Write> {
+    out: W,
+    options: WriteOptions,
+    encoding: &'static Encoding,
+    eol: &'static str,
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(mut out: W, header: &File, options: &WriteOptions) -> crate::Result<Self> {
+        let encoding = options.encoding.unwrap_or(header.encoding);
+        let line_ending = match (options.line_ending, options.lossless) {
+            (Some(line_ending), _) => line_ending,
+            (None, true) => header.layout.line_ending,
+            (None, false) => LineEnding::Lf,
+        };
+        let text = header.to_string_with(options)?;
+        let (head, _) = split_at_events(&text, header.events.order(), line_ending.as_str());
+        out.write_all(&crate::encoding::encode_start(&head, encoding)?)?;
+        Ok(Self {
+            out,
+            options: options.clone(),
+            encoding,
+            eol: line_ending.as_str(),
+        })
+    }
+
+    pub fn write(&mut self, event: &Event) -> crate::Result<()> {
+        let mut line = String::new();
+        match event.raw.as_deref().filter(|_| self.options.lossless) {
+            Some(raw) => {
+                for leading in &raw.leading {
+                    line.push_str(&leading.text);
+                    line.push_str(self.eol_for(leading.eol));
+                }
+                event.write_lossless(&mut line)?;
+                line.push_str(self.eol_for(raw.eol));
+            }
+            None => {
+                event.write_lossless(&mut line)?;
+                line.push_str(self.eol);
+            }
+        }
+        self.out
+            .write_all(&crate::encoding::encode(&line, self.encoding)?)?;
+        Ok(())
+    }
+
+    /// Writes the sections of `file` that follow its events and returns the output.
+    pub fn finish(mut self, file: &File) -> crate::Result<W> {
+        let text = file.to_string_with(&self.options)?;
+        let (_, tail) = split_at_events(&text, file.events.order(), self.eol);
+        let tail = tail.strip_prefix('\u{FEFF}').unwrap_or(tail);
+        self.out
+            .write_all(&crate::encoding::encode(tail, self.encoding)?)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn eol_for(&self, eol: &'static str) -> &'static str {
+        if self.options.line_ending.is_some() && !eol.is_empty() {
+            self.eol
+        } else {
+            eol
+        }
+    }
+}
+
+fn split_eol(line: &str) -> (&str, &'static str) {
+    if let Some(src) = line.strip_suffix("\r\n") {
+        (src, "\r\n")
+    } else if let Some(src) = line.strip_suffix('\n') {
+        (src, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// Splits a rendered script after the `Format:` line of its events. Scripts without events
+/// section get one appended to the head.
+fn split_at_events<'a>(
+    text: &'a str,
+    order: &[crate::events::EventFormat],
+    eol: &str,
+) -> (std::borrow::Cow<'a, str>, &'a str) {
+    let mut offset = 0;
+    let mut in_events = false;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        let trimmed = line.trim();
+        if in_events && !trimmed.is_empty() {
+            return (text[..offset].into(), &text[offset..]);
+        }
+        in_events = trimmed.eq_ignore_ascii_case("[events]");
+    }
+    let order = order.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+    let head = format!("{}[Events]{}Format: {}{}", text, eol, order.join(", "), eol);
+    (head.into(), "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::Timestamp;
+    use encoding_rs::UTF_16LE;
+    use std::io::Cursor;
+
+    const SOURCE: &str = "\u{FEFF}[Script Info]\r\n\
+Title: Stream\r\n\
+\r\n\
+[V4+ Styles]\r\n\
+Format: Name, Fontname, Fontsize\r\n\
+Style: Default,Arial,60\r\n\
+\r\n\
+[Events]\r\n\
+Format: Layer, Start, End, Style, Text\r\n\
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,one\r\n\
+; between\r\n\
+Dialogue: 0,0:00:03.00,0:00:04.00,Default,two\r\n\
+\r\n\
+[Fonts]\r\n\
+fontname: a_0.ttf\r\n\
+47&O\r\n";
+
+    #[test]
+    fn test_stream_round_trip() -> crate::Result<()> {
+        let mut reader = EventReader::new(Cursor::new(SOURCE.as_bytes()))?;
+        assert_eq!(reader.header().script.get_title(), Some("Stream"));
+        assert!(reader.header().styles.get("Default").is_some());
+        let mut events = vec![];
+        for event in reader.by_ref() {
+            let mut event = event?;
+            let mut shifted = Events::new(vec![]);
+            shifted.push(event.clone());
+            shifted.shift(Timestamp::from_centis(50));
+            event = shifted.pop().unwrap();
+            events.push(event);
+        }
+        assert_eq!(events.len(), 2);
+        let rest = reader.finish()?;
+        assert_eq!(rest.fonts[0].data, b"Man");
+
+        let mut writer = EventWriter::new(vec![], &rest, &WriteOptions::lossless())?;
+        for event in &events {
+            writer.write(event)?;
+        }
+        let out = writer.finish(&rest)?;
+
+        let mut file = File::from_str(SOURCE)?;
+        file.events.shift(Timestamp::from_centis(50));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            file.to_string_with(&WriteOptions::lossless())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_canonical() -> crate::Result<()> {
+        let reader = EventReader::new(Cursor::new(SOURCE.as_bytes()))?;
+        let header = reader.header().clone();
+        let mut writer = EventWriter::new(vec![], &header, &WriteOptions::default())?;
+        for event in reader {
+            writer.write(&event?)?;
+        }
+        let out = String::from_utf8(writer.finish(&header)?).unwrap();
+        let file = File::from_str(SOURCE)?;
+        assert_eq!(
+            out,
+            file.to_string()?
+                .replace("[Fonts]\nfontname: a_0.ttf\n47&O\n\n", "")
+        );
+
+        let options = WriteOptions {
+            encoding: Some(UTF_16LE),
+            ..Default::default()
+        };
+        let mut writer = EventWriter::new(vec![], &header, &options)?;
+        for event in file.events.iter() {
+            writer.write(event)?;
+        }
+        let reread = File::from_bytes(&writer.finish(&header)?)?;
+        assert_eq!(reread.encoding, UTF_16LE);
+        assert_eq!(reread.events, file.events);
+        let error = EventReader::new(Cursor::new(b"[Events]\nFormat: Start\nDialogue: x\n"))?
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(error.line(), Some(3));
+        Ok(())
+    }
+}
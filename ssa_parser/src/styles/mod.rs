@@ -3,10 +3,16 @@ use std::fmt::Display;
 use itertools::Itertools;
 
 use crate::{
-    parser::{parse_f64, parse_i64},
+    alignment::Alignment,
+    color::Color,
+    layout::RawLine,
+    parser::{parse_f64, parse_i64, Parser},
     value::Value,
+    version::Version,
 };
 
+pub mod resolved;
+
 #[derive(
     Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display, strum::EnumString, strum::VariantNames,
 )]
@@ -45,7 +51,7 @@ pub enum StyleFormat {
 }
 
 impl StyleFormat {
-    fn default_value(&self) -> Value {
+    pub(crate) fn default_value(&self) -> Value {
         match self {
             StyleFormat::Name | StyleFormat::Fontname => "".to_owned().into(),
             StyleFormat::Fontsize => 0.into(),
@@ -53,7 +59,7 @@ impl StyleFormat {
             | StyleFormat::SecondaryColour
             | StyleFormat::TertiaryColour
             | StyleFormat::OutlineColour
-            | StyleFormat::BackColour => "&H00000000".to_owned().into(),
+            | StyleFormat::BackColour => Color::default().into(),
             StyleFormat::Bold
             | StyleFormat::Italic
             | StyleFormat::Underline
@@ -79,7 +85,7 @@ impl StyleFormat {
             | StyleFormat::SecondaryColour
             | StyleFormat::TertiaryColour
             | StyleFormat::OutlineColour
-            | StyleFormat::BackColour => src.into(),
+            | StyleFormat::BackColour => Color::parse(src)?.into(),
             StyleFormat::Bold
             | StyleFormat::Italic
             | StyleFormat::Underline
@@ -101,20 +107,23 @@ impl StyleFormat {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Style(Vec<(StyleFormat, Option<Value>)>);
+#[derive(Debug, Clone)]
+pub struct Style {
+    values: Vec<(StyleFormat, Option<Value>)>,
+    pub(crate) raw: Option<Box<RawLine<StyleFormat>>>,
+}
 
 impl Style {
     pub fn new(styles: &V4Styles) -> Self {
-        let mut style = vec![];
+        let mut values = vec![];
         for format in styles.order() {
-            style.push((format.clone(), None));
+            values.push((*format, None));
         }
-        Self(style)
+        Self { values, raw: None }
     }
 
     pub fn set(&mut self, format: StyleFormat, value: impl Into<Value>) {
-        for (f, v) in self.0.iter_mut() {
+        for (f, v) in self.values.iter_mut() {
             if f == &format {
                 *v = Some(value.into());
                 return;
@@ -122,8 +131,15 @@ impl Style {
         }
     }
 
+    /// The fields in format order, `None` for fields that are not set.
+    pub fn iter(&self) -> impl Iterator<Item = (StyleFormat, Option<&Value>)> {
+        self.values
+            .iter()
+            .map(|(format, value)| (*format, value.as_ref()))
+    }
+
     pub fn get(&self, format: StyleFormat) -> Option<&Value> {
-        for (f, v) in self.0.iter() {
+        for (f, v) in self.values.iter() {
             if f == &format {
                 return v.as_ref();
             }
@@ -132,7 +148,7 @@ impl Style {
     }
 
     pub fn get_mut(&mut self, format: StyleFormat) -> Option<&mut Value> {
-        for (f, v) in self.0.iter_mut() {
+        for (f, v) in self.values.iter_mut() {
             if f == &format {
                 return v.as_mut();
             }
@@ -141,19 +157,57 @@ impl Style {
     }
 
     pub fn remove(&mut self, format: StyleFormat) {
-        for (f, v) in self.0.iter_mut() {
+        for (f, v) in self.values.iter_mut() {
             if f == &format {
                 *v = None;
                 return;
             }
         }
     }
+
+    /// The `Alignment` field read with the numbering of `version`, `None` when it is missing or
+    /// out of range.
+    pub fn alignment(&self, version: Version) -> Option<Alignment> {
+        self.get(StyleFormat::Alignment)
+            .and_then(Value::as_int)
+            .and_then(|value| Alignment::from_version(value, version))
+    }
+
+    pub fn set_alignment(&mut self, alignment: Alignment, version: Version) {
+        self.set(StyleFormat::Alignment, alignment.to_version(version));
+    }
 }
 
+impl Style {
+    pub(crate) fn write_lossless<W: std::fmt::Write>(&self, out: &mut W) -> std::fmt::Result {
+        match &self.raw {
+            Some(raw) => raw.write(
+                out,
+                "Style",
+                &self.values,
+                |format, src| format.parse_value(src).ok(),
+                |format, value| match value {
+                    Some(value) => value.to_string(),
+                    None => format.default_value().to_string(),
+                },
+            ),
+            None => write!(out, "Style: {}", self),
+        }
+    }
+}
+
+impl PartialEq for Style {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl Eq for Style {}
+
 impl Display for Style {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut values = Vec::with_capacity(self.0.len());
-        for (style, value) in self.0.iter() {
+        let mut values = Vec::with_capacity(self.values.len());
+        for (style, value) in self.values.iter() {
             match value {
                 Some(value) => {
                     values.push(value.to_string());
@@ -190,6 +244,28 @@ impl V4Styles {
         &self.order
     }
 
+    /// Rearranges every style to `order`, dropping the fields it leaves out and filling the ones
+    /// it adds with their default.
+    pub fn set_order(&mut self, order: Vec<StyleFormat>) -> crate::Result<()> {
+        if !order.contains(&StyleFormat::Name) {
+            return Err(crate::Error::V4StyleNameNotFound);
+        }
+        for (_, style) in self.styles.iter_mut() {
+            style.values = order
+                .iter()
+                .map(|format| {
+                    let value = style.get(*format).cloned();
+                    (
+                        *format,
+                        Some(value.unwrap_or_else(|| format.default_value())),
+                    )
+                })
+                .collect();
+        }
+        self.order = order;
+        Ok(())
+    }
+
     pub fn add(&mut self, style: Style) -> crate::Result<()> {
         let name = style
             .get(StyleFormat::Name)
@@ -0,0 +1,290 @@
+use crate::{
+    alignment::Alignment,
+    color::Color,
+    parser::Parser,
+    styles::{Style, StyleFormat, V4Styles},
+    value::Value,
+    version::Version,
+};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum BorderStyle {
+    /// An outline and drop shadow around the text.
+    #[default]
+    Outline = 1,
+    /// A box behind the text, drawn with the outline colour.
+    OpaqueBox = 3,
+    /// A box behind the whole event, drawn with the back colour. A libass extension.
+    BackgroundBox = 4,
+}
+
+impl BorderStyle {
+    pub fn from_i64(value: i64) -> Option<Self> {
+        match value {
+            1 => Some(BorderStyle::Outline),
+            3 => Some(BorderStyle::OpaqueBox),
+            4 => Some(BorderStyle::BackgroundBox),
+            _ => None,
+        }
+    }
+}
+
+/// A style with every field typed, missing or malformed fields fall back to the defaults of
+/// [`StyleFormat`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedStyle {
+    pub name: String,
+    pub fontname: String,
+    pub fontsize: f64,
+    pub primary_colour: Color,
+    pub secondary_colour: Color,
+    /// `OutlineColour` in V4+ styles and `TertiaryColour` in V4 styles.
+    pub outline_colour: Color,
+    pub back_colour: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strike_out: bool,
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub spacing: f64,
+    pub angle: f64,
+    pub border_style: BorderStyle,
+    pub outline: f64,
+    pub shadow: f64,
+    pub alignment: Alignment,
+    pub margin_l: i64,
+    pub margin_r: i64,
+    pub margin_v: i64,
+    pub encoding: i64,
+}
+
+impl ResolvedStyle {
+    fn resolve(get: impl Fn(StyleFormat) -> Option<Value>) -> Self {
+        let value = |format: StyleFormat| get(format).unwrap_or_else(|| format.default_value());
+        let string = |format| match value(format) {
+            Value::Str(s) => s,
+            value => value.to_string(),
+        };
+        let float = |format| to_f64(&value(format)).unwrap_or_default();
+        let int = |format| to_i64(&value(format)).unwrap_or_default();
+        let flag = |format| int(format) != 0;
+        let color = |format| {
+            to_color(&value(format))
+                .or_else(|| to_color(&StyleFormat::default_value(&format)))
+                .unwrap_or_default()
+        };
+        // V4 styles have a tertiary instead of an outline colour and number alignments the
+        // legacy way
+        let version = if get(StyleFormat::OutlineColour).is_none()
+            && get(StyleFormat::TertiaryColour).is_some()
+        {
+            Version::V4
+        } else {
+            Version::V4Plus
+        };
+        let outline_colour = match version {
+            Version::V4 => color(StyleFormat::TertiaryColour),
+            Version::V4Plus => color(StyleFormat::OutlineColour),
+        };
+        Self {
+            name: string(StyleFormat::Name),
+            fontname: string(StyleFormat::Fontname),
+            fontsize: float(StyleFormat::Fontsize),
+            primary_colour: color(StyleFormat::PrimaryColour),
+            secondary_colour: color(StyleFormat::SecondaryColour),
+            outline_colour,
+            back_colour: color(StyleFormat::BackColour),
+            bold: flag(StyleFormat::Bold),
+            italic: flag(StyleFormat::Italic),
+            underline: flag(StyleFormat::Underline),
+            strike_out: flag(StyleFormat::StrikeOut),
+            scale_x: float(StyleFormat::ScaleX),
+            scale_y: float(StyleFormat::ScaleY),
+            spacing: float(StyleFormat::Spacing),
+            angle: float(StyleFormat::Angle),
+            border_style: BorderStyle::from_i64(int(StyleFormat::BorderStyle)).unwrap_or_default(),
+            outline: float(StyleFormat::Outline),
+            shadow: float(StyleFormat::Shadow),
+            alignment: Alignment::from_version(int(StyleFormat::Alignment), version)
+                .unwrap_or_default(),
+            margin_l: int(StyleFormat::MarginL),
+            margin_r: int(StyleFormat::MarginR),
+            margin_v: int(StyleFormat::MarginV),
+            encoding: int(StyleFormat::Encoding),
+        }
+    }
+
+    /// Converts back into a [`Style`] with the field order of `styles`.
+    pub fn to_style(&self, styles: &V4Styles) -> Style {
+        let mut style = Style::new(styles);
+        let version = if styles.order().contains(&StyleFormat::OutlineColour)
+            || !styles.order().contains(&StyleFormat::TertiaryColour)
+        {
+            Version::V4Plus
+        } else {
+            Version::V4
+        };
+        for format in styles.order() {
+            let flag = |on: bool| Value::Int(if on { -1 } else { 0 });
+            let value = match format {
+                StyleFormat::Name => self.name.clone().into(),
+                StyleFormat::Fontname => self.fontname.clone().into(),
+                StyleFormat::Fontsize if self.fontsize.fract() == 0.0 => {
+                    Value::Int(self.fontsize as i64)
+                }
+                StyleFormat::Fontsize => self.fontsize.into(),
+                StyleFormat::PrimaryColour => self.primary_colour.into(),
+                StyleFormat::SecondaryColour => self.secondary_colour.into(),
+                StyleFormat::TertiaryColour | StyleFormat::OutlineColour => {
+                    self.outline_colour.into()
+                }
+                StyleFormat::BackColour => self.back_colour.into(),
+                StyleFormat::Bold => flag(self.bold),
+                StyleFormat::Italic => flag(self.italic),
+                StyleFormat::Underline => flag(self.underline),
+                StyleFormat::StrikeOut => flag(self.strike_out),
+                StyleFormat::ScaleX => self.scale_x.into(),
+                StyleFormat::ScaleY => self.scale_y.into(),
+                StyleFormat::Spacing => self.spacing.into(),
+                StyleFormat::Angle => self.angle.into(),
+                StyleFormat::BorderStyle => Value::Int(self.border_style as i64),
+                StyleFormat::Outline => self.outline.into(),
+                StyleFormat::Shadow => self.shadow.into(),
+                StyleFormat::Alignment => Value::Int(self.alignment.to_version(version)),
+                StyleFormat::MarginL => Value::Int(self.margin_l),
+                StyleFormat::MarginR => Value::Int(self.margin_r),
+                StyleFormat::MarginV => Value::Int(self.margin_v),
+                StyleFormat::AlphaLevel => format.default_value(),
+                StyleFormat::Encoding => Value::Int(self.encoding),
+            };
+            style.set(*format, value);
+        }
+        style
+    }
+}
+
+impl Default for ResolvedStyle {
+    fn default() -> Self {
+        Self::resolve(|_| None)
+    }
+}
+
+impl From<&Style> for ResolvedStyle {
+    fn from(style: &Style) -> Self {
+        Self::resolve(|format| style.get(format).cloned())
+    }
+}
+
+impl Style {
+    pub fn resolve(&self) -> ResolvedStyle {
+        ResolvedStyle::from(self)
+    }
+}
+
+fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        Value::Boolean(b) => Some(*b as i64 as f64),
+        Value::Str(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(i) => Some(*i),
+        Value::Float(f) => Some(f.round() as i64),
+        Value::Boolean(b) => Some(*b as i64),
+        Value::Str(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn to_color(value: &Value) -> Option<Color> {
+    match value {
+        Value::Color(c) => Some(*c),
+        Value::Str(s) => Color::parse(s).ok(),
+        Value::Int(i) => Some(Color::from_abgr(*i as u32)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(styles: &V4Styles, src: &str) -> crate::Result<Style> {
+        let mut style = Style::new(styles);
+        for (format, value) in styles.order().iter().zip(src.split(',')) {
+            style.set(*format, format.parse_value(value)?);
+        }
+        Ok(style)
+    }
+
+    #[test]
+    fn test_resolve() -> crate::Result<()> {
+        let styles = V4Styles::default();
+        let style = style(
+            &styles,
+            "Sign,Verdana,36,&H00FFFFFF,&H000000FF,&H80102030,&H00000000,-1,0,0,0,100,120,0,0,3,2.5,0,8,10,20,30,1",
+        )?;
+        let resolved = style.resolve();
+        assert_eq!(resolved.name, "Sign");
+        assert_eq!(resolved.fontsize, 36.0);
+        assert_eq!(resolved.outline_colour, Color::new(0x30, 0x20, 0x10, 0x80));
+        assert!(resolved.bold);
+        assert!(!resolved.italic);
+        assert_eq!(resolved.scale_y, 120.0);
+        assert_eq!(resolved.border_style, BorderStyle::OpaqueBox);
+        assert_eq!(resolved.outline, 2.5);
+        assert_eq!(resolved.alignment, Alignment::TopCenter);
+        assert_eq!(resolved.margin_v, 30);
+        assert_eq!(resolved.to_style(&styles), style);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_defaults() {
+        let styles = V4Styles::new(vec![StyleFormat::Name, StyleFormat::Fontsize]).unwrap();
+        let mut style = Style::new(&styles);
+        style.set(StyleFormat::Name, "Partial");
+        style.set(StyleFormat::Fontsize, "large");
+        let resolved = style.resolve();
+        assert_eq!(resolved.fontsize, 0.0);
+        assert_eq!(resolved.scale_x, 100.0);
+        assert_eq!(resolved.alignment, Alignment::BottomCenter);
+        assert_eq!(resolved.border_style, BorderStyle::Outline);
+        assert_eq!(resolved.encoding, 134);
+    }
+
+    #[test]
+    fn test_convert_order() -> crate::Result<()> {
+        let v4 = V4Styles::new(vec![
+            StyleFormat::Name,
+            StyleFormat::Fontsize,
+            StyleFormat::TertiaryColour,
+            StyleFormat::Alignment,
+            StyleFormat::AlphaLevel,
+        ])?;
+        let resolved = style(&v4, "Old,20,&H00FF0000,6,0")?.resolve();
+        assert_eq!(resolved.outline_colour, Color::new(0, 0, 255, 0));
+        assert_eq!(resolved.alignment, Alignment::TopCenter);
+        assert_eq!(
+            resolved.to_style(&v4).get(StyleFormat::Alignment),
+            Some(&Value::Int(6))
+        );
+        let converted = resolved.to_style(&V4Styles::default());
+        assert_eq!(
+            converted.get(StyleFormat::OutlineColour),
+            Some(&Color::new(0, 0, 255, 0).into())
+        );
+        assert_eq!(
+            converted.alignment(Version::V4Plus),
+            Some(Alignment::TopCenter)
+        );
+        assert_eq!(converted.resolve(), resolved);
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt::Display;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::time::Duration;
+
+use crate::{error::Error, parser::Parser};
+
+/// A point in time with the centisecond precision of ASS files, written as `H:MM:SS.CC`.
+///
+/// Hours are not limited to a day, and timestamps may go negative while being shifted around.
+/// Negative timestamps are written as `0:00:00.00` since the format cannot represent them.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Timestamp {
+    centis: i64,
+}
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp { centis: 0 };
+
+    pub const fn from_centis(centis: i64) -> Self {
+        Self { centis }
+    }
+
+    /// Rounds to the nearest centisecond, halves away from zero like Aegisub.
+    pub const fn from_millis(millis: i64) -> Self {
+        let centis = if millis < 0 {
+            -((-millis + 5) / 10)
+        } else {
+            millis.saturating_add(5) / 10
+        };
+        Self { centis }
+    }
+
+    pub fn from_secs_f64(secs: f64) -> Self {
+        Self {
+            centis: (secs * 100.0).round() as i64,
+        }
+    }
+
+    pub const fn centis(&self) -> i64 {
+        self.centis
+    }
+
+    pub const fn millis(&self) -> i64 {
+        self.centis * 10
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        self.centis as f64 / 100.0
+    }
+
+    pub const fn is_negative(&self) -> bool {
+        self.centis < 0
+    }
+
+    /// The timestamp as [`Duration`], negative timestamps become zero.
+    pub fn to_duration(&self) -> Duration {
+        Duration::from_millis(self.millis().max(0) as u64)
+    }
+
+    /// Multiplies the timestamp by `factor`, rounding to the nearest centisecond.
+    pub fn scale(self, factor: f64) -> Self {
+        Self {
+            centis: (self.centis as f64 * factor).round() as i64,
+        }
+    }
+}
+
+impl From<Duration> for Timestamp {
+    fn from(duration: Duration) -> Self {
+        let centis = (duration.as_nanos() + 5_000_000) / 10_000_000;
+        Self {
+            centis: centis as i64,
+        }
+    }
+}
+
+impl Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Timestamp::from_centis(self.centis + rhs.centis)
+    }
+}
+
+impl AddAssign for Timestamp {
+    fn add_assign(&mut self, rhs: Self) {
+        self.centis += rhs.centis;
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Timestamp::from_centis(self.centis - rhs.centis)
+    }
+}
+
+impl SubAssign for Timestamp {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.centis -= rhs.centis;
+    }
+}
+
+impl Neg for Timestamp {
+    type Output = Timestamp;
+
+    fn neg(self) -> Self::Output {
+        Timestamp::from_centis(-self.centis)
+    }
+}
+
+impl Mul<f64> for Timestamp {
+    type Output = Timestamp;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        self.scale(rhs)
+    }
+}
+
+impl Parser for Timestamp {
+    /// Parses `H:MM:SS.CC`. The fraction is read as a decimal fraction of a second and rounded
+    /// to centiseconds, so `.5` is half a second and `.125` rounds up to `.13`.
+    fn parse(src: &str) -> crate::Result<Self> {
+        let invalid = || Error::parse_error::<Timestamp>(format!("invalid timestamp {}", src));
+        let trimmed = src.trim();
+        let (negative, time) = match trimmed.strip_prefix('-') {
+            Some(time) => (true, time),
+            None => (false, trimmed),
+        };
+        let (hms, fraction) = time.split_once('.').ok_or_else(invalid)?;
+        let parts: Vec<_> = hms.split(':').collect();
+        let [h, m, s] = parts[..] else {
+            return Err(invalid());
+        };
+        let number = |part: &str| {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse::<i64>()
+                .map_err(|error| Error::parse_int_error(error, part))
+        };
+        let (h, m, s) = (number(h)?, number(m)?, number(s)?);
+        if m >= 60 {
+            return Err(Error::parse_error::<Timestamp>(format!(
+                "minute {} out of range",
+                src
+            )));
+        }
+        if s >= 60 {
+            return Err(Error::parse_error::<Timestamp>(format!(
+                "second {} out of range",
+                src
+            )));
+        }
+        if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let millis = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(3)
+            .fold(0, |millis, digit| millis * 10 + (digit - b'0') as i64);
+        let millis = h
+            .checked_mul(3_600_000)
+            .and_then(|hours| hours.checked_add((m * 60 + s) * 1000 + millis))
+            .ok_or_else(|| Error::parse_error::<Timestamp>(format!("hour {} out of range", src)))?;
+        let timestamp = Timestamp::from_millis(millis);
+        Ok(if negative { -timestamp } else { timestamp })
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let centis = self.centis.max(0);
+        write!(
+            f,
+            "{}:{:02}:{:02}.{:02}",
+            centis / 360_000,
+            centis % 360_000 / 6_000,
+            centis % 6_000 / 100,
+            centis % 100
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() -> crate::Result<()> {
+        assert_eq!(Timestamp::parse("0:00:01.50")?, Timestamp::from_centis(150));
+        assert_eq!(Timestamp::parse("0:00:01.5")?, Timestamp::from_centis(150));
+        assert_eq!(
+            Timestamp::parse("0:00:01.125")?,
+            Timestamp::from_centis(113)
+        );
+        assert_eq!(
+            Timestamp::parse("0:00:01.1249")?,
+            Timestamp::from_centis(112)
+        );
+        assert!(Timestamp::parse("0:00:01").is_err());
+        assert_eq!(
+            Timestamp::parse("25:00:00.00")?,
+            Timestamp::from_centis(25 * 360_000)
+        );
+        assert_eq!(
+            Timestamp::parse("-0:00:01.00")?,
+            Timestamp::from_centis(-100)
+        );
+        assert!(Timestamp::parse("0:60:00.00").is_err());
+        assert!(Timestamp::parse("0:00:60.00").is_err());
+        assert!(Timestamp::parse("0:00.00").is_err());
+        assert!(Timestamp::parse("0:00:0x.00").is_err());
+        assert!(Timestamp::parse("0:00:00.-1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_timestamp() {
+        assert_eq!(Timestamp::from_centis(150).to_string(), "0:00:01.50");
+        assert_eq!(Timestamp::from_millis(1505).to_string(), "0:00:01.51");
+        assert_eq!(Timestamp::from_millis(1504).to_string(), "0:00:01.50");
+        assert_eq!(
+            Timestamp::from_centis(100 * 360_000 + 1).to_string(),
+            "100:00:00.01"
+        );
+        assert_eq!(Timestamp::from_centis(-50).to_string(), "0:00:00.00");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = Timestamp::from_centis(150);
+        let b = Timestamp::from_centis(200);
+        assert_eq!(a - b, Timestamp::from_centis(-50));
+        assert!((a - b).is_negative());
+        assert_eq!(a - b + b, a);
+        assert_eq!(a * 2.0, Timestamp::from_centis(300));
+        assert_eq!(b.scale(25.0 / 23.976), Timestamp::from_centis(209));
+        assert_eq!(Timestamp::from_millis(-15), Timestamp::from_centis(-2));
+        assert_eq!(
+            Timestamp::from(Duration::from_millis(1234)),
+            Timestamp::from_centis(123)
+        );
+        assert_eq!(Timestamp::from_centis(-5).to_duration(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,491 @@
+//! Timing adjustments over [`Events`] that report every time they change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::{
+    events::{index::TimeIndex, Event, EventFormat, EventType, Events},
+    frames::{Boundary, FrameTimes},
+    keyframes::Keyframes,
+    timestamp::Timestamp,
+    value::Value,
+};
+
+/// Why a time was changed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChangeReason {
+    LeadIn,
+    LeadOut,
+    GapClosed,
+    MinimumDuration,
+    Overlap,
+    Keyframe,
+}
+
+/// One start or end time changed by a timing adjustment.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TimingChange {
+    /// Position of the event in [`Events`].
+    pub position: usize,
+    /// [`EventFormat::Start`] or [`EventFormat::End`].
+    pub format: EventFormat,
+    pub old: Timestamp,
+    pub new: Timestamp,
+    pub reason: ChangeReason,
+}
+
+impl Display for TimingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "event {}: {} {} -> {} ({})",
+            self.position, self.format, self.old, self.new, self.reason
+        )
+    }
+}
+
+/// The steps of Aegisub's timing post-processor, each left out while zero or disabled. They run
+/// in field order on the events of one style at a time, keyframes can be snapped afterwards
+/// with [`KeyframeSnap`] given the same lead-in and lead-out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostProcess {
+    /// Moves starts earlier, but not before the end of an earlier line.
+    pub lead_in: Timestamp,
+    /// Moves ends later, but not past the start of a later line.
+    pub lead_out: Timestamp,
+    /// Gaps up to this long between consecutive lines are closed.
+    pub gap_threshold: Timestamp,
+    /// Where gaps and overlaps are closed: 0 moves the later start, 1 the earlier end and 0.5
+    /// meets halfway.
+    pub bias: f64,
+    /// Lines shorter than this are extended, as far as the next line allows.
+    pub minimum_duration: Timestamp,
+    /// Makes consecutive overlapping lines meet instead.
+    pub fix_overlaps: bool,
+    /// Only lines of these styles are processed, all of them when empty.
+    pub styles: Vec<String>,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            lead_in: Timestamp::ZERO,
+            lead_out: Timestamp::ZERO,
+            gap_threshold: Timestamp::ZERO,
+            bias: 0.5,
+            minimum_duration: Timestamp::ZERO,
+            fix_overlaps: false,
+            styles: vec![],
+        }
+    }
+}
+
+/// Collects the changes of [`PostProcess`] while applying them to the events.
+struct Processor<'a> {
+    events: &'a mut Events,
+    /// Positions of the processed events by style, sorted by start.
+    groups: Vec<Vec<usize>>,
+    changes: Vec<TimingChange>,
+}
+
+impl Processor<'_> {
+    fn start(&self, position: usize) -> Timestamp {
+        interval(&self.events[position]).start
+    }
+
+    fn end(&self, position: usize) -> Timestamp {
+        interval(&self.events[position]).end
+    }
+
+    fn set(&mut self, position: usize, format: EventFormat, new: Timestamp, reason: ChangeReason) {
+        let old = match format {
+            EventFormat::Start => self.start(position),
+            _ => self.end(position),
+        };
+        if old == new {
+            return;
+        }
+        self.events[position].set(format, new);
+        self.changes.push(TimingChange {
+            position,
+            format,
+            old,
+            new,
+            reason,
+        });
+    }
+
+    fn sort(&mut self) {
+        for mut group in std::mem::take(&mut self.groups) {
+            group.sort_by_key(|position| (self.start(*position), *position));
+            self.groups.push(group);
+        }
+    }
+
+    fn lead_in(&mut self, lead_in: Timestamp) {
+        for group in self.groups.clone() {
+            // ends of the earlier lines, to find the latest one before each start
+            let mut ends = BTreeSet::new();
+            for position in group {
+                let (start, end) = (self.start(position), self.end(position));
+                let limit = ends
+                    .range(..=(start, usize::MAX))
+                    .next_back()
+                    .map_or(Timestamp::ZERO, |(end, _)| *end);
+                let new = (start - lead_in).max(limit).min(start);
+                self.set(position, EventFormat::Start, new, ChangeReason::LeadIn);
+                ends.insert((end, position));
+            }
+        }
+    }
+
+    fn lead_out(&mut self, lead_out: Timestamp, old_starts: &BTreeMap<usize, Timestamp>) {
+        for group in self.groups.clone() {
+            // the earliest new start of the lines starting at or after each position
+            let mut earliest = vec![None; group.len() + 1];
+            for (i, position) in group.iter().enumerate().rev() {
+                let start = self.start(*position);
+                earliest[i] =
+                    Some(earliest[i + 1].map_or(start, |next: Timestamp| next.min(start)));
+            }
+            for position in group.iter().copied() {
+                let end = self.end(position);
+                let next = group.partition_point(|other| old_starts[other] < end);
+                let mut new = end + lead_out;
+                if let Some(limit) = earliest[next] {
+                    new = new.min(limit.max(end));
+                }
+                self.set(position, EventFormat::End, new, ChangeReason::LeadOut);
+            }
+        }
+    }
+
+    /// Makes consecutive lines meet when `close` accepts the distance between them.
+    fn link(&mut self, bias: f64, reason: ChangeReason, close: impl Fn(Timestamp) -> bool) {
+        for group in self.groups.clone() {
+            for pair in group.windows(2) {
+                let (prev, next) = (pair[0], pair[1]);
+                let distance = self.start(next) - self.end(prev);
+                if !close(distance) {
+                    continue;
+                }
+                let offset = (distance.centis() as f64 * bias).round() as i64;
+                let point = self.end(prev) + Timestamp::from_centis(offset);
+                if point <= self.start(prev) || point >= self.end(next) {
+                    continue;
+                }
+                self.set(prev, EventFormat::End, point, reason);
+                self.set(next, EventFormat::Start, point, reason);
+            }
+        }
+    }
+
+    fn minimum_duration(&mut self, minimum: Timestamp) {
+        for group in self.groups.clone() {
+            for (i, position) in group.iter().copied().enumerate() {
+                let (start, end) = (self.start(position), self.end(position));
+                if end - start >= minimum {
+                    continue;
+                }
+                let mut new = start + minimum;
+                if let Some(next) = group[i + 1..]
+                    .iter()
+                    .map(|next| self.start(*next))
+                    .filter(|next| *next >= end)
+                    .min()
+                {
+                    new = new.min(next);
+                }
+                if new > end {
+                    self.set(
+                        position,
+                        EventFormat::End,
+                        new,
+                        ChangeReason::MinimumDuration,
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn post_process(events: &mut Events, options: &PostProcess) -> Vec<TimingChange> {
+    let mut groups = BTreeMap::<&str, Vec<usize>>::new();
+    for (position, event) in events.iter().enumerate() {
+        let style = style(event).unwrap_or_default();
+        if event.event_type() == EventType::Comment
+            || !(options.styles.is_empty() || options.styles.iter().any(|s| s == style))
+        {
+            continue;
+        }
+        groups.entry(style).or_default().push(position);
+    }
+    let groups = groups.into_values().collect();
+    let mut processor = Processor {
+        events,
+        groups,
+        changes: vec![],
+    };
+    processor.sort();
+    let old_starts = processor
+        .groups
+        .iter()
+        .flatten()
+        .map(|position| (*position, processor.start(*position)))
+        .collect::<BTreeMap<_, _>>();
+    if options.lead_in > Timestamp::ZERO {
+        processor.lead_in(options.lead_in);
+    }
+    if options.lead_out > Timestamp::ZERO {
+        processor.lead_out(options.lead_out, &old_starts);
+    }
+    processor.sort();
+    let threshold = options.gap_threshold;
+    if threshold > Timestamp::ZERO {
+        let close = |gap: Timestamp| gap > Timestamp::ZERO && gap <= threshold;
+        processor.link(options.bias, ChangeReason::GapClosed, close);
+    }
+    if options.minimum_duration > Timestamp::ZERO {
+        processor.minimum_duration(options.minimum_duration);
+    }
+    if options.fix_overlaps {
+        let close = |gap: Timestamp| gap < Timestamp::ZERO;
+        processor.link(options.bias, ChangeReason::Overlap, close);
+    }
+    processor.changes
+}
+
+/// How far starts and ends may move to reach a keyframe.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KeyframeSnap {
+    pub starts_before: Timestamp,
+    pub starts_after: Timestamp,
+    pub ends_before: Timestamp,
+    pub ends_after: Timestamp,
+    /// Lead-in already added to the starts. Windows are measured from the times without it,
+    /// so that padding does not take a line out of reach of its keyframe.
+    pub lead_in: Timestamp,
+    pub lead_out: Timestamp,
+}
+
+impl Default for KeyframeSnap {
+    /// About five frames before and four frames after at 23.976 fps, like Aegisub.
+    fn default() -> Self {
+        Self {
+            starts_before: Timestamp::from_centis(21),
+            starts_after: Timestamp::from_centis(17),
+            ends_before: Timestamp::from_centis(21),
+            ends_after: Timestamp::from_centis(17),
+            lead_in: Timestamp::ZERO,
+            lead_out: Timestamp::ZERO,
+        }
+    }
+}
+
+/// Moves starts to the keyframe and ends to the frame before it when one is within the window
+/// of [`KeyframeSnap`]. `Comment` events are left alone, and a time is kept when moving it
+/// would make the event empty or overlap another event of its style it did not overlap before.
+pub(crate) fn snap_to_keyframes(
+    events: &mut Events,
+    keyframes: &Keyframes,
+    frames: &FrameTimes,
+    options: &KeyframeSnap,
+) -> Vec<TimingChange> {
+    let starts = keyframes
+        .iter()
+        .map(|frame| frames.time_at(*frame, Boundary::Start))
+        .collect::<Vec<_>>();
+    let ends = keyframes
+        .iter()
+        .map(|frame| frames.time_at(frame - 1, Boundary::End))
+        .collect::<Vec<_>>();
+    let mut index = TimeIndex::new(events);
+    let mut changes = vec![];
+    for position in 0..events.len() {
+        if events[position].event_type() == EventType::Comment {
+            continue;
+        }
+        for format in [EventFormat::Start, EventFormat::End] {
+            let Some(old) = time(&events[position], format) else {
+                continue;
+            };
+            let target = match format {
+                EventFormat::Start => {
+                    let origin = old + options.lead_in;
+                    nearest(&starts, origin, options.starts_before, options.starts_after)
+                }
+                _ => {
+                    let origin = old - options.lead_out;
+                    nearest(&ends, origin, options.ends_before, options.ends_after)
+                }
+            };
+            let Some(new) = target.filter(|new| *new != old) else {
+                continue;
+            };
+            let mut event = events[position].clone();
+            event.set(format, new);
+            if !fits(events, &index, position, &event) {
+                continue;
+            }
+            events[position] = event;
+            index.update(position, &events[position]);
+            changes.push(TimingChange {
+                position,
+                format,
+                old,
+                new,
+                reason: ChangeReason::Keyframe,
+            });
+        }
+    }
+    changes
+}
+
+fn time(event: &Event, format: EventFormat) -> Option<Timestamp> {
+    event.get(format).and_then(Value::as_timestamp)
+}
+
+fn interval(event: &Event) -> Range<Timestamp> {
+    let start = time(event, EventFormat::Start).unwrap_or_default();
+    start..time(event, EventFormat::End).unwrap_or_default()
+}
+
+fn style(event: &Event) -> Option<&str> {
+    event.get(EventFormat::Style).and_then(Value::as_str)
+}
+
+/// The target in `targets` closest to `origin` from `before` earlier to `after` later.
+fn nearest(
+    targets: &[Timestamp],
+    origin: Timestamp,
+    before: Timestamp,
+    after: Timestamp,
+) -> Option<Timestamp> {
+    let first = targets.partition_point(|target| *target < origin - before);
+    targets[first..]
+        .iter()
+        .take_while(|target| **target <= origin + after)
+        .min_by_key(|target| (**target - origin).centis().abs())
+        .copied()
+}
+
+/// Whether the edited `event` at `position` is not empty and overlaps no event of its style
+/// that it did not overlap before.
+fn fits(events: &Events, index: &TimeIndex, position: usize, event: &Event) -> bool {
+    let range = interval(event);
+    if range.start >= range.end {
+        return false;
+    }
+    let before = index.overlapping(interval(&events[position]));
+    index.overlapping(range).into_iter().all(|other| {
+        other == position
+            || before.contains(&other)
+            || events[other].event_type() == EventType::Comment
+            || style(&events[other]) != style(event)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::File;
+
+    fn summary(changes: &[TimingChange]) -> Vec<String> {
+        changes.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_post_process() -> crate::Result<()> {
+        let source = "[Events]\nFormat: Start, End, Style, Text\n\
+             Dialogue: 0:00:01.00,0:00:02.00,Default,a\n\
+             Dialogue: 0:00:02.20,0:00:03.00,Default,b\n\
+             Comment: 0:00:02.90,0:00:03.00,Default,comment\n\
+             Dialogue: 0:00:05.00,0:00:05.30,Default,short\n\
+             Dialogue: 0:00:05.40,0:00:07.00,Default,overlapped\n\
+             Dialogue: 0:00:06.50,0:00:08.00,Default,overlapping\n\
+             Dialogue: 0:00:02.10,0:00:02.50,Sign,sign\n";
+        let mut file = File::from_str(source)?;
+        let options = PostProcess {
+            lead_in: Timestamp::from_centis(15),
+            lead_out: Timestamp::from_centis(5),
+            styles: vec!["Default".into()],
+            ..Default::default()
+        };
+        let changes = file.events.post_process(&options);
+        assert_eq!(
+            summary(&changes),
+            vec![
+                "event 0: Start 0:00:01.00 -> 0:00:00.85 (lead-in)",
+                "event 1: Start 0:00:02.20 -> 0:00:02.05 (lead-in)",
+                "event 3: Start 0:00:05.00 -> 0:00:04.85 (lead-in)",
+                "event 4: Start 0:00:05.40 -> 0:00:05.30 (lead-in)",
+                "event 5: Start 0:00:06.50 -> 0:00:06.35 (lead-in)",
+                "event 0: End 0:00:02.00 -> 0:00:02.05 (lead-out)",
+                "event 1: End 0:00:03.00 -> 0:00:03.05 (lead-out)",
+                "event 4: End 0:00:07.00 -> 0:00:07.05 (lead-out)",
+                "event 5: End 0:00:08.00 -> 0:00:08.05 (lead-out)",
+            ]
+        );
+
+        let mut file = File::from_str(source)?;
+        let options = PostProcess {
+            gap_threshold: Timestamp::from_centis(30),
+            bias: 1.0,
+            minimum_duration: Timestamp::from_centis(50),
+            fix_overlaps: true,
+            ..Default::default()
+        };
+        let changes = file.events.post_process(&options);
+        assert_eq!(
+            summary(&changes),
+            vec![
+                "event 0: End 0:00:02.00 -> 0:00:02.20 (gap-closed)",
+                "event 3: End 0:00:05.30 -> 0:00:05.40 (gap-closed)",
+                "event 6: End 0:00:02.50 -> 0:00:02.60 (minimum-duration)",
+                "event 4: End 0:00:07.00 -> 0:00:06.50 (overlap)",
+            ]
+        );
+        assert_eq!(file.events.post_process(&options), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snap_to_keyframes() -> crate::Result<()> {
+        let mut file = File::from_str(
+            "[Events]\nFormat: Start, End, Style, Text\n\
+             Dialogue: 0:00:01.05,0:00:01.90,Default,near both\n\
+             Dialogue: 0:00:03.50,0:00:04.00,Default,nothing near\n\
+             Comment: 0:00:01.05,0:00:01.90,Default,comment\n\
+             Dialogue: 0:00:05.08,0:00:05.50,Default,would overlap\n\
+             Dialogue: 0:00:04.70,0:00:05.05,Default,before\n",
+        )?;
+        // frames 24, 48 and 120 start at 1001, 2002 and 5005 ms
+        let frames = FrameTimes::constant(24000, 1001)?;
+        let keyframes = Keyframes::new([24, 48, 120]);
+        let changes = file
+            .events
+            .snap_to_keyframes(&keyframes, &frames, &KeyframeSnap::default());
+        assert_eq!(
+            summary(&changes),
+            vec![
+                "event 0: Start 0:00:01.05 -> 0:00:00.98 (keyframe)",
+                "event 0: End 0:00:01.90 -> 0:00:01.98 (keyframe)",
+                "event 4: End 0:00:05.05 -> 0:00:04.98 (keyframe)",
+            ]
+        );
+        assert_eq!(
+            file.events[2].get(EventFormat::Start),
+            Some(&Timestamp::from_centis(105).into())
+        );
+
+        let options = KeyframeSnap {
+            lead_in: Timestamp::from_centis(30),
+            ..Default::default()
+        };
+        let changes = file.events.snap_to_keyframes(&keyframes, &frames, &options);
+        assert!(changes.is_empty());
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use itertools::Itertools;
 
 use crate::{
+    color::Color,
     events::{effect::Effect, text::Text},
     format_duration,
+    timestamp::Timestamp,
 };
 use std::time::Duration;
 
@@ -14,8 +16,10 @@ pub enum Value {
     Boolean(bool),
     List(Vec<Value>),
     Duration(Duration),
+    Timestamp(Timestamp),
     Effect(Effect),
     Text(Text),
+    Color(Color),
 }
 
 impl PartialEq for Value {
@@ -27,8 +31,10 @@ impl PartialEq for Value {
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::List(a), Value::List(b)) => a == b,
             (Value::Duration(a), Value::Duration(b)) => a == b,
+            (Value::Timestamp(a), Value::Timestamp(b)) => a == b,
             (Value::Effect(a), Value::Effect(b)) => a == b,
             (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Color(a), Value::Color(b)) => a == b,
             _ => false,
         }
     }
@@ -49,8 +55,10 @@ impl std::fmt::Display for Value {
                 write!(f, "]")
             }
             Value::Duration(duration) => write!(f, "{}", format_duration(duration)),
+            Value::Timestamp(timestamp) => write!(f, "{}", timestamp),
             Value::Effect(effect) => write!(f, "{}", effect),
             Value::Text(text) => write!(f, "{}", text),
+            Value::Color(color) => write!(f, "{}", color),
         }
     }
 }
@@ -140,6 +148,20 @@ impl Value {
         }
     }
 
+    pub fn as_timestamp(&self) -> Option<Timestamp> {
+        match self {
+            Value::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    pub fn as_timestamp_mut(&mut self) -> Option<&mut Timestamp> {
+        match self {
+            Value::Timestamp(t) => Some(t),
+            _ => None,
+        }
+    }
+
     pub fn as_effect(&self) -> Option<&Effect> {
         match self {
             Value::Effect(e) => Some(e),
@@ -153,58 +175,98 @@ impl Value {
             _ => None,
         }
     }
+
+    pub fn as_text(&self) -> Option<&Text> {
+        match self {
+            Value::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_text_mut(&mut self) -> Option<&mut Text> {
+        match self {
+            Value::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_color(&self) -> Option<Color> {
+        match self {
+            Value::Color(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn as_color_mut(&mut self) -> Option<&mut Color> {
+        match self {
+            Value::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
 }
 
-impl Into<Value> for String {
-    fn into(self) -> Value {
-        Value::Str(self)
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
     }
 }
 
-impl Into<Value> for &str {
-    fn into(self) -> Value {
-        Value::Str(self.to_string())
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
     }
 }
 
-impl Into<Value> for i64 {
-    fn into(self) -> Value {
-        Value::Int(self)
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
     }
 }
 
-impl Into<Value> for f64 {
-    fn into(self) -> Value {
-        Value::Float(self)
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Value::List(value)
     }
 }
 
-impl Into<Value> for bool {
-    fn into(self) -> Value {
-        Value::Boolean(self)
+impl From<Duration> for Value {
+    fn from(value: Duration) -> Self {
+        Value::Duration(value)
     }
 }
 
-impl Into<Value> for Vec<Value> {
-    fn into(self) -> Value {
-        Value::List(self)
+impl From<Timestamp> for Value {
+    fn from(value: Timestamp) -> Self {
+        Value::Timestamp(value)
     }
 }
 
-impl Into<Value> for Duration {
-    fn into(self) -> Value {
-        Value::Duration(self)
+impl From<Effect> for Value {
+    fn from(value: Effect) -> Self {
+        Value::Effect(value)
     }
 }
 
-impl Into<Value> for Effect {
-    fn into(self) -> Value {
-        Value::Effect(self)
+impl From<Text> for Value {
+    fn from(value: Text) -> Self {
+        Value::Text(value)
     }
 }
 
-impl Into<Value> for Text {
-    fn into(self) -> Value {
-        Value::Text(self)
+impl From<Color> for Value {
+    fn from(value: Color) -> Self {
+        Value::Color(value)
     }
 }
@@ -1,6 +1,9 @@
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Version {
+    #[cfg_attr(feature = "serde", serde(rename = "v4.00"))]
     V4,
     #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "v4.00+"))]
     V4Plus,
 }
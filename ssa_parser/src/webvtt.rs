@@ -0,0 +1,546 @@
+use std::fmt::Write;
+
+use crate::{
+    alignment::{HorizontalAlign, VerticalAlign},
+    color::Color,
+    error::Error,
+    events::{text::Text, Event, EventFormat, EventType},
+    file::File,
+    parser::Parser,
+    srt::{
+        format_time, html_tag_to_override, new_event, new_file, parse_time, tags_to_html, time,
+        Markup,
+    },
+    styles::{Style, StyleFormat, V4Styles},
+    value::Value,
+};
+
+const DEFAULT_PLAY_RES_X: i64 = 384;
+const DEFAULT_PLAY_RES_Y: i64 = 288;
+
+impl File {
+    /// Reads a WebVTT file. Voice spans fill the `Name` field, a class span wrapping a whole cue
+    /// selects the style of the same name and other class spans become `\r` overrides.
+    pub fn from_webvtt(vtt: impl AsRef<str>) -> crate::Result<Self> {
+        let mut file = new_file()?;
+        let vtt = vtt.as_ref();
+        let vtt = vtt
+            .strip_prefix('\u{FEFF}')
+            .unwrap_or(vtt)
+            .replace("\r\n", "\n");
+        let mut blocks = vtt.split("\n\n").filter(|block| !block.trim().is_empty());
+        if !blocks
+            .next()
+            .is_some_and(|header| header.starts_with("WEBVTT"))
+        {
+            return Err(Error::parse_error::<File>(
+                "missing WEBVTT header".to_string(),
+            ));
+        }
+        for block in blocks {
+            let block = block.trim_start_matches('\n');
+            if block.starts_with("NOTE") || block.starts_with("REGION") {
+                continue;
+            }
+            if let Some(css) = block.strip_prefix("STYLE") {
+                parse_css(css, &mut file.styles)?;
+                continue;
+            }
+            let mut lines = block.lines();
+            let Some(mut line) = lines.next() else {
+                continue;
+            };
+            if !line.contains("-->") {
+                line = lines.next().unwrap_or_default();
+            }
+            let invalid = || Error::parse_error::<File>(format!("invalid webvtt timing {}", line));
+            let (start, rest) = line.split_once("-->").ok_or_else(invalid)?;
+            let mut rest = rest.split_whitespace();
+            let start = parse_time(start.trim()).ok_or_else(invalid)?;
+            let end = rest.next().and_then(parse_time).ok_or_else(invalid)?;
+            let text = lines.collect::<Vec<_>>().join("\\N");
+            let cue = Cue::parse(&text, &file.styles);
+            let mut text = String::new();
+            let alignment = settings_to_alignment(rest);
+            if alignment != 2 {
+                let _ = write!(text, "{{\\an{}}}", alignment);
+            }
+            text.push_str(&cue.text);
+            let style = cue.style.as_deref().unwrap_or("Default");
+            let name = cue.name.as_deref().unwrap_or_default();
+            let event = new_event(&file, start, end, style, name, Text::parse(&text)?);
+            file.events.push(event);
+        }
+        Ok(file)
+    }
+
+    /// Renders the dialogue events as WebVTT cues preceded by a `STYLE` block with one
+    /// `::cue(.Style)` rule per style. Placement comes from `\an` and the margins.
+    pub fn to_webvtt(&self) -> String {
+        let mut vtt = String::from("WEBVTT\n\n");
+        if self.styles.iter().next().is_some() {
+            vtt.push_str("STYLE\n");
+            for (name, style) in self.styles.iter() {
+                write_css(&mut vtt, name, style);
+            }
+            vtt.push('\n');
+        }
+        let mut events: Vec<_> = self
+            .events
+            .iter()
+            .filter(|event| event.event_type() == EventType::Dialogue)
+            .collect();
+        events.sort_by_key(|event| time(event, EventFormat::Start));
+        for (i, event) in events.into_iter().enumerate() {
+            let _ = writeln!(
+                vtt,
+                "{}\n{} --> {}{}",
+                i + 1,
+                format_time(time(event, EventFormat::Start), '.'),
+                format_time(time(event, EventFormat::End), '.'),
+                self.cue_settings(event)
+            );
+            let name = event
+                .get(EventFormat::Name)
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            if !name.is_empty() {
+                let _ = write!(vtt, "<v {}>", name);
+            }
+            let style = event.get(EventFormat::Style).and_then(Value::as_str);
+            if let Some(style) = style {
+                let _ = write!(vtt, "<c.{}>", class_name(style));
+            }
+            match event.get(EventFormat::Text) {
+                Some(Value::Text(text)) => vtt.push_str(&tags_to_html(text, Markup::WebVtt)),
+                Some(text) => vtt.push_str(&text.to_string()),
+                None => {}
+            }
+            if style.is_some() {
+                vtt.push_str("</c>");
+            }
+            vtt.push_str("\n\n");
+        }
+        vtt
+    }
+
+    fn cue_settings(&self, event: &Event) -> String {
+        let style = event
+            .get(EventFormat::Style)
+            .and_then(Value::as_str)
+            .and_then(|name| self.styles.get(name));
+        let style_value = |format| {
+            style
+                .and_then(|style| style.get(format))
+                .and_then(Value::as_int)
+                .unwrap_or_default()
+        };
+        let margin = |event_format, style_format| {
+            event
+                .get(event_format)
+                .and_then(Value::as_int)
+                .filter(|margin| *margin != 0)
+                .unwrap_or_else(|| style_value(style_format)) as f64
+        };
+        let alignment = event
+            .get(EventFormat::Text)
+            .and_then(Value::as_text)
+            .and_then(|text| text.tags().find_map(|tag| tag.kind().alignment()))
+            .or_else(|| style.and_then(|style| style.alignment(self.version)))
+            .unwrap_or_default();
+        let width = self
+            .script
+            .get_play_res_x()
+            .unwrap_or(DEFAULT_PLAY_RES_X)
+            .max(1) as f64;
+        let height = self
+            .script
+            .get_play_res_y()
+            .unwrap_or(DEFAULT_PLAY_RES_Y)
+            .max(1) as f64;
+        let margin_v = margin(EventFormat::MarginV, StyleFormat::MarginV) / height * 100.0;
+        let line = match alignment.vertical() {
+            VerticalAlign::Bottom => format!("{}%,end", percent(100.0 - margin_v)),
+            VerticalAlign::Middle => "50%,center".to_string(),
+            VerticalAlign::Top => format!("{}%,start", percent(margin_v)),
+        };
+        let (position, align) = match alignment.horizontal() {
+            HorizontalAlign::Left => {
+                let margin_l = margin(EventFormat::MarginL, StyleFormat::MarginL);
+                (
+                    format!("{}%,line-left", percent(margin_l / width * 100.0)),
+                    "start",
+                )
+            }
+            HorizontalAlign::Center => ("50%,center".to_string(), "center"),
+            HorizontalAlign::Right => {
+                let margin_r = margin(EventFormat::MarginR, StyleFormat::MarginR);
+                (
+                    format!("{}%,line-right", percent(100.0 - margin_r / width * 100.0)),
+                    "end",
+                )
+            }
+        };
+        format!(" line:{} position:{} align:{}", line, position, align)
+    }
+}
+
+/// A style name usable as CSS class, characters other than letters, digits, `-` and `_` are
+/// replaced by `_`.
+pub(crate) fn class_name(style: &str) -> String {
+    style
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn percent(value: f64) -> String {
+    let value = format!("{:.2}", value.clamp(0.0, 100.0));
+    value
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn write_css(out: &mut String, name: &str, style: &Style) {
+    let _ = writeln!(out, "::cue(.{}) {{", class_name(name));
+    let string = |format| style.get(format).and_then(Value::as_str);
+    let color = |format| style.get(format).and_then(Value::as_color);
+    let number = |format| {
+        style
+            .get(format)
+            .and_then(|value| value.as_float().or(value.as_int().map(|v| v as f64)))
+            .unwrap_or_default()
+    };
+    if let Some(font) = string(StyleFormat::Fontname).filter(|font| !font.is_empty()) {
+        let _ = writeln!(out, "  font-family: \"{}\";", font);
+    }
+    if let Some(color) = color(StyleFormat::PrimaryColour).map(|color| color.to_css()) {
+        let _ = writeln!(out, "  color: {};", color);
+    }
+    if number(StyleFormat::Bold) != 0.0 {
+        out.push_str("  font-weight: bold;\n");
+    }
+    if number(StyleFormat::Italic) != 0.0 {
+        out.push_str("  font-style: italic;\n");
+    }
+    let outline = number(StyleFormat::Outline);
+    let outline_colour = color(StyleFormat::OutlineColour).map(|color| color.to_css());
+    if let (true, Some(color)) = (outline > 0.0, outline_colour) {
+        let _ = writeln!(
+            out,
+            "  text-shadow: {c} -{w}px -{w}px 0, {c} {w}px -{w}px 0, {c} -{w}px {w}px 0, {c} {w}px {w}px 0;",
+            c = color,
+            w = outline
+        );
+    }
+    out.push_str("}\n");
+}
+
+/// Reads the `::cue(.Class)` rules of a `STYLE` block into styles derived from `Default`.
+fn parse_css(css: &str, styles: &mut V4Styles) -> crate::Result<()> {
+    let mut rest = css;
+    while let Some(selector) = rest.find("::cue(") {
+        let Some(open) = rest[selector..].find('{').map(|pos| selector + pos) else {
+            break;
+        };
+        let Some(close) = rest[open..].find('}').map(|pos| open + pos) else {
+            break;
+        };
+        let class = rest[selector + "::cue(".len()..open]
+            .trim()
+            .trim_end_matches(')')
+            .trim();
+        let declarations = &rest[open + 1..close];
+        rest = &rest[close + 1..];
+        let Some(class) = class.strip_prefix('.') else {
+            continue;
+        };
+        let Some(mut style) = styles.get(class).or_else(|| styles.get("Default")).cloned() else {
+            continue;
+        };
+        style.set(StyleFormat::Name, class);
+        for declaration in declarations.split(';') {
+            let Some((property, value)) = declaration.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match property.trim() {
+                "font-family" => {
+                    let font = value.split(',').next().unwrap_or_default();
+                    style.set(
+                        StyleFormat::Fontname,
+                        font.trim().trim_matches(|c| c == '"' || c == '\''),
+                    );
+                }
+                "color" => {
+                    if let Ok(color) = Color::from_css(value) {
+                        style.set(StyleFormat::PrimaryColour, color);
+                    }
+                }
+                "font-weight" => {
+                    let bold = value == "bold" || value.parse::<i64>().is_ok_and(|w| w >= 600);
+                    style.set(StyleFormat::Bold, if bold { -1 } else { 0 });
+                }
+                "font-style" => {
+                    style.set(StyleFormat::Italic, if value == "italic" { -1 } else { 0 });
+                }
+                "text-shadow" => {
+                    let mut words = value.split([' ', ',']);
+                    if let Some(color) = words.clone().find_map(|word| Color::from_hex(word).ok()) {
+                        style.set(StyleFormat::OutlineColour, color);
+                    }
+                    let width = words.find_map(|word| {
+                        word.strip_suffix("px")?
+                            .trim_start_matches('-')
+                            .parse::<f64>()
+                            .ok()
+                    });
+                    if let Some(width) = width {
+                        style.set(StyleFormat::Outline, width);
+                    }
+                }
+                _ => {}
+            }
+        }
+        styles.remove(class);
+        styles.add(style)?;
+    }
+    Ok(())
+}
+
+/// Maps the `line` and `align` cue settings to a numpad alignment.
+fn settings_to_alignment<'a>(settings: impl Iterator<Item = &'a str>) -> i64 {
+    let mut column = 1;
+    let mut row = 0;
+    for setting in settings {
+        let Some((key, value)) = setting.split_once(':') else {
+            continue;
+        };
+        let value = value.split(',').next().unwrap_or_default();
+        match key {
+            "align" => {
+                column = match value {
+                    "start" | "left" => 0,
+                    "end" | "right" => 2,
+                    _ => 1,
+                }
+            }
+            "line" => {
+                row = match value.strip_suffix('%') {
+                    Some(percent) => match percent.parse::<f64>() {
+                        Ok(percent) if percent < 100.0 / 3.0 => 2,
+                        Ok(percent) if percent < 200.0 / 3.0 => 1,
+                        _ => 0,
+                    },
+                    None => match value.parse::<i64>() {
+                        Ok(line) if line >= 0 => 2,
+                        _ => 0,
+                    },
+                }
+            }
+            _ => {}
+        }
+    }
+    row * 3 + column + 1
+}
+
+/// The text of a cue with its markup turned into override tags.
+#[derive(Debug, Default)]
+struct Cue {
+    text: String,
+    style: Option<String>,
+    name: Option<String>,
+}
+
+impl Cue {
+    fn parse(src: &str, styles: &V4Styles) -> Self {
+        let mut cue = Cue::default();
+        let style_of = |class: &str| {
+            styles
+                .iter()
+                .map(|(name, _)| name)
+                .find(|name| class_name(name) == class)
+                .map(str::to_string)
+        };
+        // Classes of the open `<c>` spans, `None` for spans without a class.
+        let mut classes: Vec<Option<String>> = vec![];
+        // The style of a span opened at the very start of the cue and where that span ended.
+        let mut outer: Option<(String, Option<usize>)> = None;
+        let mut rest = src;
+        while let Some(open) = rest.find('<') {
+            let Some(close) = rest[open..].find('>').map(|pos| open + pos) else {
+                break;
+            };
+            cue.push_plain(&rest[..open]);
+            let tag = rest[open + 1..close].trim();
+            rest = &rest[close + 1..];
+            let name = tag.split(['.', ' ', '\t']).next().unwrap_or_default();
+            match name {
+                "v" => {
+                    let voice = tag.split_once([' ', '\t']).map(|(_, voice)| voice.trim());
+                    if cue.name.is_none() {
+                        cue.name = voice.map(str::to_string);
+                    }
+                }
+                "c" => {
+                    let class = tag.split('.').nth(1).and_then(style_of);
+                    match &class {
+                        Some(class) if cue.text.is_empty() && classes.is_empty() => {
+                            outer = Some((class.clone(), None));
+                        }
+                        Some(class) => {
+                            let _ = write!(cue.text, "{{\\r{}}}", class);
+                        }
+                        None => {}
+                    }
+                    classes.push(class);
+                }
+                "/c" => {
+                    if classes.pop().flatten().is_none() {
+                        continue;
+                    }
+                    let outer_open = matches!(outer, Some((_, None)));
+                    if let (Some((_, end)), true) = (&mut outer, outer_open && classes.is_empty()) {
+                        *end = Some(cue.text.len());
+                        continue;
+                    }
+                    let inner = &classes[usize::from(outer_open)..];
+                    match inner.iter().rev().flatten().next() {
+                        Some(parent) => {
+                            let _ = write!(cue.text, "{{\\r{}}}", parent);
+                        }
+                        None => cue.text.push_str("{\\r}"),
+                    }
+                }
+                _ => {
+                    if let Some(tags) = html_tag_to_override(tag) {
+                        let _ = write!(cue.text, "{{{}}}", tags);
+                    }
+                }
+            }
+        }
+        cue.push_plain(rest);
+        match outer {
+            Some((style, Some(end))) if end < cue.text.len() => {
+                cue.text.insert_str(end, "{\\r}");
+                cue.text.insert_str(0, &format!("{{\\r{}}}", style));
+            }
+            Some((style, _)) => cue.style = Some(style),
+            None => {}
+        }
+        cue
+    }
+
+    fn push_plain(&mut self, src: &str) {
+        self.text.push_str(
+            &src.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&nbsp;", "\\h")
+                .replace("&lrm;", "\u{200E}")
+                .replace("&rlm;", "\u{200F}")
+                .replace("&amp;", "&"),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VTT: &str = "WEBVTT\n\nSTYLE\n::cue(.Sign) {\n  font-family: \"Verdana\";\n  color: #FFFF00;\n  font-weight: bold;\n}\n\nNOTE a comment\n\n1\n00:00:01.000 --> 00:00:02.500 line:10% align:start\n<v Alice>Hello <i>there</i> &amp; welcome\n\n00:00:02.000 --> 00:00:03.000\n<c.Sign>WARNING</c>\n\n00:00:04.000 --> 00:00:05.000\nplain <c.Sign>sign</c> text\n";
+
+    #[test]
+    fn test_from_webvtt() -> crate::Result<()> {
+        let file = File::from_webvtt(VTT)?;
+        let sign = file.styles.get("Sign").unwrap();
+        assert_eq!(sign.get(StyleFormat::Fontname), Some(&"Verdana".into()));
+        assert_eq!(
+            sign.get(StyleFormat::PrimaryColour),
+            Some(&Color::new(255, 255, 0, 0).into())
+        );
+        assert_eq!(sign.get(StyleFormat::Bold), Some(&(-1).into()));
+        assert_eq!(file.events.len(), 3);
+        let event = &file.events[0];
+        assert_eq!(event.get(EventFormat::Name), Some(&"Alice".into()));
+        assert_eq!(
+            event.get(EventFormat::Text).unwrap().to_string(),
+            "{\\an7}Hello {\\i1}there{\\i0} & welcome"
+        );
+        let event = &file.events[1];
+        assert_eq!(event.get(EventFormat::Style), Some(&"Sign".into()));
+        assert_eq!(event.get(EventFormat::Text).unwrap().to_string(), "WARNING");
+        assert_eq!(
+            file.events[2].get(EventFormat::Text).unwrap().to_string(),
+            "plain {\\rSign}sign{\\r} text"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_webvtt_literal_angle_bracket() -> crate::Result<()> {
+        let file = File::from_webvtt("WEBVTT\n\n00:01.000 --> 00:02.000\na < b\n")?;
+        assert_eq!(
+            file.events[0].get(EventFormat::Text).unwrap().to_string(),
+            "a < b"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_webvtt() -> crate::Result<()> {
+        let mut file = File::from_webvtt(VTT)?;
+        file.script.set_play_res_x(1920);
+        file.script.set_play_res_y(1080);
+        let mut comment = file.events[0].clone();
+        comment.set_event_type(EventType::Comment);
+        file.events.push(comment);
+        let vtt = file.to_webvtt();
+        assert!(vtt.starts_with("WEBVTT\n\nSTYLE\n::cue(.Default) {\n  font-family: \"Arial\";\n  color: #FFFFFF;\n  text-shadow: #000000 -2px -2px 0,"));
+        assert!(vtt.contains(
+            "::cue(.Sign) {\n  font-family: \"Verdana\";\n  color: #FFFF00;\n  font-weight: bold;\n"
+        ));
+        assert!(vtt.contains(
+            "1\n00:00:01.000 --> 00:00:02.500 line:0.93%,start position:0.52%,line-left align:start\n\
+             <v Alice><c.Default>Hello <i>there</i> &amp; welcome</c>\n\n"
+        ));
+        assert!(vtt.contains(
+            "2\n00:00:02.000 --> 00:00:03.000 line:99.07%,end position:50%,center align:center\n\
+             <c.Sign>WARNING</c>\n\n"
+        ));
+        assert!(vtt.contains("<c.Default>plain <c.Sign>sign</c> text</c>\n\n"));
+        assert!(!vtt.contains("4\n"));
+        let reparsed = File::from_webvtt(&vtt)?;
+        assert_eq!(reparsed.events.len(), 3);
+        for (reparsed, event) in reparsed.events.iter().zip(file.events.iter()) {
+            assert_eq!(reparsed, event);
+        }
+
+        let mut style = file.styles.get("Sign").unwrap().clone();
+        style.set(StyleFormat::Name, "Sign Top");
+        file.styles.add(style)?;
+        file.events[2].set(
+            EventFormat::Text,
+            Text::parse("plain {\\rSign Top}sign{\\r} text")?,
+        );
+        let vtt = file.to_webvtt();
+        assert!(vtt.contains("::cue(.Sign_Top) {\n"));
+        assert!(vtt.contains("<c.Default>plain <c.Sign_Top>sign</c> text</c>\n\n"));
+        let reparsed = File::from_webvtt(&vtt)?;
+        assert!(reparsed.styles.get("Sign_Top").is_some());
+        assert_eq!(
+            reparsed.events[2]
+                .get(EventFormat::Text)
+                .unwrap()
+                .to_string(),
+            "plain {\\rSign_Top}sign{\\r} text"
+        );
+        Ok(())
+    }
+}